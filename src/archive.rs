@@ -0,0 +1,114 @@
+//! A minimal writer for ZIP archives.
+//!
+//! The entries are stored without compression, as most of them are PNG and PDF files,
+//! which are compressed already.
+
+use color_eyre::eyre::{eyre, Result};
+
+/// The DOS date of the entries, 1980-01-01, so that archives of the same renders are identical.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Marks the entry names as UTF-8.
+const FLAGS: u16 = 1 << 11;
+
+/// The ZIP version 2.0, which is sufficient for stored entries.
+const VERSION: u16 = 20;
+
+pub(crate) struct ZipWriter {
+    data: Vec<u8>,
+    entries: Vec<Entry>,
+}
+
+/// The information about an entry repeated in the central directory.
+struct Entry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+impl ZipWriter {
+    pub(crate) fn new() -> ZipWriter {
+        ZipWriter {
+            data: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Appends a file to the archive.
+    pub(crate) fn add(&mut self, name: &str, content: &[u8]) -> Result<()> {
+        let entry = Entry {
+            name: name.to_owned(),
+            crc: crc32fast::hash(content),
+            size: u32(content.len())?,
+            offset: u32(self.data.len())?,
+        };
+        self.u32(0x04034b50);
+        self.u16(VERSION);
+        self.header(&entry)?;
+        self.u16(0); // Extra field length.
+        self.data.extend_from_slice(entry.name.as_bytes());
+        self.data.extend_from_slice(content);
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Writes the central directory and returns the archive.
+    pub(crate) fn finish(mut self) -> Result<Vec<u8>> {
+        let start = u32(self.data.len())?;
+        let entries = std::mem::take(&mut self.entries);
+        for entry in &entries {
+            self.u32(0x02014b50);
+            self.u16(VERSION); // Version made by.
+            self.u16(VERSION);
+            self.header(entry)?;
+            self.u16(0); // Extra field length.
+            self.u16(0); // Comment length.
+            self.u16(0); // Disk number.
+            self.u16(0); // Internal attributes.
+            self.u32(0); // External attributes.
+            self.u32(entry.offset);
+            self.data.extend_from_slice(entry.name.as_bytes());
+        }
+        let size = u32(self.data.len())? - start;
+        let count = u16::try_from(entries.len()).map_err(|_| eyre!("too many archive entries"))?;
+        self.u32(0x06054b50);
+        self.u16(0); // Number of this disk.
+        self.u16(0); // Disk of the central directory.
+        self.u16(count);
+        self.u16(count);
+        self.u32(size);
+        self.u32(start);
+        self.u16(0); // Comment length.
+        Ok(self.data)
+    }
+
+    /// Writes the fields shared between the local header and the central directory,
+    /// from the flags up to the name length.
+    fn header(&mut self, entry: &Entry) -> Result<()> {
+        self.u16(FLAGS);
+        self.u16(0); // Stored without compression.
+        self.u16(0); // Time.
+        self.u16(DOS_DATE);
+        self.u32(entry.crc);
+        self.u32(entry.size); // Compressed size.
+        self.u32(entry.size);
+        let name_len =
+            u16::try_from(entry.name.len()).map_err(|_| eyre!("archive entry name too long"))?;
+        self.u16(name_len);
+        Ok(())
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Converts a size or offset, which are limited to 4 GiB without the ZIP64 extension.
+fn u32(value: usize) -> Result<u32> {
+    u32::try_from(value).map_err(|_| eyre!("archive exceeds the maximum size of 4 GiB"))
+}
@@ -0,0 +1,160 @@
+//! `--autocrop`, which trims the margins of uniform background around the pages and specimens
+//! once every font is rendered.
+//!
+//! Each page is trimmed to the content of that page across all fonts, and each specimen to
+//! that of its kind, so that the renders of different fonts stay aligned at their top left
+//! corner when they are compared or shown in turn.
+
+use std::collections::HashMap;
+
+use color_eyre::eyre::{eyre, Context, Result};
+use rayon::prelude::*;
+use tiny_skia::{IntRect, Pixmap};
+use typst::foundations::Bytes;
+
+use crate::{
+    memory::MemoryLimit, raster, specimen::SpecimenKind, world::FileData, Image, Output, Render,
+};
+
+/// A page of the document, or of a specimen.
+type PageKey = (Option<SpecimenKind>, usize);
+
+/// The bounds of content relative to a page, as left, top, right and bottom.
+type Bounds = (i32, i32, i32, i32);
+
+/// Trims the pages and specimens of the renders to their content across all renders, leaving
+/// 8pt of the background around it.
+///
+/// The magnified inset of `--zoom` is kept to the right of the pages.
+pub(crate) fn apply(
+    variants: &mut [Render],
+    ppi: f32,
+    inset: bool,
+    memory: Option<&MemoryLimit>,
+) -> Result<()> {
+    let margin = (ppi * 8.0 / 72.0).round() as i32;
+    let contents = variants
+        .par_iter()
+        .map(|render| {
+            let mut contents = Vec::new();
+            for (kind, image) in images(render) {
+                let pixmap = decode(image)?;
+                for (n, &area) in image.pages.iter().enumerate() {
+                    if let Some(content) = raster::content(&pixmap, area) {
+                        contents.push(((kind, n), content));
+                    }
+                }
+            }
+            Ok(contents)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut union: HashMap<PageKey, Bounds> = HashMap::new();
+    for (key, content) in contents.into_iter().flatten() {
+        let bounds = union.entry(key).or_insert((
+            content.left(),
+            content.top(),
+            content.right(),
+            content.bottom(),
+        ));
+        *bounds = (
+            bounds.0.min(content.left()),
+            bounds.1.min(content.top()),
+            bounds.2.max(content.right()),
+            bounds.3.max(content.bottom()),
+        );
+    }
+
+    variants.par_iter_mut().try_for_each(|render| {
+        if let Output::Image(image) = &mut render.output {
+            *image = trim(image, None, &union, margin, inset)?.offload(memory)?;
+        }
+        for (kind, image) in &mut render.specimens {
+            *image = trim(image, Some(*kind), &union, margin, false)?.offload(memory)?;
+        }
+        Ok(())
+    })
+}
+
+/// The images of a render with the kind of specimen they show, if any.
+fn images(render: &Render) -> impl Iterator<Item = (Option<SpecimenKind>, &Image)> {
+    let document = match &render.output {
+        Output::Image(image) => Some((None, image)),
+        Output::Pages(_) => None,
+    };
+    document.into_iter().chain(
+        render
+            .specimens
+            .iter()
+            .map(|(kind, image)| (Some(*kind), image)),
+    )
+}
+
+/// Trims the pages of an image to the bounds of their content, stacking them anew.
+///
+/// With `inset`, the part of the image to the right of the pages is kept next to them.
+fn trim(
+    image: &Image,
+    kind: Option<SpecimenKind>,
+    union: &HashMap<PageKey, Bounds>,
+    margin: i32,
+    inset: bool,
+) -> Result<Image> {
+    let pixmap = decode(image)?;
+    let pages: Vec<Pixmap> = image
+        .pages
+        .iter()
+        .enumerate()
+        .map(|(n, &page)| {
+            let area = union
+                .get(&(kind, n))
+                .and_then(|&(left, top, right, bottom)| {
+                    IntRect::from_ltrb(
+                        page.left() + (left - margin).max(0),
+                        page.top() + (top - margin).max(0),
+                        page.left() + (right + margin).min(page.width() as i32),
+                        page.top() + (bottom + margin).min(page.height() as i32),
+                    )
+                });
+            raster::crop(&pixmap, area.or(Some(page)))
+        })
+        .collect();
+    // The pages are stacked with the same padding as before.
+    let padding = image
+        .pages
+        .first()
+        .map_or(0, |page| page.left().max(0) as u32);
+    let (mut trimmed, areas) = raster::stack(&pages, padding);
+    let right = image.pages.iter().map(|page| page.right()).max();
+    if let Some(right) = right.filter(|&right| inset && right < pixmap.width() as i32) {
+        // The inset ends where its padding does, which may be far above the pages' end.
+        let side = IntRect::from_xywh(right, 0, pixmap.width() - right as u32, pixmap.height());
+        let height = side
+            .and_then(|side| raster::content(&pixmap, side))
+            .map_or(pixmap.height(), |content| {
+                (content.bottom() as u32 + padding).min(pixmap.height())
+            });
+        let side = raster::crop(
+            &pixmap,
+            IntRect::from_xywh(right, 0, pixmap.width() - right as u32, height),
+        );
+        trimmed = raster::beside(&trimmed, &side, padding);
+    }
+    let png = trimmed
+        .encode_png()
+        .map_err(|err| eyre!("failed to encode trimmed render: {err}"))?;
+    Ok(Image {
+        data: FileData::Bytes(Bytes::from(png)),
+        width: trimmed.width(),
+        height: trimmed.height(),
+        pages: areas,
+    })
+}
+
+/// Decodes an image of a render.
+fn decode(image: &Image) -> Result<Pixmap> {
+    let bytes = image
+        .data
+        .bytes()
+        .map_err(|err| eyre!("failed to read render: {err}"))?;
+    Pixmap::decode_png(&bytes).wrap_err("failed to decode render")
+}
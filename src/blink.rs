@@ -0,0 +1,118 @@
+//! A PDF for blink comparisons, in which each page of the document follows itself in every
+//! font at the same position, so that paging through it flips between the fonts.
+
+use std::{fmt::Write, path::PathBuf, time::Instant};
+
+use color_eyre::eyre::{eyre, Result};
+use rayon::prelude::*;
+use typst::{
+    eval::Tracer,
+    foundations::{Bytes, Smart},
+};
+
+use crate::{
+    diff,
+    logging::{Event, Level},
+    metrics, raster, source_date, specimen,
+    world::{FileData, SystemWorld},
+    Args, Render,
+};
+
+/// Compiles the pages of the renders into a blink comparator and returns it as PDF.
+///
+/// All pages have the size of the largest page, with the page of the document at the same
+/// position below a label of its font.
+pub(crate) fn render_blink(
+    world: &mut SystemWorld,
+    args: &Args,
+    variants: Vec<Render>,
+) -> Result<Vec<u8>> {
+    let start = Instant::now();
+    let map_pixels = |x| (x as f32) / args.ppi * 72.0;
+
+    // The pages of each render, cut out of its image.
+    let pages: Vec<Vec<(f32, f32, Bytes)>> = variants
+        .par_iter()
+        .map(|render| {
+            let image = metrics::decode(render)?
+                .ok_or_else(|| eyre!("--blink needs rasterized renders, not --vector"))?;
+            diff::page_areas(render)
+                .iter()
+                .map(|&area| {
+                    let page = raster::crop(&image, Some(area));
+                    Ok((
+                        map_pixels(page.width()),
+                        map_pixels(page.height()),
+                        Bytes::from(page.encode_png()?),
+                    ))
+                })
+                .collect()
+        })
+        .collect::<Result<_>>()?;
+    let count = pages.iter().map(Vec::len).max().unwrap_or(0);
+    let sizes = pages
+        .iter()
+        .flatten()
+        .map(|&(width, height, _)| (width, height));
+    let (width, height) = sizes.fold((0.0f32, 0.0f32), |(w, h), (width, height)| {
+        (w.max(width), h.max(height))
+    });
+
+    let mut main = format!(
+        r#"
+        #let margin = 1cm
+        #let label-height = 2em
+        #set document(author: "{pkg_name}")
+        #set page(
+            width: {width}pt + 2 * margin,
+            height: {height}pt + label-height + 2 * margin,
+            margin: margin,
+        )
+        #set text(size: 12pt)
+        "#,
+        pkg_name = env!("CARGO_PKG_NAME"),
+    );
+    let mut files: Vec<(PathBuf, FileData)> = Vec::new();
+    for p in 0..count {
+        let mut first = true;
+        for (n, render) in variants.iter().enumerate() {
+            let Some((page_width, page_height, png)) = pages[n].get(p) else {
+                continue;
+            };
+            let name = format!("{} {:?}", render.font.family, render.font.variant);
+            let file = format!("page-{p}-{n}.png");
+            write!(
+                main,
+                r#"
+                #page[
+                    #if {first} [
+                        // Only for the outline and bookmarks.
+                        #place(hide[= Page {number}])
+                    ]
+                    #place[*Page {number}* #h(1em) #{name}]
+                    #place(dy: label-height, image(width: {page_width}pt, height: {page_height}pt, "{file}"))
+                ]
+                "#,
+                number = p + 1,
+                name = specimen::string(&name),
+            )?;
+            files.push((PathBuf::from(file), FileData::Bytes(png.clone())));
+            first = false;
+        }
+    }
+    world.replace_files(main, files);
+
+    let mut tracer = Tracer::new();
+    let document = typst::compile(world, &mut tracer)
+        .map_err(|diag| eyre!("failed to compile blink comparator: {diag:?}"))?;
+    let pdf = typst_pdf::pdf(&document, Smart::Auto, source_date::pdf_timestamp());
+    Event::new(
+        Level::Debug,
+        "collection-finish",
+        format!("Compiled blink comparator in {:.2?}", start.elapsed()),
+    )
+    .field("pages", document.pages.len())
+    .duration("duration", start.elapsed())
+    .emit();
+    Ok(pdf)
+}
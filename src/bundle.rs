@@ -0,0 +1,140 @@
+//! A ZIP archive containing the collection together with the individual renders,
+//! their metadata and an HTML index.
+
+use std::{fmt::Write, path::Path};
+
+use color_eyre::eyre::{eyre, Result};
+use serde_json::{json, Value};
+
+use crate::{
+    archive::ZipWriter, html::escape, locale::Locale, render_collection, style_name,
+    world::FileData, world::SystemWorld, write_output, Args, Output, Render, Skipped,
+};
+
+/// Write the archive to `output`.
+pub(crate) fn write_zip(
+    world: &mut SystemWorld,
+    args: &Args,
+    variants: Vec<Render>,
+    skipped: &[Skipped],
+    partial: bool,
+    output: &Path,
+) -> Result<()> {
+    let mut files: Vec<(String, FileData)> = Vec::new();
+    let mut entries = Vec::new();
+    let mut html = String::new();
+    for (n, render) in variants.iter().enumerate() {
+        let stem = render.file_stem(n);
+        let variant = render.font.variant;
+        write!(
+            html,
+            "<section>\n<h2>{family}</h2>\n<h3>{variant:?}</h3>\n",
+            family = escape(&render.font.family),
+        )?;
+        if let Some(note) = render.note(Locale::En) {
+            writeln!(html, "<p>{}</p>", escape(&note))?;
+        }
+
+        let mut entry = json!({
+            "family": render.font.family,
+            "style": style_name(variant.style),
+            "weight": variant.weight.to_number(),
+            "stretch": variant.stretch.to_ratio().get(),
+            "pages": render.metrics.pages,
+            "duration": render.metrics.duration.as_secs_f64(),
+            "coverage": render.metrics.coverage.ratio(),
+            "missing": render.metrics.coverage.missing_chars.iter().collect::<String>(),
+            "similarity": render.metrics.similarity,
+            "aliases": render.alias_names(),
+            "similar": render
+                .similar
+                .iter()
+                .map(|(font, similarity)| json!({
+                    "weight": font.variant.weight.to_number(),
+                    "style": style_name(font.variant.style),
+                    "stretch": font.variant.stretch.to_ratio().get(),
+                    "similarity": similarity,
+                }))
+                .collect::<Vec<_>>(),
+        });
+        match &render.output {
+            Output::Image(image) => {
+                let name = format!("{stem}.png");
+                writeln!(html, "<img src=\"{name}\" alt=\"{variant:?}\">")?;
+                entry["render"] = json!(name);
+                entry["width"] = json!(image.width);
+                entry["height"] = json!(image.height);
+                files.push((name, image.data.clone()));
+            }
+            Output::Pages(_) => {
+                writeln!(html, "<p>Included as vector pages in the collection.</p>")?;
+                entry["render"] = Value::Null;
+            }
+        }
+
+        let mut specimens = serde_json::Map::new();
+        for (kind, image) in &render.specimens {
+            let kind_name = kind.name();
+            let name = format!("{stem}-{kind_name}.png");
+            writeln!(
+                html,
+                "<h4>{title}</h4>\n<img src=\"{name}\" alt=\"{title}\">",
+                title = kind.title(),
+            )?;
+            specimens.insert(kind_name, json!(name));
+            files.push((name, image.data.clone()));
+        }
+        entry["specimens"] = Value::Object(specimens);
+        entries.push(entry);
+        writeln!(html, "</section>")?;
+    }
+
+    let metadata = json!({
+        "generator": concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")),
+        "input": args.input().display().to_string(),
+        "partial": partial,
+        "ppi": args.ppi,
+        "collection": "variants.pdf",
+        "variants": entries,
+    });
+    let index = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Font comparison of {input}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+img {{ display: block; max-width: 100%; border: 1px solid #ccc; }}
+</style>
+</head>
+<body>
+<h1>Font comparison of {input}</h1>
+<p><a href="variants.pdf">Collection</a> · <a href="metadata.json">Metadata</a></p>
+{partial}{html}</body>
+</html>
+"#,
+        input = escape(&args.input().display().to_string()),
+        partial = if partial {
+            "<p><strong>This comparison is partial.</strong> Rendering was interrupted.</p>\n"
+        } else {
+            ""
+        },
+    );
+
+    let pdf = render_collection(world, args, variants, skipped, partial)?;
+    let mut zip = ZipWriter::new();
+    zip.add("variants.pdf", &pdf)?;
+    for (name, data) in files {
+        let bytes = data
+            .bytes()
+            .map_err(|err| eyre!("failed to read render {name}: {err}"))?;
+        zip.add(&name, &bytes)?;
+    }
+    zip.add(
+        "metadata.json",
+        serde_json::to_string_pretty(&metadata)?.as_bytes(),
+    )?;
+    zip.add("index.html", index.as_bytes())?;
+    write_output(output, &zip.finish()?)
+}
@@ -0,0 +1,180 @@
+//! The `cache` subcommand, inspecting and clearing the files kept on disk between runs.
+//!
+//! These are the renders moved to disk with `--max-memory`, one directory per run.
+//! A run removes its directory when it finishes, but one that is killed leaves it behind.
+//! The font index is shared between runs and kept until it is cleaned.
+
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::{Parser, Subcommand};
+use color_eyre::eyre::{Context, Result};
+
+use crate::format_size;
+
+/// Inspect and clear the files typst-font-compare keeps on disk.
+#[derive(Parser)]
+#[clap(bin_name = "typst-font-compare cache")]
+struct CacheArgs {
+    #[clap(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// Show where the files are kept and how much space each run takes.
+    Info,
+    /// Remove the font index and the files of all runs, including ones that are still running.
+    Clean,
+    /// Remove the files left behind by runs that are no longer running.
+    Prune,
+}
+
+/// The directory containing the directories of each run.
+pub(crate) fn dir() -> PathBuf {
+    std::env::temp_dir().join("typst-font-compare")
+}
+
+/// The file of the font index kept between runs, see [`crate::font_index`].
+///
+/// Unlike the files of the runs, it is kept in the user's cache directory, so that it
+/// survives reboots.
+pub(crate) fn font_index_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("typst-font-compare")
+        .join("fonts.json")
+}
+
+/// The directory in which the current run keeps its files.
+pub(crate) fn run_dir() -> PathBuf {
+    dir().join(std::process::id().to_string())
+}
+
+/// Runs the subcommand with the arguments following `cache`.
+pub(crate) fn run(args: impl IntoIterator<Item = OsString>) -> Result<()> {
+    let args = CacheArgs::parse_from(args);
+    let dir = dir();
+    let runs = runs(&dir)?;
+    match args.action {
+        Action::Info => {
+            let font_index = font_index_path();
+            if let Ok(metadata) = fs::metadata(&font_index) {
+                println!(
+                    "Font index: {} ({})",
+                    font_index.display(),
+                    format_size(metadata.len() as usize),
+                );
+            }
+            println!("Cache directory: {}", dir.display());
+            if runs.is_empty() {
+                println!("No files are kept.");
+                return Ok(());
+            }
+            for run in &runs {
+                println!(
+                    "run {pid}: {size} ({state})",
+                    pid = run.pid,
+                    size = format_size(run.size),
+                    state = if run.running { "running" } else { "finished" },
+                );
+            }
+            let total = runs.iter().map(|run| run.size).sum();
+            println!("Total: {}", format_size(total));
+        }
+        Action::Clean | Action::Prune => {
+            let prune = matches!(args.action, Action::Prune);
+            let mut removed = 0;
+            if !prune {
+                let font_index = font_index_path();
+                if let Ok(metadata) = fs::metadata(&font_index) {
+                    fs::remove_file(&font_index)
+                        .wrap_err_with(|| format!("failed to remove {}", font_index.display()))?;
+                    removed += metadata.len() as usize;
+                }
+            }
+            for run in runs.iter().filter(|run| !(prune && run.running)) {
+                fs::remove_dir_all(&run.path)
+                    .wrap_err_with(|| format!("failed to remove {}", run.path.display()))?;
+                removed += run.size;
+            }
+            println!("Removed {}.", format_size(removed));
+        }
+    }
+    Ok(())
+}
+
+/// The files kept by a run.
+struct Run {
+    pid: u32,
+    path: PathBuf,
+    size: usize,
+    running: bool,
+}
+
+/// Lists the directories of the runs, ignoring other files.
+fn runs(dir: &Path) -> Result<Vec<Run>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).wrap_err_with(|| format!("failed to read {}", dir.display())),
+    };
+    let mut runs = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        else {
+            continue;
+        };
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        runs.push(Run {
+            pid,
+            size: size(&entry.path()),
+            path: entry.path(),
+            running: is_running(pid),
+        });
+    }
+    runs.sort_by_key(|run| run.pid);
+    Ok(runs)
+}
+
+/// The total size of the files in a directory, ignoring ones that can't be read.
+fn size(dir: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => size(&entry.path()),
+            _ => entry
+                .metadata()
+                .map_or(0, |metadata| metadata.len() as usize),
+        })
+        .sum()
+}
+
+/// Whether a process with this ID is running.
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Sends no signal, but checks whether the process exists. It may belong to another user.
+    let exists = unsafe { libc::kill(pid, 0) } == 0;
+    exists || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Whether a process with this ID is running, which can't be checked on this platform.
+#[cfg(not(unix))]
+fn is_running(pid: u32) -> bool {
+    pid == std::process::id()
+}
@@ -0,0 +1,44 @@
+// The layout of the collection written by typst-font-compare.
+//
+// Print it with `--dump-template`, and use a modified copy with `--template`. The slots in
+// double braces are filled in before compiling:
+//
+// - margin, text-size, spacing: the margin of the pages, the size of the labels, and the space
+//   between the renders of a variant, from `--layout`, `--collection-margin` and `--label-size`
+// - page-size: the size arguments of `page`, from `--paper` and `--landscape`
+// - numbering, first-page: the page numbering, from `--page-numbering` and `--first-page-number`
+// - lang: the language of the labels, from `--locale`
+// - background, foreground, accent: the colors of the `--theme`
+// - render: defines `render(path, width, height)`, which shows a render, scaled down to fit
+//   the paper if needed
+// - fonts, created-using: the title of the outline and the credit below it
+// - tool, version, homepage: the name, version and homepage of typst-font-compare
+// - partial: the note that the run was interrupted, if it was
+// - overview: the page of thumbnails linking to the variants
+// - ranking: the fonts ranked by their similarity to the `--baseline`
+// - variants: the pages of the variants, which use `margin`, `spacing` and `render`
+// - appendices: the font files, the excluded fonts and the missing characters
+
+#let margin = {{margin}}
+#let text-size = {{text-size}}
+#let spacing = {{spacing}}
+
+#set document(author: "{{tool}}")
+#set page({{page-size}}, margin: margin, numbering: {{numbering}}, footer: none, fill: {{background}})
+#counter(page).update({{first-page}})
+#set text(size: text-size, lang: "{{lang}}", fill: {{foreground}})
+#show link: set text(fill: {{accent}})
+#show heading: set text(fill: {{accent}})
+{{render}}
+#set heading(numbering: "1.1")
+#show heading: set text(size: text-size)
+
+#metadata(none) <overview>
+#outline(indent: auto, title: [{{fonts}}])
+
+{{created-using}} #link("{{homepage}}")[`{{tool}} v{{version}}` ({{homepage}})]. \
+{{partial}}
+{{overview}}
+{{ranking}}
+{{variants}}
+{{appendices}}
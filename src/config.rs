@@ -0,0 +1,268 @@
+//! Layering of the configuration sources.
+//!
+//! From lowest to highest priority: defaults, preset, configuration file, environment,
+//! command line. Values of the configuration file and preset are turned into additional
+//! command-line arguments for every option that was not given with a higher priority.
+
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::{
+    parser::ValueSource, Arg, ArgAction, ArgMatches, Command, CommandFactory, FromArgMatches,
+};
+use color_eyre::eyre::{eyre, Context, Result};
+
+use crate::{Args, Preset};
+
+/// The IDs of the arguments that steer the configuration itself and thus can't be configured,
+/// and of `--post-render`, so that a configuration file in the current directory can't run
+/// commands.
+const UNCONFIGURABLE: [&str; 5] = [
+    "config",
+    "print_config",
+    "dump_template",
+    "help",
+    "post_render",
+];
+
+/// The name of the configuration file looked up in the current and user configuration directory.
+const FILE_NAME: &str = "typst-font-compare.toml";
+
+/// The parsed arguments together with the layer each of them was taken from.
+pub(crate) struct Config {
+    pub(crate) args: Args,
+    file: Option<PathBuf>,
+    matches: ArgMatches,
+    from_file: HashSet<String>,
+    from_preset: HashSet<String>,
+}
+
+impl Config {
+    /// Parses the command line, starting with the program name, and layers it on top of the
+    /// configuration file and preset.
+    pub(crate) fn load(cli: Vec<OsString>) -> Result<Config> {
+        let command = Args::command();
+        let matches = command.clone().get_matches_from(&cli);
+
+        let file = match matches.get_one::<PathBuf>("config") {
+            Some(path) => Some(path.clone()),
+            None => default_file(),
+        };
+        let mut extra = Vec::new();
+        let mut from_file = HashSet::new();
+        if let Some(path) = &file {
+            let table = read_file(path)?;
+            for (key, value) in &table {
+                let arg = command
+                    .get_arguments()
+                    .filter(|arg| !UNCONFIGURABLE.contains(&arg.get_id().as_str()))
+                    .find(|arg| arg.get_long() == Some(key))
+                    .ok_or_else(|| {
+                        eyre!(
+                            "unknown key `{key}` in configuration file {}",
+                            path.display()
+                        )
+                    })?;
+                let id = arg.get_id().as_str();
+                if is_explicit(&matches, id) {
+                    continue;
+                }
+                extra.extend(to_args(key, value, arg).wrap_err_with(|| {
+                    format!(
+                        "invalid value for `{key}` in configuration file {}",
+                        path.display()
+                    )
+                })?);
+                from_file.insert(id.to_owned());
+            }
+        }
+        let matches = reparse(&command, &cli, &extra);
+
+        let mut from_preset = HashSet::new();
+        if let Some(preset) = matches.get_one::<Preset>("preset") {
+            for &(long, value) in preset.defaults() {
+                let arg = find_arg(&command, long);
+                let id = arg.get_id().to_string();
+                // Leave out the values that would conflict with ones given explicitly, like
+                // the overview of the quick preset with `--vector`.
+                if is_explicit(&matches, &id)
+                    || command
+                        .get_arg_conflicts_with(arg)
+                        .iter()
+                        .any(|other| is_explicit(&matches, other.get_id().as_str()))
+                {
+                    continue;
+                }
+                if arg.get_action().takes_values() {
+                    extra.push(format!("--{long}={value}").into());
+                } else {
+                    extra.push(format!("--{long}").into());
+                }
+                from_preset.insert(id);
+            }
+        }
+        let matches = reparse(&command, &cli, &extra);
+
+        Ok(Config {
+            args: Args::from_arg_matches(&matches)?,
+            file,
+            matches,
+            from_file,
+            from_preset,
+        })
+    }
+
+    /// Prints the effective configuration in the format of the configuration file.
+    pub(crate) fn print(&self) {
+        match &self.file {
+            Some(path) => println!("# Configuration file: {}", path.display()),
+            None => println!("# No configuration file"),
+        }
+        for arg in Args::command().get_arguments() {
+            let id = arg.get_id().as_str();
+            let Some(long) = arg.get_long() else {
+                continue;
+            };
+            if UNCONFIGURABLE.contains(&id) {
+                continue;
+            }
+
+            let source = self.source(id);
+            let mut values = self
+                .matches
+                .get_raw(id)
+                .into_iter()
+                .flatten()
+                .map(|raw| toml::Value::String(raw.to_string_lossy().into_owned()));
+            let value = match arg.get_action() {
+                ArgAction::SetTrue => toml::Value::Boolean(self.matches.get_flag(id)),
+                ArgAction::Count => toml::Value::Integer(self.matches.get_count(id).into()),
+                ArgAction::Append => match values.collect::<Vec<_>>() {
+                    // An option with optional values, like `--charmap`, given without any.
+                    values
+                        if values.is_empty()
+                            && arg.get_num_args().is_some_and(|n| n.min_values() == 0)
+                            && self.matches.value_source(id) == Some(ValueSource::CommandLine) =>
+                    {
+                        toml::Value::Boolean(true)
+                    }
+                    values => toml::Value::Array(values),
+                },
+                _ => match values.next() {
+                    Some(value) => value,
+                    // An option whose value is optional, like `--open`, given without one.
+                    None if self.matches.value_source(id).is_some() => toml::Value::Boolean(true),
+                    None => {
+                        println!("# {long} is not set");
+                        continue;
+                    }
+                },
+            };
+            println!("{long} = {value}  # {source}");
+        }
+    }
+
+    /// Describes the layer the value of an argument was taken from.
+    fn source(&self, id: &str) -> &'static str {
+        if self.from_file.contains(id) {
+            "configuration file"
+        } else if self.from_preset.contains(id) {
+            "preset"
+        } else {
+            match self.matches.value_source(id) {
+                Some(ValueSource::CommandLine) => "command line",
+                Some(ValueSource::EnvVariable) => "environment",
+                _ => "default",
+            }
+        }
+    }
+}
+
+/// Looks for a configuration file in the current and then in the user configuration directory.
+fn default_file() -> Option<PathBuf> {
+    [
+        Some(PathBuf::from(FILE_NAME)),
+        dirs::config_dir().map(|dir| dir.join(FILE_NAME)),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|path| path.is_file())
+}
+
+fn read_file(path: &Path) -> Result<toml::Table> {
+    fs::read_to_string(path)
+        .wrap_err_with(|| format!("failed to read configuration file {}", path.display()))?
+        .parse()
+        .wrap_err_with(|| format!("failed to parse configuration file {}", path.display()))
+}
+
+/// Returns the argument with the given long name.
+fn find_arg<'a>(command: &'a Command, long: &str) -> &'a Arg {
+    command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(long))
+        .expect("argument with long name must exist")
+}
+
+/// Whether an argument was given on the command line or through the environment.
+fn is_explicit(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    )
+}
+
+/// Turns a configuration value into the equivalent command-line arguments.
+///
+/// A boolean enables or disables an option whose value is optional, like `--open`.
+fn to_args(long: &str, value: &toml::Value, arg: &Arg) -> Result<Vec<OsString>> {
+    let action = arg.get_action();
+    let optional_value = arg
+        .get_num_args()
+        .is_some_and(|num_args| num_args.min_values() == 0);
+    let scalar = |value: &toml::Value| match value {
+        toml::Value::String(string) => Ok(string.clone()),
+        toml::Value::Integer(integer) => Ok(integer.to_string()),
+        toml::Value::Float(float) => Ok(float.to_string()),
+        _ => Err(eyre!(
+            "expected a string or number, found {}",
+            value.type_str()
+        )),
+    };
+    match (value, action) {
+        (toml::Value::Boolean(true), ArgAction::SetTrue) => Ok(vec![format!("--{long}").into()]),
+        (toml::Value::Boolean(false), ArgAction::SetTrue) => Ok(vec![]),
+        (_, ArgAction::SetTrue) => Err(eyre!("expected a boolean, found {}", value.type_str())),
+        (toml::Value::Boolean(true), _) if optional_value => Ok(vec![format!("--{long}").into()]),
+        (toml::Value::Boolean(false), _) if optional_value => Ok(vec![]),
+        (toml::Value::Integer(count), ArgAction::Count) => {
+            Ok(vec![format!("--{long}").into(); (*count).max(0) as usize])
+        }
+        (_, ArgAction::Count) => Err(eyre!("expected an integer, found {}", value.type_str())),
+        // Options taking several values at once, like `--only A B`.
+        (toml::Value::Array(values), _)
+            if arg
+                .get_num_args()
+                .is_some_and(|num_args| num_args.min_values() > 1) =>
+        {
+            std::iter::once(Ok(format!("--{long}").into()))
+                .chain(values.iter().map(|value| Ok(scalar(value)?.into())))
+                .collect()
+        }
+        (toml::Value::Array(values), ArgAction::Append) => values
+            .iter()
+            .map(|value| Ok(format!("--{long}={}", scalar(value)?).into()))
+            .collect(),
+        (value, _) => Ok(vec![format!("--{long}={}", scalar(value)?).into()]),
+    }
+}
+
+/// Parses the command line again with additional arguments inserted in front of it.
+fn reparse(command: &Command, cli: &[OsString], extra: &[OsString]) -> ArgMatches {
+    let args = cli[..1].iter().chain(extra).chain(&cli[1..]);
+    command.clone().get_matches_from(args)
+}
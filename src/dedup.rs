@@ -0,0 +1,145 @@
+//! Collapsing of fonts that render identically, e.g. families that are aliases of each other,
+//! and of variants that look nearly the same.
+
+use std::collections::HashMap;
+
+use color_eyre::eyre::{eyre, Result};
+use rayon::prelude::*;
+use tiny_skia::Pixmap;
+use typst::util::hash128;
+
+use crate::{
+    logging::{Event, Level},
+    metrics, raster,
+    world::FileData,
+    Output, Render,
+};
+
+/// Merge each render into the first render that is pixel-identical to it, including its
+/// specimens, and list the merged fonts as aliases of the kept one.
+///
+/// Vector pages are never merged.
+pub(crate) fn deduplicate(variants: Vec<Render>) -> Result<Vec<Render>> {
+    let hash = |data: &FileData| {
+        data.bytes()
+            .map(|bytes| hash128(&bytes))
+            .map_err(|err| eyre!("failed to read render: {err}"))
+    };
+
+    let mut seen: HashMap<Vec<u128>, usize> = HashMap::new();
+    let mut kept: Vec<Render> = Vec::with_capacity(variants.len());
+    for render in variants {
+        let Output::Image(image) = &render.output else {
+            kept.push(render);
+            continue;
+        };
+        let key = std::iter::once(&image.data)
+            .chain(render.specimens.iter().map(|(_, image)| &image.data))
+            .map(hash)
+            .collect::<Result<Vec<_>>>()?;
+        match seen.get(&key) {
+            Some(&index) => {
+                let original = &mut kept[index];
+                Event::new(
+                    Level::Debug,
+                    "duplicate",
+                    format!(
+                        "font {} {:?} renders identically to {} {:?}",
+                        render.font.family,
+                        render.font.variant,
+                        original.font.family,
+                        original.font.variant
+                    ),
+                )
+                .emit();
+                original.aliases.push(render.font);
+            }
+            None => {
+                seen.insert(key, kept.len());
+                kept.push(render);
+            }
+        }
+    }
+
+    let merged: usize = kept.iter().map(|render| render.aliases.len()).sum();
+    if merged > 0 {
+        Event::new(
+            Level::Info,
+            "duplicates",
+            format!("Merged {merged} fonts that render identically to another font"),
+        )
+        .field("merged", merged)
+        .emit();
+    }
+    Ok(kept)
+}
+
+/// Merge each variant into the first earlier variant of its family whose render is at least
+/// `threshold` similar to it, listing it as similar to the kept one.
+///
+/// Vector pages are never merged. The specimens of merged variants are dropped.
+pub(crate) fn merge_similar(variants: Vec<Render>, threshold: f64) -> Result<Vec<Render>> {
+    // The variants are sorted by family, so each family is a consecutive run.
+    let mut families: Vec<Vec<Render>> = Vec::new();
+    for render in variants {
+        match families.last_mut() {
+            Some(family) if family[0].font.family == render.font.family => family.push(render),
+            _ => families.push(vec![render]),
+        }
+    }
+
+    let mut kept = Vec::new();
+    let mut merged = 0;
+    for family in families {
+        let images = family
+            .par_iter()
+            .map(metrics::decode)
+            .collect::<Result<Vec<_>>>()?;
+        // The kept variants of the family, with their decoded images.
+        let mut originals: Vec<(Render, Option<Pixmap>)> = Vec::new();
+        for (render, image) in family.into_iter().zip(images) {
+            let closest = image.as_ref().and_then(|image| {
+                originals
+                    .par_iter()
+                    .enumerate()
+                    .filter_map(|(i, (_, original))| {
+                        Some((i, raster::similarity(original.as_ref()?, image)))
+                    })
+                    .filter(|&(_, similarity)| similarity >= threshold)
+                    .min_by_key(|&(i, _)| i)
+            });
+            let Some((i, similarity)) = closest else {
+                originals.push((render, image));
+                continue;
+            };
+            let original = &mut originals[i].0;
+            Event::new(
+                Level::Debug,
+                "similar",
+                format!(
+                    "variant {:?} of font {} looks nearly the same as {:?}, similarity {similarity:.4}",
+                    render.font.variant, render.font.family, original.font.variant
+                ),
+            )
+            .emit();
+            merged += 1;
+            original.similar.extend(
+                std::iter::once(render.font)
+                    .chain(render.aliases)
+                    .map(|font| (font, similarity)),
+            );
+        }
+        kept.extend(originals.into_iter().map(|(render, _)| render));
+    }
+
+    if merged > 0 {
+        Event::new(
+            Level::Info,
+            "similar-variants",
+            format!("Merged {merged} variants that look nearly the same as another variant"),
+        )
+        .field("merged", merged)
+        .emit();
+    }
+    Ok(kept)
+}
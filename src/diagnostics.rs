@@ -0,0 +1,295 @@
+//! Compile errors and warnings of each font in a machine-readable form.
+//!
+//! With `--retry-failed`, the file of the previous run selects the fonts to render again,
+//! and their new entries replace the old ones.
+
+use std::{
+    cmp::Ordering,
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use serde_json::{json, Value};
+use typst::{
+    diag::{Severity, SourceDiagnostic},
+    layout::Abs,
+    text::{FontBook, FontInfo},
+    World,
+};
+
+use crate::{sandbox::Sandbox, style_name, world::SystemWorld, Args, Render, SkipReason, Skipped};
+
+/// The version of the diagnostics file's schema, increased on incompatible changes.
+const SCHEMA_VERSION: u32 = 1;
+
+/// An error or warning with its location resolved.
+#[derive(Debug, Clone)]
+pub(crate) struct Diagnostic {
+    pub(crate) severity: Severity,
+    pub(crate) message: String,
+    pub(crate) hints: Vec<String>,
+    /// The path of the file within the root, or the package, starting with a slash.
+    pub(crate) file: Option<String>,
+    /// The line and column, starting at one.
+    pub(crate) position: Option<(usize, usize)>,
+}
+
+impl Diagnostic {
+    /// Resolves the location of Typst's diagnostics.
+    pub(crate) fn resolve(
+        world: &SystemWorld,
+        diagnostics: &[SourceDiagnostic],
+    ) -> Vec<Diagnostic> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let id = diagnostic.span.id();
+                let file = id.map(|id| {
+                    let path = id.vpath().as_rooted_path().display();
+                    match id.package() {
+                        Some(package) => format!("{package}{path}"),
+                        None => path.to_string(),
+                    }
+                });
+                let position = id.and_then(|id| world.source(id).ok()).and_then(|source| {
+                    let start = source.range(diagnostic.span)?.start;
+                    Some((
+                        source.byte_to_line(start)? + 1,
+                        source.byte_to_column(start)? + 1,
+                    ))
+                });
+                Diagnostic {
+                    severity: diagnostic.severity,
+                    message: diagnostic.message.to_string(),
+                    hints: diagnostic.hints.iter().map(ToString::to_string).collect(),
+                    file,
+                    position,
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "severity": match self.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            },
+            "message": self.message,
+            "hints": self.hints,
+            "file": self.file,
+            "line": self.position.map(|(line, _)| line),
+            "column": self.position.map(|(_, column)| column),
+        })
+    }
+
+    /// Reads a diagnostic written by [`Diagnostic::to_json`].
+    pub(crate) fn from_json(value: &Value) -> Option<Diagnostic> {
+        let severity = match value["severity"].as_str()? {
+            "error" => Severity::Error,
+            "warning" => Severity::Warning,
+            _ => return None,
+        };
+        let position = |key: &str| value[key].as_u64().map(|n| n as usize);
+        Some(Diagnostic {
+            severity,
+            message: value["message"].as_str()?.to_owned(),
+            hints: value["hints"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|hint| Some(hint.as_str()?.to_owned()))
+                .collect(),
+            file: value["file"].as_str().map(str::to_owned),
+            position: position("line").zip(position("column")),
+        })
+    }
+}
+
+/// Formats the diagnostic like `error: message (/main.typ:3:5)`.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message)?,
+            Severity::Warning => write!(f, "warning: {}", self.message)?,
+        }
+        match (&self.file, self.position) {
+            (Some(file), Some((line, column))) => write!(f, " ({file}:{line}:{column})")?,
+            (Some(file), None) => write!(f, " ({file})")?,
+            _ => {}
+        }
+        for hint in &self.hints {
+            write!(f, "; hint: {hint}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The errors a compilation failed with.
+#[derive(Debug)]
+pub(crate) struct CompileError(pub(crate) Vec<Diagnostic>);
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to compile")?;
+        for (i, diagnostic) in self.0.iter().enumerate() {
+            let separator = if i == 0 { ": " } else { ", " };
+            write!(f, "{separator}{diagnostic}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for CompileError {}
+
+/// Writes the diagnostics of each rendered or skipped font as JSON.
+pub(crate) fn write_diagnostics(
+    args: &Args,
+    variants: &[Render],
+    skipped: &[Skipped],
+    sandbox: Option<&Sandbox>,
+    path: &Path,
+) -> Result<()> {
+    let font = |font: &FontInfo, status: &str| {
+        json!({
+            "family": font.family,
+            "style": style_name(font.variant.style),
+            "weight": font.variant.weight.to_number(),
+            "stretch": font.variant.stretch.to_ratio().get(),
+            "status": status,
+        })
+    };
+    let mut fonts: Vec<(&FontInfo, Value)> = variants
+        .iter()
+        .map(|render| {
+            let coverage = &render.metrics.coverage;
+            let mut entry = font(&render.font, "ok");
+            entry["errors"] = json!([]);
+            entry["warnings"] = render.warnings.iter().map(Diagnostic::to_json).collect();
+            entry["coverage"] = json!(coverage.ratio());
+            entry["missing"] = coverage
+                .missing_chars
+                .iter()
+                .map(|c| Value::String(c.to_string()))
+                .collect();
+            if let Some(scaled) = &render.metrics.scaled {
+                entry["scale"] = json!(scaled.factor);
+                entry["size"] = json!(scaled.matching_size().map(Abs::to_pt));
+            }
+            (&render.font, entry)
+        })
+        .chain(skipped.iter().map(|skipped| {
+            let (status, errors) = match &skipped.reason {
+                SkipReason::Timeout => ("timeout", Vec::new()),
+                SkipReason::Error(_, diagnostics) => ("error", diagnostics.clone()),
+            };
+            let mut entry = font(&skipped.font, status);
+            entry["reason"] = json!(skipped.reason.to_string());
+            entry["errors"] = errors.iter().map(Diagnostic::to_json).collect();
+            entry["warnings"] = json!([]);
+            entry["coverage"] = Value::Null;
+            entry["missing"] = Value::Null;
+            (&skipped.font, entry)
+        }))
+        .collect();
+    fonts.sort_by(|(a, _), (b, _)| a.family.cmp(&b.family).then(a.variant.cmp(&b.variant)));
+    let mut fonts: Vec<Value> = fonts.into_iter().map(|(_, entry)| entry).collect();
+    if args.retry_failed {
+        // Keep the entries of the fonts that weren't rendered again.
+        let retried: Vec<Key> = fonts.iter().map(key).collect();
+        let previous = read_fonts(path)?;
+        fonts.extend(
+            previous
+                .into_iter()
+                .filter(|entry| !retried.contains(&key(entry))),
+        );
+        fonts.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap_or(Ordering::Equal));
+    }
+
+    let mut diagnostics = json!({
+        "version": SCHEMA_VERSION,
+        "generator": concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")),
+        "input": args.input().display().to_string(),
+        "fonts": fonts,
+    });
+    if let Some(sandbox) = sandbox {
+        let paths = |paths: Vec<PathBuf>| -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect()
+        };
+        diagnostics["sandbox"] = json!({
+            "read": paths(sandbox.read()),
+            "denied": paths(sandbox.denied()),
+        });
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&diagnostics)?)
+        .wrap_err_with(|| format!("failed to write diagnostics to {}", path.display()))
+}
+
+/// The fonts of the book that failed to render in the run that wrote the diagnostics file.
+pub(crate) fn failed_fonts(path: &Path, book: &FontBook) -> Result<Vec<FontInfo>> {
+    let mut failed: Vec<_> = read_fonts(path)?
+        .iter()
+        .filter(|entry| entry["status"] != "ok")
+        .map(key)
+        .collect();
+    // Like the selection, take the first of several fonts with the same family and variant.
+    Ok(book
+        .families()
+        .flat_map(|(_, fonts)| fonts)
+        .filter(|font| {
+            let key = font_key(font);
+            let index = failed.iter().position(|failed| *failed == key);
+            index.map(|index| failed.swap_remove(index)).is_some()
+        })
+        .cloned()
+        .collect())
+}
+
+/// Reads the entries of the fonts from a diagnostics file.
+fn read_fonts(path: &Path) -> Result<Vec<Value>> {
+    let text = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("failed to read diagnostics from {}", path.display()))?;
+    let mut diagnostics: Value = serde_json::from_str(&text)
+        .wrap_err_with(|| format!("failed to parse diagnostics from {}", path.display()))?;
+    if diagnostics["version"] != SCHEMA_VERSION {
+        return Err(eyre!(
+            "diagnostics in {} were written by an incompatible version",
+            path.display()
+        ));
+    }
+    match diagnostics["fonts"].take() {
+        Value::Array(fonts) => Ok(fonts),
+        _ => Err(eyre!("diagnostics in {} list no fonts", path.display())),
+    }
+}
+
+/// Identifies the font of an entry by its family and variant, ordered like the fonts.
+type Key = (String, u8, f64, f64);
+
+fn key(entry: &Value) -> Key {
+    let style = match entry["style"].as_str() {
+        Some("normal") => 0,
+        Some("italic") => 1,
+        _ => 2,
+    };
+    (
+        entry["family"].as_str().unwrap_or_default().to_owned(),
+        style,
+        entry["weight"].as_f64().unwrap_or_default(),
+        entry["stretch"].as_f64().unwrap_or_default(),
+    )
+}
+
+fn font_key(font: &FontInfo) -> Key {
+    key(&json!({
+        "family": font.family,
+        "style": style_name(font.variant.style),
+        "weight": font.variant.weight.to_number(),
+        "stretch": font.variant.stretch.to_ratio().get(),
+    }))
+}
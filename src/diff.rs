@@ -0,0 +1,165 @@
+//! The `diff` subcommand, highlighting where the renders of a document with two fonts differ.
+
+use std::{ffi::OsString, fs, path::Path};
+
+use crate::{
+    find_families, logging, metrics, parse_similarity,
+    raster::{self, Metric, Palette},
+    render_variants, selected_fonts, variant_file_name,
+    world::SystemWorld,
+    Args, Output, Render,
+};
+use clap::Parser;
+use color_eyre::eyre::{eyre, Context, Result};
+use tiny_skia::{IntRect, Pixmap};
+use typst::text::FontInfo;
+
+/// Render a document with two font families and write an image of their differences.
+///
+/// Ink only in the first render is red, ink only in the second render is cyan, unless a
+/// different palette is chosen. The first render is the baseline of `--page-diffs`.
+/// Accepts the options of `compare`, of which those selecting fonts, output formats
+/// and reports are ignored.
+#[derive(Parser)]
+#[clap(bin_name = "typst-font-compare diff")]
+struct DiffArgs {
+    #[clap(flatten)]
+    compare: Args,
+    /// The family to compare against.
+    first: String,
+    /// The family to compare with the first.
+    second: String,
+    #[clap(flatten)]
+    comparison: Comparison,
+    /// Fail if the similarity is below this, e.g. to check that a substitute is close enough.
+    #[clap(long, value_name = "SIMILARITY", value_parser = parse_similarity)]
+    threshold: Option<f64>,
+}
+
+/// How `diff` and `test` measure the similarity of renders and show their differences.
+#[derive(clap::Args)]
+pub(crate) struct Comparison {
+    /// How to measure the similarity of two renders.
+    ///
+    /// Exact comparisons fail on the slight differences of anti-aliasing between platforms,
+    /// which the other metrics tolerate.
+    #[clap(long, default_value = "ink")]
+    metric: Metric,
+    /// How much each pixel may differ to still match with the tolerance and delta-e metrics
+    /// [default: 16 and 2.3]
+    #[clap(long, value_name = "TOLERANCE")]
+    tolerance: Option<f64>,
+    /// Show both renders and only their differences side by side, instead of overlaying
+    /// them.
+    #[clap(long)]
+    side_by_side: bool,
+}
+
+impl Comparison {
+    /// Measures how similar two renders are, from 0 for entirely different to 1 for identical.
+    pub(crate) fn similarity(&self, a: &Pixmap, b: &Pixmap) -> f64 {
+        let tolerance = self
+            .tolerance
+            .unwrap_or_else(|| self.metric.default_tolerance());
+        self.metric.similarity(a, b, tolerance)
+    }
+
+    /// An image of the differences between two renders.
+    pub(crate) fn image(&self, a: &Pixmap, b: &Pixmap, palette: Palette) -> Pixmap {
+        if !self.side_by_side {
+            return raster::diff(a, b, palette);
+        }
+        let differences = raster::differences(a, b, palette);
+        raster::beside(&raster::beside(a, b, 0), &differences, 0)
+    }
+}
+
+/// Runs the subcommand with the arguments following `diff`.
+pub(crate) fn run(args: impl IntoIterator<Item = OsString>) -> Result<()> {
+    let DiffArgs {
+        compare: mut args,
+        first,
+        second,
+        comparison,
+        threshold,
+    } = DiffArgs::parse_from(args);
+    let verbosity = if args.quiet { -1 } else { args.verbose as i8 };
+    logging::init(verbosity, args.log_format);
+    args.fonts.expand_font_paths()?;
+    if args.vector {
+        return Err(eyre!("diff needs rasterized renders, not --vector"));
+    }
+    args.fonts.include = None;
+    args.fonts.exclude = None;
+    args.fonts.include_glob = None;
+    args.fonts.exclude_glob = None;
+    args.fonts.variants = false;
+    args.fonts.only = vec![first, second];
+    args.specimen.clear();
+    args.waterfall = None;
+
+    let world = SystemWorld::new(&args)?;
+    let fonts = selected_fonts(&world, &args)?;
+    let (variants, skipped) =
+        render_variants(world, &args, fonts, None).wrap_err("while rendering variants")?;
+    let pair = find_families(variants, &skipped, &args.fonts.only)?;
+    let images = pair
+        .iter()
+        .map(|render| Ok(metrics::decode(render)?.expect("renders of diff are rasterized")))
+        .collect::<Result<Vec<_>>>()?;
+    let (a, b) = (&images[0], &images[1]);
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input().with_extension("diff.png"));
+    comparison
+        .image(a, b, args.palette)
+        .save_png(&output)
+        .wrap_err_with(|| format!("failed to write {}", output.display()))?;
+    if let Some(dir) = &args.page_diffs {
+        let pages = [page_areas(&pair[0]), page_areas(&pair[1])];
+        write_page_diffs(dir, &pair[1].font, args.palette, [a, b], pages)?;
+    }
+    let similarity = comparison.similarity(a, b);
+    println!("similarity: {similarity:.4}");
+    if let Some(threshold) = threshold.filter(|&threshold| similarity < threshold) {
+        return Err(eyre!(
+            "the renders are less similar than the threshold of {threshold}"
+        ));
+    }
+    Ok(())
+}
+
+/// Writes an image of the differences of each page of a render to the same page of a
+/// reference into the directory, named like `Noto-Serif-normal-400-1000-page-2.png`.
+///
+/// Takes the reference first, and the areas of the images that each page covers. Pages that
+/// only one of them has are compared to an empty page.
+pub(crate) fn write_page_diffs(
+    dir: &Path,
+    font: &FontInfo,
+    palette: Palette,
+    images: [&Pixmap; 2],
+    pages: [&[IntRect]; 2],
+) -> Result<()> {
+    fs::create_dir_all(dir)
+        .wrap_err_with(|| format!("failed to create directory {}", dir.display()))?;
+    let name = variant_file_name(font);
+    for n in 0..pages[0].len().max(pages[1].len()) {
+        let [a, b] = [0, 1].map(|i| raster::crop(images[i], pages[i].get(n).copied()));
+        let path = dir.join(format!("{name}-page-{}.png", n + 1));
+        raster::diff(&a, &b, palette)
+            .save_png(&path)
+            .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// The areas of a render's image that each page covers, or none for vector pages.
+pub(crate) fn page_areas(render: &Render) -> &[IntRect] {
+    match &render.output {
+        Output::Image(image) => &image.pages,
+        Output::Pages(_) => &[],
+    }
+}
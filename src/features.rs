@@ -0,0 +1,138 @@
+//! Inspection of the OpenType layout features provided by a font.
+
+use std::collections::BTreeSet;
+
+use ttf_parser::{gsub::SubstitutionSubtable, GlyphId, Tag};
+use typst::text::Font;
+
+/// Returns the tags of all GSUB and GPOS features the font provides.
+pub(crate) fn supported(font: &Font) -> BTreeSet<Tag> {
+    let tables = font.ttf().tables();
+    [tables.gsub, tables.gpos]
+        .into_iter()
+        .flatten()
+        .flat_map(|table| table.features)
+        .map(|feature| feature.tag)
+        .collect()
+}
+
+/// Creates a tag from a four-character feature name like `onum`.
+pub(crate) fn tag(name: &str) -> Tag {
+    Tag::from_bytes_lossy(name.as_bytes())
+}
+
+/// Returns the name a font assigns to one of its stylistic sets or character variants.
+pub(crate) fn ui_name(font: &Font, tag: Tag) -> Option<String> {
+    // Both the `ssXX` and `cvXX` parameter tables store the name ID right after their version.
+    let name_id = read_u16(params(font, tag)?, 2)?;
+    font.find_name(name_id)
+}
+
+/// Returns the characters whose glyphs may be substituted by a GSUB feature.
+///
+/// Character variants can list the characters they affect themselves.
+/// Otherwise, the characters are derived from the coverage of the feature's lookups.
+pub(crate) fn affected_chars(font: &Font, tag: Tag) -> Vec<char> {
+    let listed = listed_chars(font, tag);
+    if !listed.is_empty() {
+        return listed;
+    }
+
+    let coverages: Vec<_> = substitutions(font, tag)
+        .into_iter()
+        .map(|subtable| subtable.coverage())
+        .collect();
+    let is_affected = |glyph: GlyphId| coverages.iter().any(|coverage| coverage.contains(glyph));
+
+    let mut chars = BTreeSet::new();
+    for subtable in font
+        .ttf()
+        .tables()
+        .cmap
+        .into_iter()
+        .flat_map(|cmap| cmap.subtables)
+        .filter(|subtable| subtable.is_unicode())
+    {
+        subtable.codepoints(|codepoint| {
+            let Some(c) = char::from_u32(codepoint) else {
+                return;
+            };
+            if !c.is_control()
+                && !c.is_whitespace()
+                && subtable.glyph_index(codepoint).is_some_and(is_affected)
+            {
+                chars.insert(c);
+            }
+        });
+    }
+    chars.into_iter().collect()
+}
+
+/// Returns the number of alternates a GSUB feature can choose from, which is at least one.
+pub(crate) fn alternate_count(font: &Font, tag: Tag) -> u16 {
+    substitutions(font, tag)
+        .into_iter()
+        .filter_map(|subtable| match subtable {
+            SubstitutionSubtable::Alternate(alternate) => Some(alternate),
+            _ => None,
+        })
+        .flat_map(|alternate| alternate.alternate_sets)
+        .map(|set| set.alternates.len())
+        .fold(1, u16::max)
+}
+
+/// Returns the substitution subtables of all lookups belonging to a GSUB feature.
+fn substitutions(font: &Font, tag: Tag) -> Vec<SubstitutionSubtable<'_>> {
+    let Some(gsub) = font.ttf().tables().gsub else {
+        return Vec::new();
+    };
+    gsub.features
+        .into_iter()
+        .filter(|feature| feature.tag == tag)
+        .flat_map(|feature| feature.lookup_indices)
+        .filter_map(|index| gsub.lookups.get(index))
+        .flat_map(|lookup| lookup.subtables.into_iter::<SubstitutionSubtable>())
+        .collect()
+}
+
+/// Returns the characters listed in the parameters of a character variant feature.
+fn listed_chars(font: &Font, tag: Tag) -> Vec<char> {
+    if !tag.to_bytes().starts_with(b"cv") {
+        return Vec::new();
+    }
+    let Some((params, count)) =
+        params(font, tag).and_then(|params| Some((params, read_u16(params, 12)?)))
+    else {
+        return Vec::new();
+    };
+    (0..count as usize)
+        .map_while(|i| params.get(14 + i * 3..17 + i * 3))
+        .filter_map(|bytes| char::from_u32(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]])))
+        .collect()
+}
+
+/// Returns the raw parameter table of a GSUB feature.
+///
+/// `ttf-parser` skips over feature parameters, so they are read from the raw table instead.
+fn params(font: &Font, tag: Tag) -> Option<&[u8]> {
+    let gsub = font.ttf().raw_face().table(Tag::from_bytes(b"GSUB"))?;
+    let feature_list = gsub.get(read_u16(gsub, 6)? as usize..)?;
+    let count = read_u16(feature_list, 0)?;
+    (0..count as usize).find_map(|i| {
+        let record = 2 + i * 6;
+        if feature_list.get(record..record + 4)? != tag.to_bytes() {
+            return None;
+        }
+        let feature = feature_list.get(read_u16(feature_list, record + 4)? as usize..)?;
+        match read_u16(feature, 0)? {
+            0 => None,
+            offset => feature.get(offset as usize..),
+        }
+    })
+}
+
+/// Reads a big-endian `u16` at a byte offset.
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
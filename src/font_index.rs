@@ -0,0 +1,265 @@
+//! The information about each font face, kept on disk between runs.
+//!
+//! Reading every font file and extracting its [`FontInfo`] takes seconds on systems with
+//! thousands of fonts. Files whose modification time and size are unchanged are instead
+//! looked up in the index, which is rewritten whenever a file is added, changed or removed.
+//! The faces such a file contains are also kept, so that it isn't even scanned by fontdb.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde_json::{json, Map, Value};
+use typst::text::FontInfo;
+
+use crate::{cache, world::FontFile};
+
+/// The version of the index file's schema, increased on incompatible changes.
+const SCHEMA_VERSION: u64 = 3;
+
+/// What is known about a face: its information, or `None` if Typst can't load it.
+pub(crate) type Entry = Option<(FontInfo, FontFile)>;
+
+/// The faces of the font files read in previous runs.
+#[derive(Default)]
+pub(crate) struct FontIndex {
+    files: HashMap<PathBuf, IndexedFile>,
+    changed: bool,
+}
+
+/// The faces of a font file, as they were when it was last modified.
+struct IndexedFile {
+    stamp: Stamp,
+    /// The indices of the faces fontdb found in the file, if it was scanned.
+    listed: Option<Vec<u32>>,
+    faces: HashMap<u32, Entry>,
+}
+
+/// The modification time in nanoseconds and the size of a file, which change whenever
+/// the file does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    modified: u128,
+    size: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Stamp> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Stamp {
+            modified: metadata
+                .modified()
+                .ok()?
+                .duration_since(UNIX_EPOCH)
+                .ok()?
+                .as_nanos(),
+            size: metadata.len(),
+        })
+    }
+}
+
+impl FontIndex {
+    /// Reads the index of previous runs, or starts an empty one if there is none or it can't
+    /// be read.
+    pub(crate) fn load() -> FontIndex {
+        fs::read(cache::font_index_path())
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .and_then(|value| FontIndex::from_json(&value))
+            .unwrap_or_default()
+    }
+
+    /// Looks up a face of a file, unless the file is new or was changed since.
+    pub(crate) fn get(&self, path: &Path, index: u32) -> Option<Entry> {
+        let file = self.files.get(path)?;
+        if Some(file.stamp) != Stamp::of(path) {
+            return None;
+        }
+        file.faces.get(&index).cloned()
+    }
+
+    /// Returns the indices of the faces fontdb found in a file, unless the file is new or was
+    /// changed since.
+    pub(crate) fn listed(&self, path: &Path) -> Option<&[u32]> {
+        let file = self.files.get(path)?;
+        if Some(file.stamp) != Stamp::of(path) {
+            return None;
+        }
+        file.listed.as_deref()
+    }
+
+    /// Remembers the indices of the faces fontdb found in a file.
+    pub(crate) fn list(&mut self, path: &Path, indices: Vec<u32>) {
+        let Some(file) = self.file_mut(path) else {
+            return;
+        };
+        if file.listed.as_ref() != Some(&indices) {
+            file.listed = Some(indices);
+            self.changed = true;
+        }
+    }
+
+    /// Remembers a face that was loaded from a file.
+    pub(crate) fn insert(&mut self, path: &Path, index: u32, entry: Entry) {
+        let Some(file) = self.file_mut(path) else {
+            return;
+        };
+        file.faces.insert(index, entry);
+        self.changed = true;
+    }
+
+    /// Returns the entry of a file, which is emptied if the file was changed since.
+    fn file_mut(&mut self, path: &Path) -> Option<&mut IndexedFile> {
+        let stamp = Stamp::of(path)?;
+        let empty = || IndexedFile {
+            stamp,
+            listed: None,
+            faces: HashMap::new(),
+        };
+        let file = self.files.entry(path.to_owned()).or_insert_with(empty);
+        if file.stamp != stamp {
+            *file = empty();
+        }
+        Some(file)
+    }
+
+    /// Writes the index for the next run if anything changed, dropping the files that no
+    /// longer exist.
+    ///
+    /// Failing to write it only makes the next run slower, so errors are ignored.
+    pub(crate) fn save(mut self) {
+        let before = self.files.len();
+        self.files.retain(|path, _| path.exists());
+        if !self.changed && self.files.len() == before {
+            return;
+        }
+        let path = cache::font_index_path();
+        let Some(dir) = path.parent() else {
+            return;
+        };
+        let Ok(data) = serde_json::to_vec(&self.to_json()) else {
+            return;
+        };
+        // Writing to a file of this process first keeps concurrent runs from reading a
+        // half-written index.
+        let partial = dir.join(format!("fonts.{}.json", std::process::id()));
+        let written = fs::create_dir_all(dir)
+            .and_then(|()| fs::write(&partial, data))
+            .and_then(|()| fs::rename(&partial, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let files: Map<String, Value> = self
+            .files
+            .iter()
+            .filter_map(|(path, file)| {
+                let faces: Vec<Value> = file
+                    .faces
+                    .iter()
+                    .map(|(index, entry)| match entry {
+                        Some((info, file)) => json!({
+                            "index": index,
+                            "info": info,
+                            "collection": file.collection,
+                            "outlines": file.outlines,
+                            "variable": file.variable,
+                            "glyphs": file.glyphs,
+                            "tables": file.tables,
+                            "postscript_name": file.postscript_name,
+                            "full_name": file.full_name,
+                        }),
+                        None => json!({ "index": index, "info": null }),
+                    })
+                    .collect();
+                let value = json!({
+                    "modified": file.stamp.modified.to_string(),
+                    "size": file.stamp.size,
+                    "listed": file.listed,
+                    "faces": faces,
+                });
+                Some((path.to_str()?.to_owned(), value))
+            })
+            .collect();
+        json!({ "version": SCHEMA_VERSION, "files": files })
+    }
+
+    /// Reads the index, or returns `None` if it was written by an incompatible version.
+    fn from_json(value: &Value) -> Option<FontIndex> {
+        if value["version"].as_u64()? != SCHEMA_VERSION {
+            return None;
+        }
+        let mut files = HashMap::new();
+        for (path, file) in value["files"].as_object()? {
+            let stamp = Stamp {
+                modified: file["modified"].as_str()?.parse().ok()?,
+                size: file["size"].as_u64()?,
+            };
+            let listed = match &file["listed"] {
+                Value::Null => None,
+                listed => Some(
+                    listed
+                        .as_array()?
+                        .iter()
+                        .map(|index| u32::try_from(index.as_u64()?).ok())
+                        .collect::<Option<_>>()?,
+                ),
+            };
+            let mut faces = HashMap::new();
+            for face in file["faces"].as_array()? {
+                let index = u32::try_from(face["index"].as_u64()?).ok()?;
+                faces.insert(index, face_from_json(path, stamp, face)?);
+            }
+            files.insert(
+                PathBuf::from(path),
+                IndexedFile {
+                    stamp,
+                    listed,
+                    faces,
+                },
+            );
+        }
+        Some(FontIndex {
+            files,
+            changed: false,
+        })
+    }
+}
+
+/// Reads the entry of a face, or returns `None` if it is malformed.
+fn face_from_json(path: &str, stamp: Stamp, face: &Value) -> Option<Entry> {
+    if face["info"].is_null() {
+        return Some(None);
+    }
+    let info: FontInfo = serde_json::from_value(face["info"].clone()).ok()?;
+    // The strings are mapped back to the static ones of `FontFile`.
+    let outlines = ["CFF2", "CFF", "TrueType", "none"]
+        .into_iter()
+        .find(|outlines| face["outlines"] == *outlines)?;
+    let tables = face["tables"]
+        .as_array()?
+        .iter()
+        .map(|table| {
+            FontFile::NOTABLE_TABLES
+                .into_iter()
+                .find(|notable| table == notable)
+        })
+        .collect::<Option<_>>()?;
+    let file = FontFile {
+        path: Some(PathBuf::from(path)),
+        size: usize::try_from(stamp.size).ok()?,
+        collection: face["collection"].as_bool()?,
+        outlines,
+        variable: face["variable"].as_bool()?,
+        glyphs: u16::try_from(face["glyphs"].as_u64()?).ok()?,
+        tables,
+        postscript_name: face["postscript_name"].as_str().map(str::to_owned),
+        full_name: face["full_name"].as_str().map(str::to_owned),
+    };
+    Some(Some((info, file)))
+}
@@ -0,0 +1,93 @@
+//! Reading the names and variant of a face without reading its whole file.
+//!
+//! Only the small tables Typst derives them from are read and assembled into a font without
+//! glyphs, so the face's information lacks its coverage of characters.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
+use typst::text::FontInfo;
+
+use crate::world::FontFile;
+
+/// The tables needed to parse a face and to determine its family, variant and flags.
+const TABLES: [&[u8; 4]; 6] = [b"head", b"hhea", b"maxp", b"name", b"OS/2", b"post"];
+
+/// Reads the information of a face apart from its coverage, and its names.
+pub(crate) fn read(source: &fontdb::Source, index: u32) -> Option<(FontInfo, FontFile)> {
+    let font = match source {
+        fontdb::Source::File(path) => {
+            let mut file = File::open(path).ok()?;
+            assemble(index, |offset, len| {
+                let mut data = vec![0; len];
+                file.seek(SeekFrom::Start(offset as u64)).ok()?;
+                file.read_exact(&mut data).ok()?;
+                Some(data)
+            })
+        }
+        fontdb::Source::SharedFile(_, data) | fontdb::Source::Binary(data) => {
+            let data = (**data).as_ref();
+            assemble(index, |offset, len| {
+                data.get(offset..offset.checked_add(len)?)
+                    .map(<[u8]>::to_vec)
+            })
+        }
+    }?;
+    let path = match source {
+        fontdb::Source::File(path) | fontdb::Source::SharedFile(path, _) => Some(path.clone()),
+        fontdb::Source::Binary(_) => None,
+    };
+    Some((FontInfo::new(&font, 0)?, FontFile::unread(path, &font)))
+}
+
+/// Assembles a font of the face's metadata tables, reading `len` bytes at an offset of the
+/// file with `read`.
+fn assemble(index: u32, mut read: impl FnMut(usize, usize) -> Option<Vec<u8>>) -> Option<Vec<u8>> {
+    let mut offset = 0;
+    let mut header = read(0, 12)?;
+    if header.starts_with(b"ttcf") {
+        if index as usize >= u32_at(&header, 8)? {
+            return None;
+        }
+        // The offsets of the faces follow the number of faces in a collection.
+        offset = u32_at(&read(12 + 4 * index as usize, 4)?, 0)?;
+        header = read(offset, 12)?;
+    } else if index != 0 {
+        return None;
+    }
+    let num_tables = u16::from_be_bytes(header.get(4..6)?.try_into().ok()?) as usize;
+    let records = read(offset + 12, 16 * num_tables)?;
+
+    let mut tables = Vec::new();
+    for record in records.chunks_exact(16) {
+        let tag = &record[..4];
+        if TABLES.iter().any(|wanted| wanted[..] == *tag) {
+            tables.push((tag, read(u32_at(record, 8)?, u32_at(record, 12)?)?));
+        }
+    }
+
+    let mut font = header[..4].to_vec();
+    font.extend((tables.len() as u16).to_be_bytes());
+    // The search hints are ignored when parsing.
+    font.extend([0; 6]);
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, data) in &tables {
+        font.extend(*tag);
+        font.extend(0u32.to_be_bytes());
+        font.extend((offset as u32).to_be_bytes());
+        font.extend((data.len() as u32).to_be_bytes());
+        offset += data.len().next_multiple_of(4);
+    }
+    for (_, data) in tables {
+        font.extend(data);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+    Some(font)
+}
+
+/// Reads a big-endian `u32` at an offset.
+fn u32_at(data: &[u8], at: usize) -> Option<usize> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as usize)
+}
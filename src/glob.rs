@@ -0,0 +1,71 @@
+//! Glob patterns like `Noto {Sans,Serif}*` as a simpler alternative to regular expressions.
+
+use std::str::FromStr;
+
+/// A glob pattern, translated to a regular expression matching whole names.
+///
+/// `*` matches any text, `?` a single character, `[abc]` and `[a-z]` one of the characters,
+/// `{a,b}` one of the alternatives, and `\` escapes the next character.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Glob {
+    regex: String,
+}
+
+impl Glob {
+    /// The equivalent regular expression.
+    pub(crate) fn regex(&self) -> &str {
+        &self.regex
+    }
+}
+
+impl FromStr for Glob {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut regex = String::from("^(?:");
+        // The number of alternatives that are currently open.
+        let mut depth = 0;
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' => regex.push_str(".*"),
+                '?' => regex.push('.'),
+                '{' => {
+                    depth += 1;
+                    regex.push_str("(?:");
+                }
+                ',' if depth > 0 => regex.push('|'),
+                '}' if depth > 0 => {
+                    depth -= 1;
+                    regex.push(')');
+                }
+                '[' => {
+                    regex.push('[');
+                    if matches!(chars.clone().next(), Some('!' | '^')) {
+                        chars.next();
+                        regex.push('^');
+                    }
+                    loop {
+                        match chars.next() {
+                            Some(']') => break,
+                            Some('-') => regex.push('-'),
+                            Some(c) => regex.push_str(&regex::escape(&c.to_string())),
+                            None => return Err(format!("unclosed `[` in glob `{s}`")),
+                        }
+                    }
+                    regex.push(']');
+                }
+                '\\' => match chars.next() {
+                    Some(c) => regex.push_str(&regex::escape(&c.to_string())),
+                    None => return Err(format!("trailing `\\` in glob `{s}`")),
+                },
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        if depth > 0 {
+            return Err(format!("unclosed `{{` in glob `{s}`"));
+        }
+        regex.push_str(")$");
+        Ok(Glob { regex })
+    }
+}
@@ -0,0 +1,288 @@
+//! The `glyphs` subcommand, comparing the glyphs of two fonts for each character they share.
+
+use std::{ffi::OsString, fmt::Write, fs, path::PathBuf};
+
+use clap::Parser;
+use color_eyre::eyre::{eyre, Context, Result};
+use rayon::prelude::*;
+use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Transform};
+use ttf_parser::GlyphId;
+use typst::{
+    eval::Tracer,
+    foundations::{Bytes, Smart},
+    text::{Font, FontBook},
+    World,
+};
+
+use crate::{
+    paths,
+    raster::{self, Palette},
+    source_date,
+    specimen::{self, charset},
+    world::{FileData, FontSources, SystemWorld},
+};
+
+/// The width and height of each rasterized glyph, in pixels.
+const SIZE: u32 = 96;
+
+/// The region drawn around each glyph, as its left, top, right and bottom edge in ems from
+/// the glyph's origin, with y pointing down.
+const REGION: [f32; 4] = [-1.0 / 3.0, -1.25, 4.0 / 3.0, 5.0 / 12.0];
+
+/// Compare two fonts glyph by glyph, e.g. to validate a fork or an update of a font.
+///
+/// Writes a PDF listing the glyphs of the shared characters that differ most first, each with
+/// an overlay in which ink only in the first font is red and ink only in the second is cyan,
+/// unless a different palette is chosen.
+#[derive(Parser)]
+#[clap(bin_name = "typst-font-compare glyphs")]
+struct GlyphsArgs {
+    /// The first font, as a path to a font file or a family name.
+    first: String,
+    /// The second font, as a path to a font file or a family name.
+    second: String,
+    /// Path to the output file.
+    #[clap(short, long, default_value = "glyphs.pdf")]
+    output: PathBuf,
+    /// How many of the most different glyphs to show. Identical glyphs are never shown.
+    #[clap(long, default_value_t = 100)]
+    limit: usize,
+    /// The colors of ink only in the first and only in the second font in the overlays.
+    #[clap(long, default_value = "red-cyan")]
+    palette: Palette,
+    /// Adds additional directories to search for fonts in.
+    ///
+    /// Can be given multiple times or as a comma-separated list.
+    /// A leading `~` and environment variables like `$HOME` are expanded.
+    #[clap(
+        long = "font-path",
+        env = "TYPST_FONT_PATHS",
+        value_name = "DIR",
+        value_delimiter = if cfg!(windows) { ';' } else { ':' },
+    )]
+    font_paths: Vec<PathBuf>,
+    /// Only use the fonts from `--font-path` and Typst's embedded fonts, not the ones installed
+    /// on the system, e.g. for reproducible comparisons in CI.
+    #[clap(long)]
+    ignore_system_fonts: bool,
+    /// Don't use the fonts bundled with the Typst compiler, like Linux Libertine and New
+    /// Computer Modern, which `typst compile` uses even if they are not installed.
+    #[clap(long)]
+    ignore_embedded_fonts: bool,
+}
+
+/// The glyphs of both fonts for a character.
+struct Comparison {
+    c: char,
+    first: Pixmap,
+    second: Pixmap,
+    similarity: f64,
+}
+
+/// Runs the subcommand with the arguments following `glyphs`.
+pub(crate) fn run(args: impl IntoIterator<Item = OsString>) -> Result<()> {
+    let args = GlyphsArgs::parse_from(args);
+    let mut world = SystemWorld::without_input(
+        &paths::expand_list(&args.font_paths)?,
+        FontSources {
+            system: !args.ignore_system_fonts,
+            embedded: !args.ignore_embedded_fonts,
+        },
+    )?;
+    let (first, second) = (load(&world, &args.first)?, load(&world, &args.second)?);
+
+    let shared: Vec<char> = charset(&first)
+        .intersection(&charset(&second))
+        .copied()
+        .collect();
+    let mut comparisons: Vec<Comparison> = shared
+        .par_iter()
+        .filter_map(|&c| {
+            let (first, second) = rasterize(&first, &second, c)?;
+            let similarity = raster::similarity(&first, &second);
+            Some(Comparison {
+                c,
+                first,
+                second,
+                similarity,
+            })
+        })
+        .collect();
+    comparisons.sort_by(|a, b| a.similarity.total_cmp(&b.similarity).then(a.c.cmp(&b.c)));
+    let different = comparisons
+        .iter()
+        .filter(|comparison| comparison.similarity < 1.0)
+        .count();
+
+    let [(first_name, first_color), (second_name, second_color)] = args.palette.legend();
+    let mut main = format!(
+        r#"
+        #set document(author: "{pkg_name}")
+        #set page(margin: 1.5cm, height: auto)
+        #set text(size: 10pt)
+
+        *Glyphs of {first} and {second}*
+
+        {shared} shared characters, of which {different} differ.
+        Ink only in the first font is #text(fill: rgb("{first_color}"))[{first_name}], ink only in the second is #text(fill: rgb("{second_color}").darken(20%))[{second_name}].
+
+        #table(
+            columns: (auto, 1fr, 1fr, 1fr, auto),
+            align: horizon,
+            stroke: none,
+            [*Character*], [*First*], [*Second*], [*Overlay*], [*Similarity*],
+        "#,
+        pkg_name = env!("CARGO_PKG_NAME"),
+        first = specimen::string(&args.first),
+        second = specimen::string(&args.second),
+        shared = shared.len(),
+    );
+    let shown: Vec<&Comparison> = comparisons
+        .iter()
+        .filter(|comparison| comparison.similarity < 1.0)
+        .take(args.limit)
+        .collect();
+    let files = shown
+        .par_iter()
+        .enumerate()
+        .map(|(n, comparison)| {
+            let overlay = raster::diff(&comparison.first, &comparison.second, args.palette);
+            [
+                ("first", &comparison.first),
+                ("second", &comparison.second),
+                ("overlay", &overlay),
+            ]
+            .into_iter()
+            .map(|(name, pixmap)| {
+                let png = pixmap.encode_png()?;
+                Ok((
+                    PathBuf::from(format!("{name}-{n}.png")),
+                    FileData::Bytes(Bytes::from(png)),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for (n, comparison) in shown.iter().enumerate() {
+        writeln!(
+            main,
+            r#"[#{c} #text(size: 0.8em, raw("U+{code:04X}"))], image("first-{n}.png", width: 2cm), image("second-{n}.png", width: 2cm), image("overlay-{n}.png", width: 2cm), [{similarity:.1} %],"#,
+            c = specimen::string(&comparison.c.to_string()),
+            code = comparison.c as u32,
+            similarity = comparison.similarity * 100.0,
+        )?;
+    }
+    main.push_str(")\n");
+
+    world.replace_files(main, files.into_iter().flatten());
+    let mut tracer = Tracer::new();
+    let document = typst::compile(&world, &mut tracer)
+        .map_err(|diag| eyre!("failed to compile glyph comparison: {diag:?}"))?;
+    fs::write(
+        &args.output,
+        typst_pdf::pdf(&document, Smart::Auto, source_date::pdf_timestamp()),
+    )
+    .wrap_err_with(|| format!("failed to write {}", args.output.display()))?;
+    println!(
+        "{different} of {} shared characters differ, written to {}",
+        shared.len(),
+        args.output.display()
+    );
+    Ok(())
+}
+
+/// Loads a font from a file, or the regular style of an installed family.
+///
+/// Of a font collection, the regular style of its first family is loaded.
+fn load(world: &SystemWorld, font: &str) -> Result<Font> {
+    let path = paths::expand(font)?;
+    if path.is_file() {
+        let data = fs::read(&path).wrap_err_with(|| format!("failed to read {font}"))?;
+        let faces: Vec<Font> = Font::iter(Bytes::from(data)).collect();
+        let book = FontBook::from_fonts(&faces);
+        let family = faces
+            .first()
+            .ok_or_else(|| eyre!("failed to parse {font}"))?
+            .info()
+            .family
+            .to_lowercase();
+        let index = book
+            .select(&family, Default::default())
+            .expect("a family of the collection must have a face");
+        return Ok(faces[index].clone());
+    }
+    world
+        .book
+        .select(&font.to_lowercase(), Default::default())
+        .and_then(|index| world.font(index))
+        .ok_or_else(|| eyre!("font {font} is neither a file nor an installed family"))
+}
+
+/// Rasterizes the glyphs of both fonts for a character, scaled to each font's em so that
+/// they are drawn at the same size and on the same baseline.
+///
+/// Both glyphs are drawn into the region of [`REGION`], which is enlarged to fit glyphs that
+/// extend beyond it, like wide ligatures or tall accents. Returns `None` if either font has
+/// no glyph for the character.
+fn rasterize(first: &Font, second: &Font, c: char) -> Option<(Pixmap, Pixmap)> {
+    let glyphs = [first, second].map(|font| Some((font, font.ttf().glyph_index(c)?)));
+    let [Some(first), Some(second)] = glyphs else {
+        return None;
+    };
+    let [mut left, mut top, mut right, mut bottom] = REGION;
+    for (font, glyph) in [first, second] {
+        let face = font.ttf();
+        if let Some(bbox) = face.glyph_bounding_box(glyph) {
+            let em = |units: i16| f32::from(units) / f32::from(face.units_per_em());
+            left = left.min(em(bbox.x_min));
+            top = top.min(-em(bbox.y_max));
+            right = right.max(em(bbox.x_max));
+            bottom = bottom.max(-em(bbox.y_min));
+        }
+    }
+    let scale = SIZE as f32 / (right - left).max(bottom - top);
+    let draw = |(font, glyph): (&Font, GlyphId)| {
+        let face = font.ttf();
+        let mut pixmap = Pixmap::new(SIZE, SIZE).expect("glyph pixmap must not be empty");
+        let mut builder = Outline(PathBuilder::new());
+        // An empty outline, e.g. of a space-like character, leaves the pixmap blank.
+        if face.outline_glyph(glyph, &mut builder).is_some() {
+            if let Some(path) = builder.0.finish() {
+                let units = scale / f32::from(face.units_per_em());
+                let transform =
+                    Transform::from_row(units, 0.0, 0.0, -units, -left * scale, -top * scale);
+                // Not quite black, as the similarity skips pixels that are opaque black in both.
+                let mut paint = Paint::default();
+                paint.set_color_rgba8(1, 1, 1, 255);
+                pixmap.fill_path(&path, &paint, FillRule::Winding, transform, None);
+            }
+        }
+        pixmap
+    };
+    Some((draw(first), draw(second)))
+}
+
+/// Collects the outline of a glyph into a path.
+struct Outline(PathBuilder);
+
+impl ttf_parser::OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.move_to(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.line_to(x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.0.quad_to(x1, y1, x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.0.cubic_to(x1, y1, x2, y2, x, y);
+    }
+
+    fn close(&mut self) {
+        self.0.close();
+    }
+}
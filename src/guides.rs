@@ -0,0 +1,220 @@
+//! Guide lines and annotations drawn over the renders to make the metrics of the fonts visible.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use color_eyre::eyre::{eyre, Result};
+use typst::{
+    eval::Tracer,
+    layout::{Abs, Em, Frame, FrameItem, Page, Point, Transform},
+    syntax::Span,
+    text::{Font, TextItem},
+    visualize::{Color, FixedStroke, Geometry},
+};
+
+use crate::world::SystemWorld;
+
+/// The gap between an annotation and the text block it describes, in points.
+const ANNOTATION_GAP: f64 = 4.0;
+
+/// Draws lines across the page at the baseline, x-height, cap height, ascender and descender
+/// of the first line of body text on the first page that has any.
+///
+/// Body text is set in the family at the size that most of its text is set in, so that
+/// headings and text in other fonts, like raw text or fallback, are passed over.
+pub(crate) fn draw_metrics(pages: &mut [Page], family: &str, body_size: Abs) {
+    let is_body = |text: &TextItem| {
+        text.font.info().family.eq_ignore_ascii_case(family)
+            && (text.size.to_pt() * 100.0).round() == (body_size.to_pt() * 100.0).round()
+    };
+    for page in pages {
+        let Some(&(origin, text)) = texts(&page.frame).iter().find(|(_, text)| is_body(text))
+        else {
+            continue;
+        };
+        let (size, metrics) = (text.size, text.font.metrics());
+        let width = page.frame.width();
+        let guides = [
+            (metrics.ascender, Color::ORANGE),
+            (height(&text.font, 'H', metrics.cap_height), Color::GREEN),
+            (height(&text.font, 'x', metrics.x_height), Color::BLUE),
+            (Em::zero(), Color::RED),
+            (metrics.descender, Color::PURPLE),
+        ];
+        for (height, color) in guides {
+            let y = origin.y - height.at(size);
+            let line = Geometry::Line(Point::with_x(width))
+                .stroked(FixedStroke::from_pair(color, Abs::pt(0.5)));
+            page.frame
+                .push(Point::with_y(y), FrameItem::Shape(line, Span::detached()));
+        }
+        return;
+    }
+}
+
+/// The height of a glyph's outline, e.g. of `H` for the cap height.
+///
+/// Falls back to the height from the font's metrics if the glyph is missing. Typst uses the
+/// ascender for the cap and x-height of fonts that don't declare them, so measuring is more
+/// accurate for those.
+fn height(font: &Font, c: char, fallback: Em) -> Em {
+    let face = font.ttf();
+    face.glyph_index(c)
+        .and_then(|glyph| face.glyph_bounding_box(glyph))
+        .map_or(fallback, |bbox| font.to_em(bbox.y_max))
+}
+
+/// Prints the size of the text block and the average number of characters per line into the
+/// top margin of each page with text.
+///
+/// The annotations are typeset in a separate document of the world, one page each.
+pub(crate) fn annotate_measurements(world: &SystemWorld, pages: &mut [Page]) -> Result<()> {
+    let measured: Vec<(&mut Page, Measurement)> = pages
+        .iter_mut()
+        .filter_map(|page| {
+            let measurement = measure(&page.frame)?;
+            Some((page, measurement))
+        })
+        .collect();
+    if measured.is_empty() {
+        return Ok(());
+    }
+
+    let mut source = String::from(
+        "#set page(width: auto, height: auto, margin: 0pt)\n#set text(size: 7pt, fill: rgb(\"#0074d9\"))\n",
+    );
+    for (n, (_, measurement)) in measured.iter().enumerate() {
+        if n > 0 {
+            source.push_str("#pagebreak()\n");
+        }
+        let size = measurement.max - measurement.min;
+        writeln!(
+            source,
+            "Text block {:.1} × {:.1} mm, {:.1} characters per line on {} lines",
+            size.x.to_mm(),
+            size.y.to_mm(),
+            measurement.chars as f64 / measurement.lines as f64,
+            measurement.lines,
+        )?;
+    }
+    let mut tracer = Tracer::new();
+    let annotations = typst::compile(&world.with_main_source(source), &mut tracer)
+        .map_err(|diag| eyre!("failed to compile measurement annotations: {diag:?}"))?;
+
+    for ((page, measurement), annotation) in measured.into_iter().zip(annotations.pages) {
+        let frame = annotation.frame;
+        let y = (measurement.min.y - Abs::pt(ANNOTATION_GAP) - frame.height()).max(Abs::zero());
+        page.frame
+            .push_frame(Point::new(measurement.min.x, y), frame);
+    }
+    Ok(())
+}
+
+/// The extent of the text on a page.
+struct Measurement {
+    /// The top left corner of the text block.
+    min: Point,
+    /// The bottom right corner of the text block.
+    max: Point,
+    /// The number of lines, counting text on the same baseline as one line.
+    lines: usize,
+    /// The number of characters, including spaces.
+    chars: usize,
+}
+
+/// Measures the text of a page's body, or returns `None` if it has none.
+fn measure(frame: &Frame) -> Option<Measurement> {
+    let texts = body_texts(frame);
+    if texts.is_empty() {
+        return None;
+    }
+    let (mut min, mut max) = (Point::splat(Abs::inf()), Point::splat(-Abs::inf()));
+    let mut lines = BTreeMap::new();
+    for (origin, text) in texts {
+        min = min.min(Point::new(origin.x, top(origin, text)));
+        max = max.max(Point::new(origin.x + text.width(), bottom(origin, text)));
+        // Rounded to a tenth of a point, so that text of a line in different styles is merged.
+        let baseline = (origin.y.to_pt() * 10.0).round() as i64;
+        *lines.entry(baseline).or_insert(0) += text.text.chars().count();
+    }
+    Some(Measurement {
+        min,
+        max,
+        lines: lines.len(),
+        chars: lines.values().sum(),
+    })
+}
+
+/// The texts of a page without the ones of its header and footer.
+///
+/// Typst adds the header before and the footer after the body, in the margins. By default,
+/// they are raised and lowered by 30% of the margin, so they are told apart from the body by a
+/// gap of at least a quarter of their distance to the page's edge, which is far more than the
+/// spacing of the body's lines.
+fn body_texts(frame: &Frame) -> Vec<(Point, &TextItem)> {
+    let texts = texts(frame);
+    // The bottom of the lowest and the top of the highest text.
+    let bottom_of = |texts: &[(Point, &TextItem)]| {
+        texts
+            .iter()
+            .map(|&(origin, text)| bottom(origin, text))
+            .fold(-Abs::inf(), Abs::max)
+    };
+    let top_of = |texts: &[(Point, &TextItem)]| {
+        texts
+            .iter()
+            .map(|&(origin, text)| top(origin, text))
+            .fold(Abs::inf(), Abs::min)
+    };
+    let start = (1..texts.len())
+        .find(|&i| {
+            let header = bottom_of(&texts[..i]);
+            top_of(&texts[i..]) - header >= header / 4.0
+        })
+        .unwrap_or(0);
+    let end = (start + 1..texts.len())
+        .rev()
+        .find(|&i| {
+            let footer = top_of(&texts[i..]);
+            footer - bottom_of(&texts[start..i]) >= (frame.height() - footer) / 4.0
+        })
+        .unwrap_or(texts.len());
+    texts[start..end].to_vec()
+}
+
+/// The top of a text's line, at its ascender.
+fn top(origin: Point, text: &TextItem) -> Abs {
+    origin.y - text.font.metrics().ascender.at(text.size)
+}
+
+/// The bottom of a text's line, at its descender.
+fn bottom(origin: Point, text: &TextItem) -> Abs {
+    origin.y - text.font.metrics().descender.at(text.size)
+}
+
+/// Collects the texts of a frame in order, together with the positions of their baselines'
+/// starts relative to the page.
+fn texts(frame: &Frame) -> Vec<(Point, &TextItem)> {
+    let mut texts = Vec::new();
+    collect_texts(frame, Transform::identity(), &mut texts);
+    texts
+}
+
+fn collect_texts<'a>(
+    frame: &'a Frame,
+    transform: Transform,
+    texts: &mut Vec<(Point, &'a TextItem)>,
+) {
+    for (pos, item) in frame.items() {
+        match item {
+            FrameItem::Group(group) => collect_texts(
+                &group.frame,
+                transform
+                    .pre_concat(Transform::translate(pos.x, pos.y))
+                    .pre_concat(group.transform),
+                texts,
+            ),
+            FrameItem::Text(text) => texts.push((pos.transform(transform), text)),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Templates of the headers and footers of the collection's variant pages.
+
+use std::str::FromStr;
+
+use crate::specimen;
+
+/// A text like `{family} — {variant} — {date}` with placeholders filled in for each page.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PageTemplate {
+    pieces: Vec<Piece>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Family,
+    Variant,
+    Date,
+    Page,
+}
+
+impl PageTemplate {
+    /// Fills in the template as Typst code evaluating to content, with the families and
+    /// variants of all fonts on the page.
+    pub(crate) fn typst(&self, families: &[&str], variants: &[String]) -> String {
+        let pieces: Vec<String> = self
+            .pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => specimen::string(text),
+                Piece::Family => specimen::string(&families.join(" / ")),
+                Piece::Variant => specimen::string(&variants.join(" / ")),
+                Piece::Date => "datetime.today().display()".to_owned(),
+                Piece::Page => "counter(page).display()".to_owned(),
+            })
+            .collect();
+        if pieces.is_empty() {
+            return "none".to_owned();
+        }
+        format!("({},).join()", pieces.join(", "))
+    }
+}
+
+impl FromStr for PageTemplate {
+    type Err = String;
+
+    /// Parses the placeholders `{family}`, `{variant}`, `{date}` and `{page}`.
+    /// `{{` and `}}` stand for literal braces.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed `{{` in template `{s}`")),
+                        }
+                    }
+                    let piece = match name.as_str() {
+                        "family" => Piece::Family,
+                        "variant" => Piece::Variant,
+                        "date" => Piece::Date,
+                        "page" => Piece::Page,
+                        _ => {
+                            return Err(format!(
+                                "unknown placeholder `{{{name}}}`, expected one of `{{family}}`, `{{variant}}`, `{{date}}` and `{{page}}`"
+                            ))
+                        }
+                    };
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(piece);
+                }
+                '}' => return Err(format!("unmatched `}}` in template `{s}`")),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(PageTemplate { pieces })
+    }
+}
@@ -0,0 +1,341 @@
+//! A static HTML gallery, in which two fonts can be compared with a slider over their renders.
+//!
+//! Its look can be adapted with a theme, either a CSS file added after the gallery's styles
+//! or a TOML file setting some of its properties:
+//!
+//! ```toml
+//! font = "Inter, sans-serif"
+//! background = "#111"
+//! text = "#eee"
+//! accent = "#f80"
+//! ```
+
+use std::{fmt::Write, fs, path::Path};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use rayon::prelude::*;
+use serde_json::json;
+use typst::foundations::Bytes;
+
+use crate::{locale::Locale, raster, world::FileData, write_files, Args, Output, Render};
+
+/// The styles of the gallery.
+const STYLE: &str = r#"
+:root { --font: sans-serif; --background: Canvas; --text: CanvasText; --accent: #e33;
+  --link: LinkText; --border: #ccc; --margin: 2em; --column-width: 16em; --gap: 2em; }
+body { font-family: var(--font); background: var(--background); color: var(--text);
+  margin: var(--margin); }
+a { color: var(--link); }
+img { display: block; max-width: 100%; }
+#gallery { display: grid; grid-template-columns: repeat(auto-fill, minmax(var(--column-width), 1fr));
+  gap: var(--gap); }
+#gallery h2 { grid-column: 1 / -1; margin: 0; }
+figure { margin: 0; }
+figure img { border: 1px solid var(--border); height: auto; }
+.slider { position: relative; display: inline-block; max-width: 100%; --position: 50%;
+  cursor: ew-resize; user-select: none; touch-action: none; border: 1px solid var(--border); }
+.slider img { width: 100%; }
+.slider .right { position: absolute; top: 0; left: 0; clip-path: inset(0 0 0 var(--position)); }
+.slider .handle { position: absolute; top: 0; bottom: 0; left: var(--position); width: 2px;
+  margin-left: -1px; background: var(--accent); pointer-events: none; }
+.labels { display: flex; justify-content: space-between; gap: 1em; margin: 0.5em 0; }
+"#;
+
+/// The keys of a TOML theme, each setting the CSS variable of the same name.
+const THEME_KEYS: [&str; 9] = [
+    "font",
+    "background",
+    "text",
+    "accent",
+    "link",
+    "border",
+    "margin",
+    "column-width",
+    "gap",
+];
+
+/// Fills the selections, moves the slider while dragging it and handles the keyboard shortcuts.
+const SCRIPT: &str = r#"
+const left = document.getElementById("left");
+const right = document.getElementById("right");
+const slider = document.getElementById("slider");
+for (const select of [left, right]) {
+  for (const [i, variant] of variants.entries()) {
+    select.add(new Option(variant.name, i));
+  }
+  select.addEventListener("change", update);
+}
+left.value = baseline;
+right.value = baseline === 0 ? Math.min(1, variants.length - 1) : 0;
+
+function update() {
+  slider.querySelector(".left").src = variants[left.value].src;
+  slider.querySelector(".right").src = variants[right.value].src;
+}
+
+function setPosition(position) {
+  slider.style.setProperty("--position", `${position * 100}%`);
+}
+
+function move(event) {
+  const rect = slider.getBoundingClientRect();
+  setPosition(Math.min(Math.max((event.clientX - rect.left) / rect.width, 0), 1));
+}
+slider.addEventListener("pointerdown", (event) => {
+  slider.setPointerCapture(event.pointerId);
+  move(event);
+});
+slider.addEventListener("pointermove", (event) => {
+  if (slider.hasPointerCapture(event.pointerId)) {
+    move(event);
+  }
+});
+
+for (const button of document.querySelectorAll("button[data-variant]")) {
+  button.addEventListener("click", () => {
+    right.value = button.dataset.variant;
+    update();
+    document.getElementById("compare").scrollIntoView();
+  });
+}
+// Load the neighbouring renders ahead of time once flipping started, so that it is instant.
+function preload() {
+  for (const offset of [-1, 1]) {
+    const index = (Number(right.value) + offset + variants.length) % variants.length;
+    new Image().src = variants[index].src;
+  }
+}
+
+// The blink test: the arrow keys flip the right font through all fonts in place and
+// the space bar switches between showing only the left or only the right font.
+let showsRight = false;
+document.addEventListener("keydown", (event) => {
+  if (event.altKey || event.ctrlKey || event.metaKey || event.target instanceof HTMLSelectElement) {
+    return;
+  }
+  if (event.key === "ArrowLeft" || event.key === "ArrowRight") {
+    const step = event.key === "ArrowRight" ? 1 : -1;
+    right.value = (Number(right.value) + step + variants.length) % variants.length;
+    update();
+    preload();
+    if (!showsRight) {
+      showsRight = true;
+      setPosition(0);
+    }
+  } else if (event.key === " ") {
+    showsRight = !showsRight;
+    setPosition(showsRight ? 0 : 1);
+  } else {
+    return;
+  }
+  // Keep the scroll position, which these keys would change otherwise.
+  event.preventDefault();
+});
+
+update();
+"#;
+
+/// Write the gallery to `output` and the renders it shows into a `renders` directory next to it.
+pub(crate) fn write_html(
+    args: &Args,
+    variants: Vec<Render>,
+    partial: bool,
+    output: &Path,
+) -> Result<()> {
+    let theme = match &args.html_theme {
+        Some(path) => load_theme(path)?,
+        None => String::new(),
+    };
+    // Shrink the renders in parallel, so that the gallery opens quickly even for many fonts.
+    // The full renders are only loaded when they are opened or compared.
+    let thumbnails: Vec<Option<(FileData, u32, u32)>> = variants
+        .par_iter()
+        .map(|render| {
+            let Output::Image(image) = &render.output else {
+                return Ok(None);
+            };
+            let bytes = image
+                .data
+                .bytes()
+                .map_err(|err| eyre!("failed to read render: {err}"))?;
+            let (thumbnail, width, height) = raster::thumbnail(&bytes, raster::THUMBNAIL_WIDTH)?;
+            Ok(Some((
+                FileData::Bytes(Bytes::from(thumbnail)),
+                width,
+                height,
+            )))
+        })
+        .collect::<Result<_>>()?;
+
+    let mut files = Vec::new();
+    let mut comparable = Vec::new();
+    // The index of the variant initially shown on the left.
+    let mut baseline = None;
+    let mut gallery = String::new();
+    for (n, render) in variants.iter().enumerate() {
+        let name = format!("{} {:?}", render.font.family, render.font.variant);
+        let stem = render.file_stem(n);
+        writeln!(
+            gallery,
+            "<figure>\n<figcaption><strong>{family}</strong> {variant:?}",
+            family = escape(&render.font.family),
+            variant = render.font.variant,
+        )?;
+        if let Some(note) = render.note(Locale::En) {
+            write!(gallery, "<br><small>{}</small>", escape(&note))?;
+        }
+        match (&render.output, &thumbnails[n]) {
+            (Output::Image(image), Some((thumbnail, width, height))) => {
+                let src = format!("{stem}.png");
+                let thumbnail_src = format!("{stem}-thumbnail.png");
+                writeln!(
+                    gallery,
+                    "<button data-variant=\"{index}\">Compare</button></figcaption>\n\
+                     <a href=\"{src}\"><img src=\"{thumbnail_src}\" width=\"{width}\" \
+                     height=\"{height}\" loading=\"lazy\" alt=\"{name}\"></a>",
+                    index = comparable.len(),
+                    name = escape(&name),
+                )?;
+                files.push((thumbnail_src, thumbnail));
+                let is_baseline = args.baseline.as_ref().is_some_and(|baseline| {
+                    std::iter::once(&render.font)
+                        .chain(&render.aliases)
+                        .any(|font| font.family.eq_ignore_ascii_case(baseline))
+                });
+                if is_baseline && baseline.is_none() {
+                    baseline = Some(comparable.len());
+                }
+                comparable.push(json!({ "name": name, "src": src }));
+                files.push((src, &image.data));
+            }
+            _ => writeln!(
+                gallery,
+                "</figcaption>\n<p>Rendered as vector pages, which are only part of the PDF.</p>"
+            )?,
+        }
+        for (kind, image) in &render.specimens {
+            let src = format!("{stem}-{}.png", kind.name());
+            writeln!(
+                gallery,
+                "<h4>{title}</h4>\n<img src=\"{src}\" loading=\"lazy\" alt=\"{title}\">",
+                title = kind.title(),
+            )?;
+            files.push((src, &image.data));
+        }
+        writeln!(gallery, "</figure>")?;
+    }
+
+    let (compare, script) = if comparable.is_empty() {
+        (String::new(), String::new())
+    } else {
+        // Prevent the data from closing the script element early.
+        let data = serde_json::to_string(&comparable)?.replace("</", r"<\/");
+        let compare = r#"<section id="compare">
+<h2>Compare</h2>
+<p>Drag over the renders to move the divider between the two fonts.
+Press <kbd>←</kbd> and <kbd>→</kbd> to flip through all fonts on the right,
+and <kbd>Space</kbd> to switch between the left and the right font.</p>
+<div class="labels">
+<label>Left <select id="left"></select></label>
+<label>Right <select id="right"></select></label>
+</div>
+<div class="slider" id="slider">
+<img class="left" alt="Left font">
+<img class="right" alt="Right font">
+<div class="handle"></div>
+</div>
+</section>
+"#;
+        let script = format!(
+            "<script>\nconst variants = {data};\nconst baseline = {baseline};\n{SCRIPT}</script>\n",
+            baseline = baseline.unwrap_or(0),
+        );
+        (compare.to_owned(), script)
+    };
+
+    let input = escape(&args.input().display().to_string());
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Font comparison of {input}</title>
+<style>{STYLE}{theme}</style>
+</head>
+<body>
+<h1>Font comparison of {input}</h1>
+<p>Created using <a href="{pkg_homepage}">{pkg_name} v{pkg_version}</a>.</p>
+{partial}{compare}<section id="gallery">
+<h2>Fonts</h2>
+{gallery}</section>
+{script}</body>
+</html>
+"#,
+        pkg_name = env!("CARGO_PKG_NAME"),
+        pkg_version = env!("CARGO_PKG_VERSION"),
+        pkg_homepage = env!("CARGO_PKG_HOMEPAGE"),
+        partial = if partial {
+            "<p><strong>This comparison is partial.</strong> Rendering was interrupted.</p>\n"
+        } else {
+            ""
+        },
+    );
+
+    write_files(output, files)?;
+    std::fs::write(output, html)?;
+    Ok(())
+}
+
+/// Reads a theme, returning the CSS to add after the gallery's styles.
+///
+/// A `.toml` theme sets the gallery's properties, any other file is taken as CSS.
+fn load_theme(path: &Path) -> Result<String> {
+    let source = fs::read_to_string(path)
+        .wrap_err_with(|| format!("failed to read HTML theme {}", path.display()))?;
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "toml")
+    {
+        let table: toml::Table = source
+            .parse()
+            .wrap_err_with(|| format!("failed to parse HTML theme {}", path.display()))?;
+        let invalid = |message: String| eyre!("invalid HTML theme {}: {message}", path.display());
+        let mut css = String::from(":root {");
+        for (key, value) in table {
+            if !THEME_KEYS.contains(&key.as_str()) {
+                return Err(invalid(format!(
+                    "unknown key `{key}`, expected one of {}",
+                    THEME_KEYS.join(", ")
+                )));
+            }
+            let value = value
+                .as_str()
+                .ok_or_else(|| invalid(format!("`{key}` must be a string")))?;
+            if value.contains(['{', '}', ';', '<']) {
+                return Err(invalid(format!("`{key}` must be a single CSS value")));
+            }
+            write!(css, " --{key}: {value};")?;
+        }
+        css.push_str(" }\n");
+        Ok(css)
+    } else {
+        // Prevent the styles from closing the style element early.
+        Ok(source.replace("</", r"<\/"))
+    }
+}
+
+/// Escape text for use in HTML content and attributes.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
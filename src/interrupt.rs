@@ -0,0 +1,25 @@
+//! Handling of Ctrl-C, so that an interrupted run still assembles the variants rendered so far.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs a handler that records the first interrupt instead of exiting.
+///
+/// A second interrupt terminates the process as usual.
+pub(crate) fn install() {
+    #[cfg(unix)]
+    {
+        extern "C" fn handle(_: libc::c_int) {
+            REQUESTED.store(true, Ordering::SeqCst);
+            // Only `signal` and atomics are async-signal-safe here.
+            unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+        }
+        unsafe { libc::signal(libc::SIGINT, handle as *const () as libc::sighandler_t) };
+    }
+}
+
+/// Whether an interrupt was received.
+pub(crate) fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
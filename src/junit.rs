@@ -0,0 +1,113 @@
+//! A JUnit XML report, so that font availability checks show up in CI test reports.
+
+use std::{fmt::Write, path::Path, time::Duration};
+
+use color_eyre::eyre::{Context, Result};
+use typst::text::FontInfo;
+
+use crate::{Args, Render, SkipReason, Skipped};
+
+/// A test case of the report, representing a single font.
+struct TestCase<'a> {
+    font: &'a FontInfo,
+    duration: Duration,
+    /// The type and message of the failure, and its details.
+    failure: Option<(&'static str, String, String)>,
+}
+
+/// Writes a report in which each rendered or skipped font is a test case.
+pub(crate) fn write_junit(
+    args: &Args,
+    variants: &[Render],
+    skipped: &[Skipped],
+    path: &Path,
+) -> Result<()> {
+    let mut cases: Vec<TestCase> = variants
+        .iter()
+        .map(|render| {
+            let coverage = &render.metrics.coverage;
+            TestCase {
+                font: &render.font,
+                duration: render.metrics.duration,
+                failure: (coverage.missing > 0).then(|| {
+                    (
+                        "coverage",
+                        format!("missing glyphs for {} characters", coverage.missing),
+                        coverage.missing_chars.iter().collect(),
+                    )
+                }),
+            }
+        })
+        .chain(skipped.iter().map(|skipped| TestCase {
+            font: &skipped.font,
+            duration: Duration::ZERO,
+            failure: Some(match &skipped.reason {
+                SkipReason::Timeout => ("timeout", skipped.reason.to_string(), String::new()),
+                SkipReason::Error(error, _) => {
+                    ("compile", "compilation failed".to_owned(), error.clone())
+                }
+            }),
+        }))
+        .collect();
+    cases.sort_by(|a, b| {
+        a.font
+            .family
+            .cmp(&b.font.family)
+            .then(a.font.variant.cmp(&b.font.variant))
+    });
+
+    let failures = cases.iter().filter(|case| case.failure.is_some()).count();
+    let time: f64 = cases.iter().map(|case| case.duration.as_secs_f64()).sum();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        xml,
+        r#"<testsuites name="{pkg_name}" tests="{tests}" failures="{failures}" time="{time:.3}">"#,
+        pkg_name = env!("CARGO_PKG_NAME"),
+        tests = cases.len(),
+    )?;
+    writeln!(
+        xml,
+        r#"  <testsuite name="{input}" tests="{tests}" failures="{failures}" errors="0" skipped="0" time="{time:.3}">"#,
+        input = escape(&args.input().display().to_string()),
+        tests = cases.len(),
+    )?;
+    for case in &cases {
+        write!(
+            xml,
+            r#"    <testcase classname="{family}" name="{family} {variant:?}" time="{time:.3}""#,
+            family = escape(&case.font.family),
+            variant = case.font.variant,
+            time = case.duration.as_secs_f64(),
+        )?;
+        match &case.failure {
+            None => writeln!(xml, "/>")?,
+            Some((kind, message, details)) => writeln!(
+                xml,
+                "><failure type=\"{kind}\" message=\"{message}\">{details}</failure></testcase>",
+                message = escape(message),
+                details = escape(details),
+            )?,
+        }
+    }
+    writeln!(xml, "  </testsuite>\n</testsuites>")?;
+    std::fs::write(path, xml)
+        .wrap_err_with(|| format!("failed to write JUnit report to {}", path.display()))
+}
+
+/// Escapes text for use in XML content and attributes.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than whitespace are not allowed in XML 1.0.
+            c if c.is_control() && !matches!(c, '\n' | '\t' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
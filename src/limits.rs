@@ -0,0 +1,197 @@
+//! The memory limit of a single compilation, so that a font that makes the layout degenerate
+//! fails only its variant instead of exhausting the memory of the whole run.
+//!
+//! Typst itself gives up on layouts that don't converge within five iterations and on calls
+//! nested deeper than 80, which can't be configured. The memory is counted by the global
+//! allocator for the thread a compilation runs on, and checked whenever Typst accesses its
+//! world, e.g. to look up a font while shaping text. Once the limit is exceeded, the world
+//! fails to provide files and fonts, so that Typst finishes the compilation quickly with
+//! errors, which are then replaced by an [`Exceeded`] error.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    fmt, mem, ptr,
+    sync::atomic::{AtomicIsize, Ordering},
+};
+
+use typst::diag::{FileError, FileResult};
+
+use crate::format_size;
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// The system allocator, counting the bytes allocated by compilations with a limit.
+///
+/// Each allocation is preceded by a pointer to the counter it was charged to, or null, so that
+/// it is subtracted from the same counter when it is freed, even by another thread.
+struct Counting;
+
+thread_local! {
+    /// The counter of the compilation running on this thread, if it has a limit.
+    static COUNTER: Cell<*const AtomicIsize> = const { Cell::new(ptr::null()) };
+    /// The bytes counted when the current compilation began, how many it may allocate, and
+    /// whether it was found to exceed them.
+    static BUDGET: Cell<Option<(isize, u64, bool)>> = const { Cell::new(None) };
+}
+
+/// The size of the header before each allocation, which keeps the allocation aligned.
+fn header(layout: Layout) -> usize {
+    layout.align().max(mem::size_of::<*const AtomicIsize>())
+}
+
+/// The layout of an allocation including its header.
+fn with_header(layout: Layout) -> Option<Layout> {
+    let size = layout.size().checked_add(header(layout))?;
+    Layout::from_size_align(size, header(layout)).ok()
+}
+
+/// Returns the counter of this thread's compilation, unless it is being torn down.
+fn current() -> *const AtomicIsize {
+    COUNTER.try_with(Cell::get).unwrap_or(ptr::null())
+}
+
+/// Charges a change in size to a counter.
+///
+/// # Safety
+/// The counter must be null or leaked by [`with_limit`].
+unsafe fn charge(counter: *const AtomicIsize, bytes: isize) {
+    if let Some(counter) = counter.as_ref() {
+        counter.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+/// Writes the header of an allocation and returns the pointer handed out for it.
+///
+/// # Safety
+/// `base` must be null or the start of an allocation with the layout of [`with_header`].
+unsafe fn finish(base: *mut u8, layout: Layout) -> *mut u8 {
+    if base.is_null() {
+        return base;
+    }
+    let ptr = base.add(header(layout));
+    let counter = current();
+    ptr.cast::<*const AtomicIsize>().sub(1).write(counter);
+    charge(counter, layout.size() as isize);
+    ptr
+}
+
+/// Returns the counter an allocation was charged to and the start of its allocation.
+///
+/// # Safety
+/// `ptr` must have been returned by [`finish`] for an allocation with this layout.
+unsafe fn owner(ptr: *mut u8, layout: Layout) -> (*const AtomicIsize, *mut u8) {
+    let counter = ptr.cast::<*const AtomicIsize>().sub(1).read();
+    (counter, ptr.sub(header(layout)))
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match with_header(layout) {
+            Some(full) => finish(System.alloc(full), layout),
+            None => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        match with_header(layout) {
+            Some(full) => finish(System.alloc_zeroed(full), layout),
+            None => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let Some(new_full) = with_header(new_layout) else {
+            return ptr::null_mut();
+        };
+        let (counter, base) = owner(ptr, layout);
+        let base = System.realloc(base, with_header(layout).unwrap(), new_full.size());
+        if base.is_null() {
+            return base;
+        }
+        charge(counter, -(layout.size() as isize));
+        finish(base, new_layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (counter, base) = owner(ptr, layout);
+        charge(counter, -(layout.size() as isize));
+        System.dealloc(base, with_header(layout).unwrap());
+    }
+}
+
+/// The error of a compilation that exceeded its memory limit.
+#[derive(Debug)]
+pub(crate) struct Exceeded {
+    limit: u64,
+}
+
+impl fmt::Display for Exceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "it allocated more than the --max-compile-memory of {}",
+            format_size(self.limit as usize)
+        )
+    }
+}
+
+impl std::error::Error for Exceeded {}
+
+/// Runs a compilation on this thread, limiting how many bytes it may allocate.
+///
+/// Fails with [`Exceeded`] instead of returning the compilation's output if it allocated
+/// more than the limit.
+pub(crate) fn with_limit<T>(
+    limit: Option<u64>,
+    compile: impl FnOnce() -> T,
+) -> Result<T, Exceeded> {
+    let Some(limit) = limit else {
+        return Ok(compile());
+    };
+
+    /// Restores the previous budget, also when the compilation panics.
+    struct Restore(Option<(isize, u64, bool)>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            BUDGET.set(self.0);
+        }
+    }
+
+    // The counter outlives the thread, as its allocations may be freed by others later.
+    if COUNTER.get().is_null() {
+        COUNTER.set(Box::leak(Box::new(AtomicIsize::new(0))));
+    }
+    let _restore = Restore(BUDGET.replace(Some((counted(), limit, false))));
+    let output = compile();
+    // Also catches what was allocated after Typst accessed the world for the last time, e.g.
+    // by rasterizing the pages.
+    match check() {
+        Ok(()) => Ok(output),
+        Err(_) => Err(Exceeded { limit }),
+    }
+}
+
+/// The bytes currently charged to this thread's counter.
+fn counted() -> isize {
+    // SAFETY: The counter is null or leaked by `with_limit`.
+    unsafe { COUNTER.get().as_ref() }.map_or(0, |counter| counter.load(Ordering::Relaxed))
+}
+
+/// Fails if the compilation on this thread allocated more than its limit, and keeps failing
+/// until it is finished.
+pub(crate) fn check() -> FileResult<()> {
+    let Some((start, limit, exceeded)) = BUDGET.get() else {
+        return Ok(());
+    };
+    if exceeded || counted() - start > limit as isize {
+        BUDGET.set(Some((start, limit, true)));
+        return Err(FileError::Other(Some(
+            Exceeded { limit }.to_string().into(),
+        )));
+    }
+    Ok(())
+}
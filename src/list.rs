@@ -0,0 +1,47 @@
+//! The `list` subcommand, printing the fonts a comparison would include.
+
+use std::ffi::OsString;
+
+use clap::Parser;
+use color_eyre::eyre::Result;
+
+use crate::{style_name, world, FontSelection};
+
+/// List the fonts and variants that match the selection, one per line.
+#[derive(Parser)]
+#[clap(bin_name = "typst-font-compare list")]
+struct ListArgs {
+    #[clap(flatten)]
+    fonts: FontSelection,
+    /// Also print the path of each font's file.
+    #[clap(long)]
+    paths: bool,
+}
+
+/// Runs the subcommand with the arguments following `list`.
+pub(crate) fn run(args: impl IntoIterator<Item = OsString>) -> Result<()> {
+    let mut args = ListArgs::parse_from(args);
+    args.fonts.expand_font_paths()?;
+    let (book, files) = world::font_files(&args.fonts.font_paths, args.fonts.sources())?;
+    for font in args.fonts.select(&book, |index| &files[index])? {
+        let variant = font.variant;
+        print!(
+            "{family}\t{style}\t{weight}\t{stretch}%",
+            family = font.family,
+            style = style_name(variant.style),
+            weight = variant.weight.to_number(),
+            stretch = variant.stretch.to_ratio().get() * 100.0,
+        );
+        if args.paths {
+            let path = book
+                .select(&font.family.to_lowercase(), variant)
+                .and_then(|index| files[index].path.as_ref());
+            match path {
+                Some(path) => print!("\t{}", path.display()),
+                None => print!("\tembedded"),
+            }
+        }
+        println!();
+    }
+    Ok(())
+}
@@ -0,0 +1,172 @@
+//! Translations of the text the collection adds around the renders.
+
+use clap::ValueEnum;
+
+use crate::specimen::SpecimenKind;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Locale {
+    /// English.
+    En,
+    /// German.
+    De,
+}
+
+/// The text of the collection in one language.
+///
+/// `{count}`, `{fonts}`, `{variants}` and the other names in braces are replaced where the text
+/// mentions them.
+pub(crate) struct Strings {
+    /// The ISO 639 code of the language, for hyphenation and quotes.
+    pub(crate) lang: &'static str,
+    pub(crate) fonts: &'static str,
+    pub(crate) created_using: &'static str,
+    pub(crate) created_per_family: &'static str,
+    pub(crate) partial: &'static str,
+    pub(crate) partial_variants: &'static str,
+    pub(crate) partial_families: &'static str,
+    pub(crate) overview: &'static str,
+    pub(crate) previous: &'static str,
+    pub(crate) next: &'static str,
+    pub(crate) similarity_to: &'static str,
+    pub(crate) rank: &'static str,
+    pub(crate) font: &'static str,
+    pub(crate) family: &'static str,
+    pub(crate) variants: &'static str,
+    pub(crate) file: &'static str,
+    pub(crate) similarity: &'static str,
+    pub(crate) identical: &'static str,
+    pub(crate) similar: &'static str,
+    pub(crate) scaled_to_baseline: &'static str,
+    pub(crate) scaled_to_average: &'static str,
+    pub(crate) scaled_size: &'static str,
+    pub(crate) sizes_for_baseline: &'static str,
+    pub(crate) sizes_for_average: &'static str,
+    pub(crate) size_instead: &'static str,
+    pub(crate) x_height: &'static str,
+    pub(crate) cap_height: &'static str,
+    pub(crate) font_files: &'static str,
+    pub(crate) format: &'static str,
+    pub(crate) glyphs: &'static str,
+    pub(crate) tables: &'static str,
+    pub(crate) size: &'static str,
+    pub(crate) embedded: &'static str,
+    pub(crate) excluded: &'static str,
+    pub(crate) excluded_description: &'static str,
+    pub(crate) reason: &'static str,
+    pub(crate) missing: &'static str,
+    pub(crate) missing_description: &'static str,
+    pub(crate) coverage: &'static str,
+    pub(crate) characters: &'static str,
+}
+
+const EN: Strings = Strings {
+    lang: "en",
+    fonts: "Fonts",
+    created_using: "Comparison created using",
+    created_per_family: "One comparison per family, created using",
+    partial: "This comparison is partial.",
+    partial_variants:
+        "Rendering was interrupted, so only the {count} variants rendered until then are included.",
+    partial_families: "Rendering was interrupted, so some families are missing.",
+    overview: "Overview",
+    previous: "← Previous",
+    next: "Next →",
+    similarity_to: "Similarity to",
+    rank: "Rank",
+    font: "Font",
+    family: "Family",
+    variants: "Variants",
+    file: "File",
+    similarity: "Similarity",
+    identical: "Rendered identically by {fonts}.",
+    similar: "Also stands for the nearly indistinguishable variants {variants}.",
+    scaled_to_baseline: "Scaled to {factor} to match the {height} of {baseline}.",
+    scaled_to_average: "Scaled to {factor} to match the average {height} of the fonts.",
+    scaled_size: "Use {size} instead of {original} for the same {height}.",
+    sizes_for_baseline: "Sizes matching the {height} of {baseline}:",
+    sizes_for_average: "Sizes matching the average {height}:",
+    size_instead: "{size} instead of {original}",
+    x_height: "x-height",
+    cap_height: "cap height",
+    font_files: "Font Files",
+    format: "Format",
+    glyphs: "Glyphs",
+    tables: "Tables",
+    size: "Size",
+    embedded: "embedded",
+    excluded: "Excluded Fonts",
+    excluded_description: "These fonts match the filters, but are not part of the comparison.",
+    reason: "Reason",
+    missing: "Missing Characters",
+    missing_description: "These fonts lack glyphs for some of the document's characters.",
+    coverage: "Coverage",
+    characters: "Characters",
+};
+
+const DE: Strings = Strings {
+    lang: "de",
+    fonts: "Schriften",
+    created_using: "Vergleich erstellt mit",
+    created_per_family: "Ein Vergleich pro Schriftfamilie, erstellt mit",
+    partial: "Dieser Vergleich ist unvollständig.",
+    partial_variants: "Das Rendern wurde unterbrochen, daher sind nur die {count} bis dahin gerenderten Varianten enthalten.",
+    partial_families: "Das Rendern wurde unterbrochen, daher fehlen einige Schriftfamilien.",
+    overview: "Übersicht",
+    previous: "← Zurück",
+    next: "Weiter →",
+    similarity_to: "Ähnlichkeit zu",
+    rank: "Rang",
+    font: "Schrift",
+    family: "Schriftfamilie",
+    variants: "Varianten",
+    file: "Datei",
+    similarity: "Ähnlichkeit",
+    identical: "Identisch dargestellt von {fonts}.",
+    similar: "Steht auch für die kaum unterscheidbaren Varianten {variants}.",
+    scaled_to_baseline: "Auf {factor} skaliert, damit die {height} der von {baseline} entspricht.",
+    scaled_to_average: "Auf {factor} skaliert, damit die {height} dem Durchschnitt der Schriften entspricht.",
+    scaled_size: "Für dieselbe {height} {size} statt {original} verwenden.",
+    sizes_for_baseline: "Größen für dieselbe {height} wie {baseline}:",
+    sizes_for_average: "Größen für die durchschnittliche {height}:",
+    size_instead: "{size} statt {original}",
+    x_height: "x-Höhe",
+    cap_height: "Versalhöhe",
+    font_files: "Schriftdateien",
+    format: "Format",
+    glyphs: "Glyphen",
+    tables: "Tabellen",
+    size: "Größe",
+    embedded: "eingebettet",
+    excluded: "Ausgeschlossene Schriften",
+    excluded_description: "Diese Schriften passen zu den Filtern, sind aber nicht Teil des Vergleichs.",
+    reason: "Grund",
+    missing: "Fehlende Zeichen",
+    missing_description: "Diesen Schriften fehlen Glyphen für einige Zeichen des Dokuments.",
+    coverage: "Abdeckung",
+    characters: "Zeichen",
+};
+
+impl Locale {
+    pub(crate) fn strings(self) -> &'static Strings {
+        match self {
+            Locale::En => &EN,
+            Locale::De => &DE,
+        }
+    }
+
+    /// The title shown above a specimen in the collection.
+    pub(crate) fn specimen_title(self, kind: SpecimenKind) -> &'static str {
+        match (self, kind) {
+            (Locale::En, _) => kind.title(),
+            (Locale::De, SpecimenKind::Numerals) => "Ziffern",
+            (Locale::De, SpecimenKind::StylisticSets) => "Stilsets",
+            (Locale::De, SpecimenKind::CharacterVariants) => "Zeichenvarianten",
+            (Locale::De, SpecimenKind::Alternates) => "Alternativen",
+            (Locale::De, SpecimenKind::Waterfall) => "Wasserfall",
+            (Locale::De, SpecimenKind::Charmap) => "Zeichentabelle",
+            (Locale::De, SpecimenKind::Kerning) => "Unterschneidung",
+            (Locale::De, SpecimenKind::Languages) => "Sprachen",
+        }
+    }
+}
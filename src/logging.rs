@@ -0,0 +1,129 @@
+//! Progress and diagnostic messages, printed to stderr as text or JSON lines.
+
+use std::{
+    io::Write,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// How log messages are printed.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LogFormat {
+    /// Human-readable messages.
+    Text,
+    /// One JSON object per line, containing the event name and all its fields.
+    Json,
+}
+
+/// The importance of a log message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Level {
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+}
+
+struct Logger {
+    max_level: Level,
+    format: LogFormat,
+    start: Instant,
+}
+
+/// Configures logging. Events emitted before are printed with the default settings.
+///
+/// `verbosity` is the number of `--verbose` flags, or `-1` for `--quiet`.
+/// Fatal errors are not logged but reported when exiting.
+pub(crate) fn init(verbosity: i8, format: LogFormat) {
+    let max_level = match verbosity {
+        ..=-1 => Level::Warn,
+        0 => Level::Info,
+        1 => Level::Debug,
+        _ => Level::Trace,
+    };
+    let _ = LOGGER.set(Logger {
+        max_level,
+        format,
+        start: Instant::now(),
+    });
+}
+
+/// A log message with structured fields, printed once [emitted](Event::emit).
+#[must_use = "events are only printed when emitted"]
+pub(crate) struct Event {
+    level: Level,
+    name: &'static str,
+    message: String,
+    fields: Map<String, Value>,
+}
+
+impl Event {
+    /// Creates an event with a stable machine-readable name and a human-readable message.
+    pub(crate) fn new(level: Level, name: &'static str, message: impl Into<String>) -> Event {
+        Event {
+            level,
+            name,
+            message: message.into(),
+            fields: Map::new(),
+        }
+    }
+
+    /// Attaches a field, which is only printed in the JSON format.
+    pub(crate) fn field(mut self, key: &str, value: impl Into<Value>) -> Event {
+        self.fields.insert(key.to_owned(), value.into());
+        self
+    }
+
+    /// Attaches a duration in seconds.
+    pub(crate) fn duration(self, key: &str, duration: Duration) -> Event {
+        self.field(key, duration.as_secs_f64())
+    }
+
+    /// Prints the event if its level is enabled.
+    pub(crate) fn emit(self) {
+        let logger = LOGGER.get_or_init(|| Logger {
+            max_level: Level::Info,
+            format: LogFormat::Text,
+            start: Instant::now(),
+        });
+        if self.level > logger.max_level {
+            return;
+        }
+
+        let mut stderr = std::io::stderr().lock();
+        let _ = match logger.format {
+            LogFormat::Text => match self.level {
+                Level::Warn => writeln!(stderr, "warning: {}", self.message),
+                _ => writeln!(stderr, "{}", self.message),
+            },
+            LogFormat::Json => {
+                let mut object = Map::new();
+                object.insert("level".into(), self.level.name().into());
+                object.insert("event".into(), self.name.into());
+                object.insert("message".into(), self.message.into());
+                object.insert(
+                    "elapsed".into(),
+                    logger.start.elapsed().as_secs_f64().into(),
+                );
+                object.extend(self.fields);
+                writeln!(stderr, "{}", Value::Object(object))
+            }
+        };
+    }
+}
@@ -1,46 +1,589 @@
+mod archive;
+mod autocrop;
+mod blink;
+mod bundle;
+mod cache;
+mod config;
+mod dedup;
+mod diagnostics;
+mod diff;
+mod features;
+mod font_index;
+mod font_names;
+mod glob;
+mod glyphs;
+mod guides;
+mod header;
+mod html;
+mod interrupt;
+mod junit;
+mod limits;
+mod list;
+mod locale;
+mod logging;
+mod markdown;
+mod memory;
+mod metrics;
+mod overrides;
+mod paths;
+#[cfg(unix)]
+mod picker;
+mod plan;
+mod post_render;
+mod raster;
+mod region;
+mod retry;
+mod samples;
+mod sandbox;
+mod serve;
+mod sheet;
+mod snapshot;
+mod source_date;
+mod specimen;
+mod template;
+mod timings;
 mod world;
 
-use std::{fmt::Write, path::PathBuf};
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fmt::{self, Write},
+    ops::{Range, RangeInclusive},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
 
-use clap::{Parser, ValueEnum};
-use color_eyre::eyre::{eyre, Context, Result};
+use clap::{ArgAction, Parser, ValueEnum};
+use color_eyre::eyre::{eyre, Context, Report, Result};
+use config::Config;
+use diagnostics::{CompileError, Diagnostic};
+use glob::Glob;
+use header::PageTemplate;
+use locale::Locale;
+use logging::{Event, Level, LogFormat};
+use memory::MemoryLimit;
+use metrics::{Coverage, Metrics, Scaled};
+use overrides::Overrides;
+use post_render::PostRender;
+use raster::Palette;
 use rayon::prelude::*;
-use regex::Regex;
+use regex::RegexBuilder;
+use region::Region;
+use samples::Script;
+use serde_json::json;
+use specimen::SpecimenKind;
+use timings::Phase;
+use tiny_skia::IntRect;
 use typst::{
     eval::Tracer,
-    foundations::{Bytes, Smart, StyleChain},
-    layout::Abs,
-    text::{FontFamily, FontInfo, TextElem},
-    visualize::Color,
+    foundations::{Bytes, Dict, IntoValue, Label, Smart},
+    introspection::Meta,
+    layout::{Abs, Em, Frame, FrameItem, GroupItem, Length, Page, Point, Size},
+    model::{Destination, Document, NumberingPattern},
+    syntax::Span,
+    text::{Font, FontBook, FontInfo, FontVariant, Lang},
+    visualize::{self, Color, ImageFormat, RasterFormat},
+    World,
 };
-use world::SystemWorld;
+use world::{FileData, FontFile, FontSources, SystemWorld};
 
 /// A tool to compare how Typst documents would look using different fonts or font variants.
 #[derive(Parser)]
+#[clap(after_help = "\
+Commands, each with its own --help:
+  compare  Compare the fonts, the default without a command
+  list     List the fonts that would be compared
+  diff     Write an image of where the renders of two families differ
+  test     Compare the renders against stored reference images
+  glyphs   Compare two fonts glyph by glyph, e.g. a fork or an update of a font
+  specimen Write a type specimen of a single family, without a document
+  serve    Show the HTML gallery on a local web server
+  cache    Manage the files kept between runs")]
 struct Args {
     /// Path to the Typst input file.
-    input: PathBuf,
-    /// Path to the output PDF.
+    #[clap(required_unless_present = "dump_template")]
+    input: Option<PathBuf>,
+    /// Path to the output file.
+    ///
+    /// For an `input.typ`, the output will be `input.variants.pdf`,
+    /// or `input.variants.zip`, `input.variants.md` and `input.variants.html` for the other formats.
     ///
-    /// For an `input.typ`, the output will be `input.variants.pdf`.
+    /// With `-`, a PDF or ZIP archive is written to stdout instead, e.g. to pipe it into
+    /// another program. With several formats, the extension of the output is replaced
+    /// for each of them.
     #[clap(short, long)]
     output: Option<PathBuf>,
-    /// Whether to try each variant (style, weight, stretch).
-    #[clap(short, long)]
-    variants: bool,
+    /// The format of the output, or several formats separated by commas, like `pdf,html`,
+    /// which are all written from the same renders.
+    #[clap(long, default_value = "pdf", value_delimiter = ',')]
+    format: Vec<Format>,
+    /// Adapt the fonts, colors and layout of the HTML gallery, e.g. to embed it into a
+    /// documentation site.
+    ///
+    /// A `.css` file is added after the gallery's styles. A `.toml` file sets some of its
+    /// properties, with the keys `font`, `background`, `text`, `accent`, `link`, `border`,
+    /// `margin`, `column-width` and `gap`.
+    #[clap(long, value_name = "FILE")]
+    html_theme: Option<PathBuf>,
+    /// Write a separate PDF for each font family into a `variants` directory next to the output.
+    ///
+    /// The output itself then becomes an index of the families.
+    #[clap(long, conflicts_with = "only")]
+    split_by_family: bool,
+    /// Write each page of the document in every font on consecutive pages of the same size
+    /// and layout, so that paging through the PDF flips between the fonts like a blink
+    /// comparator.
+    #[clap(long, conflicts_with_all = ["split_by_family", "vector"])]
+    blink: bool,
+    /// Place the renders of this many fonts side by side on each page of the collection,
+    /// e.g. to print fewer pages.
+    #[clap(long, default_value_t = 1, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=3), conflicts_with = "vector")]
+    columns: u32,
+    /// The paper size of the collection's pages, instead of pages as wide as the renders
+    /// and as tall as their content.
+    ///
+    /// Renders that don't fit onto a page are scaled down.
+    #[clap(long, default_value = "auto")]
+    paper: Paper,
+    /// Turn the collection's pages of a paper size sideways, e.g. for wide renders.
+    #[clap(long)]
+    landscape: bool,
+    /// The colors of the collection's pages, text and links.
+    ///
+    /// The rasterized renders are recolored to match, so that the dark theme suits dark slide
+    /// decks. Vector pages keep their colors.
+    #[clap(long, default_value = "light")]
+    theme: Theme,
+    /// The spacing and sizes of the collection's pages, to match a house style.
+    #[clap(long, default_value = "regular")]
+    layout: Layout,
+    /// Add a contact sheet of all renders after the outline of the collection, with each
+    /// thumbnail linking to its font's page.
+    #[clap(long, conflicts_with = "vector")]
+    overview: bool,
+    /// The margin of the collection's pages, like `2cm`, instead of the one of the layout.
+    #[clap(long, value_name = "LENGTH", value_parser = region::parse_length)]
+    collection_margin: Option<Abs>,
+    /// The size of the collection's labels and headings, like `12pt`, instead of the one
+    /// of the layout.
+    #[clap(long, value_name = "LENGTH", value_parser = region::parse_length)]
+    label_size: Option<Abs>,
+    /// The header of the variant pages instead of the title and navigation, like
+    /// `"{family} — {variant} — {date}"`.
+    ///
+    /// The placeholders `{family}`, `{variant}`, `{date}` and `{page}` are filled in for each
+    /// page.
+    #[clap(long, value_name = "TEMPLATE")]
+    header: Option<PageTemplate>,
+    /// The footer of the variant pages, with the same placeholders as `--header`.
+    #[clap(long, value_name = "TEMPLATE")]
+    footer: Option<PageTemplate>,
+    /// Lay out the collection with this Typst template instead of the embedded one, e.g. a
+    /// modified copy of `--dump-template`.
+    ///
+    /// Its slots like `{{variants}}` are filled in before compiling, as described at its top.
+    #[clap(long, value_name = "FILE")]
+    template: Option<PathBuf>,
+    /// The numbering of the collection's pages, as a Typst numbering pattern like `i` or `— 1 —`.
+    #[clap(long, default_value = "1", value_name = "PATTERN", value_parser = parse_numbering)]
+    page_numbering: String,
+    /// The number of the collection's first page.
+    #[clap(long, default_value_t = 1, value_name = "N")]
+    first_page_number: u32,
+    /// Start the page numbers anew at the first page of each font family.
+    #[clap(long)]
+    restart_page_numbers: bool,
+    /// The language of the headings, labels and notes the collection adds around the renders,
+    /// and of the sizes reported with `--normalize`.
+    #[clap(long, default_value = "en")]
+    locale: Locale,
+    #[clap(flatten)]
+    fonts: FontSelection,
+    /// Choose among the matching fonts in an interactive list before rendering, which is
+    /// filtered by fuzzy search while typing. Only supported on Unix.
+    #[clap(long, conflicts_with = "only")]
+    pick: bool,
     /// Whether to enable font fallback.
     #[clap(short, long)]
     fallback: bool,
-    /// Only include font families that match this regular expression.
+    /// Fall back to these families in order, like `"Noto Sans, Noto Color Emoji"`, instead of
+    /// Typst's built-in fallback, e.g. to match the fallback of production.
+    #[clap(
+        long,
+        value_name = "FAMILIES",
+        value_delimiter = ',',
+        requires = "fallback"
+    )]
+    fallback_list: Vec<String>,
+    /// Which pages of the document to render, e.g. `1,3-5`.
+    ///
+    /// By default, all pages are rendered.
+    #[clap(long, value_delimiter = ',')]
+    pages: Vec<PageRange>,
+    /// Whether to append a page with technical information about each font file.
+    #[clap(long)]
+    font_info: bool,
+    /// Draw guide lines at the ascender (orange), cap height (green), x-height (blue),
+    /// baseline (red) and descender (purple) of the first line of body text of each render.
+    #[clap(long)]
+    show_metrics: bool,
+    /// Print the size of the text block and the average number of characters per line in the
+    /// top margin of each page.
+    #[clap(long)]
+    show_measurements: bool,
+    /// Render each page with justified paragraphs, followed by the same page ragged-right,
+    /// unless the document sets the justification itself.
+    ///
+    /// Shows how well the spacing of each font holds up under justification.
+    #[clap(long)]
+    compare_justify: bool,
+    /// Add a magnified inset of a region of the first page next to each render, given as
+    /// `x,y,width,height`, e.g. `2cm,3cm,6cm,2cm`.
+    ///
+    /// With `--zoom-label`, the region is relative to the labelled element.
+    #[clap(long, value_name = "REGION", conflicts_with = "vector")]
+    zoom: Option<Region>,
+    /// Zoom into the element with this label, e.g. `intro` for `<intro>`.
+    ///
+    /// Shows a region of 6cm by 2cm starting at the element, unless `--zoom` is given.
+    #[clap(long, value_name = "LABEL", conflicts_with = "vector")]
+    zoom_label: Option<String>,
+    /// Render only a region of each selected page, given as `x,y,width,height`,
+    /// e.g. `2.5cm,4cm,16cm,5cm`.
+    ///
+    /// Keeps the renders small when only a part of the document matters.
+    #[clap(long, value_name = "REGION")]
+    crop: Option<Region>,
+    /// Trim the margins of uniform background around the content of each page and specimen,
+    /// so that short samples don't leave most of the collection's pages empty.
+    ///
+    /// Each page is trimmed to the content of that page with any font, so that the renders
+    /// stay aligned with each other. With `--vector`, only the specimens are trimmed.
+    #[clap(long)]
+    autocrop: bool,
+    /// Additional specimens to render for each font.
+    #[clap(long, value_delimiter = ',')]
+    specimen: Vec<SpecimenKind>,
+    /// Render this line at a descending series of sizes for each font.
+    ///
+    /// Adds the waterfall specimen, which otherwise shows a pangram, or a sample paragraph in
+    /// the main language of the document if it is not English.
+    #[clap(long, value_name = "TEXT")]
+    waterfall: Option<String>,
+    /// Render the pangram of this script in the waterfall, e.g. `cyrillic` or `greek`.
+    ///
+    /// Adds the waterfall specimen, like `--waterfall` with the pangram.
+    #[clap(long, value_name = "SCRIPT", conflicts_with = "waterfall")]
+    pangram: Option<Script>,
+    /// Append a map of the glyphs for the characters of the document's script to each font,
+    /// labeled with their code points.
+    ///
+    /// Shows the rows of 128 code points of the Unicode table that the document's text uses,
+    /// or the given comma-separated ranges like `U+0370-U+03FF`.
+    #[clap(
+        long,
+        value_name = "RANGES",
+        num_args = 0..,
+        require_equals = true,
+        value_delimiter = ',',
+        value_parser = parse_code_points,
+    )]
+    charmap: Option<Vec<RangeInclusive<u32>>>,
+    /// Append the kerning of critical pairs of characters like `AV` and `To` to each font.
+    ///
+    /// Shows a list of common pairs, or the given comma-separated pairs.
+    #[clap(
+        long,
+        value_name = "PAIRS",
+        num_args = 0..,
+        require_equals = true,
+        value_delimiter = ','
+    )]
+    kerning: Option<Vec<String>>,
+    /// Append a sample paragraph in each of these comma-separated languages to each font,
+    /// e.g. `de,ru,el,ar,zh`.
+    ///
+    /// Shows the first article of the Universal Declaration of Human Rights, to evaluate the
+    /// coverage and appearance of a font for multilingual documents together. Without it, the
+    /// languages specimen shows the languages set with `text.lang` in the document.
+    #[clap(long, value_name = "LANGS", value_delimiter = ',', value_parser = parse_language)]
+    sample_lang: Vec<String>,
+    /// Hyphenate the text in this language, e.g. `de`, unless the document sets the language
+    /// or hyphenation itself.
+    ///
+    /// Makes the hyphenation part of the comparison without editing the document.
+    #[clap(long, value_name = "LANG", value_parser = parse_lang)]
+    hyphenate_lang: Option<Lang>,
+    /// Set the spacing between the lines of paragraphs, e.g. `0.7em` or `3mm`, unless the
+    /// document sets it itself.
+    ///
+    /// Normalizes the line spacing of fonts with very different vertical metrics.
+    #[clap(long, value_name = "LENGTH", value_parser = parse_leading)]
+    leading: Option<Length>,
+    /// Scale the size of each font so that all render with the same x-height or cap height,
+    /// making their shapes comparable although they are drawn at different sizes for the
+    /// same font size.
+    ///
+    /// The fonts are scaled to the height of the `--baseline` if it is rendered, otherwise to
+    /// their average height. Text sizes set by the document are scaled as well, and so are
+    /// equations and the spacing between lines and paragraphs. Other lengths in `em`, like
+    /// those of `#v`, `#h`, boxes and blocks, keep their size, as they don't belong to text.
+    #[clap(long, value_name = "HEIGHT")]
+    normalize: Option<Normalize>,
+    /// Prepend the content of this Typst file to the document of each font, e.g. to add set
+    /// and show rules without editing the document.
+    ///
+    /// Its rules apply to the whole document, and relative paths in it start at the root. The
+    /// document is included after it rather than pasted into it, so the variables, functions
+    /// and imports of the preamble are not visible in the document.
+    #[clap(long, value_name = "FILE")]
+    preamble: Option<PathBuf>,
+    /// Apply extra styles to the fonts whose family matches a pattern, read from this TOML file,
+    /// e.g. a larger size for fonts that appear small.
+    ///
+    /// Each `[[override]]` table has a `family` regex and the `styles` to apply as Typst code,
+    /// like `"#set text(ligatures: false)"`. The styles also apply to the specimens.
+    #[clap(long, value_name = "FILE")]
+    overrides: Option<PathBuf>,
+    /// Provide the font to the document through `sys.inputs`, as its `family`, `style`,
+    /// `weight` and `stretch`, e.g. to adapt the size for fonts that appear small.
+    ///
+    /// The document is then evaluated anew for each font, which makes the run slower.
+    #[clap(long)]
+    font_inputs: bool,
+    /// Specify a different project root folder.
+    #[clap(long, env = "TYPST_ROOT", value_name = "DIR")]
+    root: Option<PathBuf>,
+    /// Only let the document read files within the root and the package directories, even
+    /// through symbolic links, e.g. to compare fonts for untrusted documents.
+    ///
+    /// The files that were read and denied are listed in the `--diagnostics` file.
+    #[clap(long)]
+    sandbox: bool,
+    /// Guarantee that nothing is downloaded, e.g. for air-gapped build environments.
+    ///
+    /// Packages are only loaded from the local package directories, and a missing one fails
+    /// with an error saying that it would have to be downloaded. `serve` still listens on
+    /// localhost.
+    #[clap(long)]
+    offline: bool,
+    /// The resolution to render the embedded variant content to.
+    #[clap(long, default_value_t = 300.0)]
+    ppi: f32,
+    /// Render at this multiple of the resolution and downsample afterwards.
+    ///
+    /// Improves the quality of small text without increasing the size of the embedded images.
+    #[clap(long, default_value_t = 1, value_name = "FACTOR", value_parser = clap::value_parser!(u32).range(1..=8))]
+    supersample: u32,
+    /// Embed the variants as vector pages instead of images, keeping their text selectable.
+    ///
+    /// Specimens are still embedded as images.
+    #[clap(long, conflicts_with = "only")]
+    vector: bool,
+    /// Run this command on each rendered image before it is embedded, e.g. `"optipng {png}"`
+    /// to optimize it or a script annotating it.
+    ///
+    /// The shell runs it with `{png}` replaced by the path of the image, which it may change
+    /// in place. The environment variables `TYPST_FONT_COMPARE_FAMILY` and
+    /// `TYPST_FONT_COMPARE_VARIANT` name the font, and `TYPST_FONT_COMPARE_IMAGE` is `document`
+    /// or the kind of specimen. A font whose images fail or change their size is skipped.
+    ///
+    /// It can't be set in a configuration file.
+    #[clap(long, value_name = "COMMAND", value_parser = post_render::parse_command)]
+    post_render: Option<String>,
+    /// Skip a font if compiling the document with it takes longer than this, e.g. `90s` or `2m`.
+    ///
+    /// Guards against fonts that make the layout hang. Like fonts that the document fails to
+    /// compile with, the font is skipped with a warning and listed in an appendix.
+    #[clap(long, value_parser = parse_duration)]
+    timeout: Option<Duration>,
+    /// Fail a font if a single compilation with it allocates more than this, e.g. `2G`.
+    ///
+    /// Guards against fonts that make the layout consume all memory. Typst itself gives up on
+    /// layouts that don't converge within five iterations and on calls nested deeper than 80.
+    #[clap(long, value_name = "SIZE", value_parser = memory::parse_size)]
+    max_compile_memory: Option<u64>,
+    /// Compile a font up to this many more times if it fails with an error that might be
+    /// transient, like a failure to read a file, before reporting it as failed.
+    ///
+    /// With `--cache-policy aggressive`, the caches are cleared before each attempt, freeing
+    /// their memory. They are shared with the fonts compiled in parallel, so they are kept
+    /// otherwise.
+    #[clap(long, default_value_t = 0, value_name = "N")]
+    retries: u32,
+    /// Keep the memory usage below this size, e.g. `4G` or `512M`.
+    ///
+    /// When approaching the limit, fonts are compiled one at a time, caches are cleared
+    /// and finished renders are moved to disk. Only supported on Linux.
+    #[clap(long, value_name = "SIZE", value_parser = memory::parse_size)]
+    max_memory: Option<u64>,
+    /// Abort before rendering if the outputs are estimated to be larger than this, e.g. `500M`.
+    ///
+    /// The estimate is based on the number of fonts and pages, the resolution and the
+    /// specimens, with the pages counted by compiling the document once unless `--pages`
+    /// selects a fixed number of them. Without a limit, the document isn't compiled, and
+    /// outputs estimated to be larger than 1 GiB for a single page are only warned about.
+    #[clap(long, value_name = "SIZE", value_parser = memory::parse_size)]
+    max_output_size: Option<u64>,
+    /// When to evict cached compilation results.
+    #[clap(long, default_value = "balanced")]
+    cache_policy: CachePolicy,
+    /// The family to compare the other fonts against for the similarity score.
+    ///
+    /// The score measures how similar the renders look, from 0 to 1.
+    #[clap(long, value_name = "FAMILY")]
+    baseline: Option<String>,
+    /// Write an image of the differences of each page to the same page of the baseline into
+    /// this directory, named by font and page, like `Noto-Serif-normal-400-1000-page-2.png`.
+    ///
+    /// `diff` compares the pages of the second family to those of the first and `test` to
+    /// those of the references.
+    #[clap(long, value_name = "DIR")]
+    page_diffs: Option<PathBuf>,
+    /// The colors of ink only in the first and only in the second render in images of their
+    /// differences.
+    #[clap(long, default_value = "red-cyan")]
+    palette: Palette,
+    /// With `--variants`, merge each variant into an earlier variant of its family
+    /// if their renders are at least this similar, e.g. `0.99`.
+    ///
+    /// Reduces the size of the collection when weights barely differ on a short sample.
+    #[clap(long, value_name = "SIMILARITY", value_parser = parse_similarity)]
+    merge_similar: Option<f64>,
+    /// Write the metrics of each font to this CSV file, e.g. its coverage and compile time.
+    #[clap(long, value_name = "FILE")]
+    metrics_csv: Option<PathBuf>,
+    /// Write a JUnit XML report to this file, with a test case for each font.
+    ///
+    /// A font passes if the document compiles with it and it covers all characters. Fonts that
+    /// fail are skipped in the collection, as they are without a report.
+    #[clap(long, value_name = "FILE")]
+    junit: Option<PathBuf>,
+    /// Write the compile errors, warnings and missing characters of each font to this JSON file.
+    ///
+    /// If fonts failed, the renders are kept next to it for `--retry-failed`, e.g. in
+    /// `diagnostics.renders` for `diagnostics.json`.
+    #[clap(long, value_name = "FILE")]
+    diagnostics: Option<PathBuf>,
+    /// Record the phases of the run and the compilation of each font to this JSON file, in
+    /// the Chrome trace format that Perfetto and `chrome://tracing` display as a timeline.
+    ///
+    /// Shows where the time of long runs goes, e.g. which fonts hold up the others.
+    #[clap(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+    /// Render only the fonts that failed in the run that wrote the `--diagnostics` file,
+    /// and update their entries in it.
+    ///
+    /// The outputs are written again with the renders that run kept of the other fonts, so
+    /// that they contain every font that compiled. The settings of the renders must not have
+    /// changed since.
+    #[clap(long, requires = "diagnostics", conflicts_with_all = ["only", "vector"])]
+    retry_failed: bool,
+    /// Use the settings of a preset for all options that are not given explicitly.
+    #[clap(long)]
+    preset: Option<Preset>,
+    /// Path to a configuration file providing defaults for the other options.
+    ///
+    /// Its keys are the long option names, e.g. `ppi = 150` or `style = ["normal", "italic"]`.
+    /// Defaults to `typst-font-compare.toml` in the current directory
+    /// or in the user configuration directory, if it exists.
+    #[clap(long, env = "TYPST_FONT_COMPARE_CONFIG", value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Open the output after it was written, with the given viewer or the default application.
+    #[clap(long, value_name = "VIEWER", require_equals = true)]
+    open: Option<Option<String>>,
+    /// Print the effective configuration and where each value comes from, then exit.
+    #[clap(long)]
+    print_config: bool,
+    /// Print the embedded template of the collection, to be modified and used with
+    /// `--template`, then exit.
+    #[clap(long)]
+    dump_template: bool,
+    /// Print the fonts that would be rendered in order, how many images that makes and the
+    /// estimated size of the outputs, then exit without compiling anything.
+    #[clap(long)]
+    dry_run: bool,
+    /// Print more detailed progress, including timings. Can be given twice, like `-VV`.
+    ///
+    /// The short flag is `-V`, as `-v` is already short for `--variants`.
+    #[clap(short = 'V', long, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    /// Only print warnings and errors.
+    #[clap(short, long)]
+    quiet: bool,
+    /// The format of progress and diagnostic messages.
+    #[clap(long, default_value = "text")]
+    log_format: LogFormat,
+}
+
+impl Args {
+    /// The path of the input, which is only missing with `--dump-template`.
+    fn input(&self) -> &Path {
+        self.input
+            .as_deref()
+            .expect("the input is required without --dump-template")
+    }
+}
+
+/// Which fonts and variants to compare.
+#[derive(clap::Args)]
+struct FontSelection {
+    /// Whether to try each variant (style, weight, stretch).
+    #[clap(short, long)]
+    variants: bool,
+    /// Only include fonts whose family, PostScript name or full name match this regular
+    /// expression.
     ///
     /// The exclude regex takes priority over this regex.
     #[clap(short = 'i', long)]
     include: Option<String>,
-    /// Exclude font families that match this regular expression.
+    /// Exclude fonts whose family, PostScript name or full name match this regular expression.
     ///
     /// Takes priority over the include regex.
     #[clap(short = 'e', long)]
     exclude: Option<String>,
+    /// Only include fonts whose family, PostScript name or full name match this glob pattern,
+    /// like `'Noto {Sans,Serif}*'`, instead of a regular expression.
+    ///
+    /// `*` matches any text, `?` a single character, `[a-z]` one of the characters and
+    /// `{a,b}` one of the alternatives.
+    #[clap(long, value_name = "GLOB", conflicts_with = "include")]
+    include_glob: Option<Glob>,
+    /// Exclude fonts whose family, PostScript name or full name match this glob pattern,
+    /// instead of a regular expression.
+    #[clap(long, value_name = "GLOB", conflicts_with = "exclude")]
+    exclude_glob: Option<Glob>,
+    /// Match the include and exclude patterns regardless of case.
+    #[clap(long)]
+    ignore_case: bool,
+    /// Only match the include and exclude regexes against whole names, as if they started
+    /// with `^` and ended with `$`.
+    #[clap(long)]
+    full_match: bool,
+    /// Quickly compare only these two font families, side by side on a single page.
+    ///
+    /// Skips the outline, the overview and the appendices of the collection.
+    #[clap(
+        long,
+        num_args = 2,
+        value_names = ["FAMILY", "FAMILY"],
+        conflicts_with_all = [
+            "include",
+            "exclude",
+            "include_glob",
+            "exclude_glob",
+            "variants",
+        ],
+    )]
+    only: Vec<String>,
     /// Which font styles to check.
     #[clap(long, default_value = "normal")]
     style: Vec<FontStyle>,
@@ -50,10 +593,10 @@ struct Args {
     /// Which font stretch values to check.
     #[clap(long)]
     stretch: Vec<FontStretch>,
-    /// Specify a different project root folder.
-    #[clap(long, env = "TYPST_ROOT", value_name = "DIR")]
-    root: Option<PathBuf>,
     /// Adds additional directories to search for fonts in.
+    ///
+    /// Can be given multiple times or as a comma-separated list.
+    /// A leading `~` and environment variables like `$HOME` are expanded.
     #[clap(
         long = "font-path",
         env = "TYPST_FONT_PATHS",
@@ -61,241 +604,2359 @@ struct Args {
         value_delimiter = if cfg!(windows) { ';' } else { ':' },
     )]
     font_paths: Vec<PathBuf>,
-    /// The resolution to render the embedded variant content to.
-    #[clap(long, default_value_t = 300.0)]
-    ppi: f32,
+    /// Only use the fonts from `--font-path` and Typst's embedded fonts, not the ones installed
+    /// on the system, e.g. for reproducible comparisons in CI.
+    #[clap(long)]
+    ignore_system_fonts: bool,
+    /// Don't use the fonts bundled with the Typst compiler, like Linux Libertine and New
+    /// Computer Modern, which `typst compile` uses even if they are not installed.
+    #[clap(long)]
+    ignore_embedded_fonts: bool,
+}
+
+impl FontSelection {
+    /// Split comma-separated font paths and expand `~` and environment variables in them.
+    fn expand_font_paths(&mut self) -> Result<()> {
+        self.font_paths = paths::expand_list(&self.font_paths)?;
+        Ok(())
+    }
+
+    /// Where fonts are loaded from besides `--font-path`.
+    fn sources(&self) -> FontSources {
+        FontSources {
+            system: !self.ignore_system_fonts,
+            embedded: !self.ignore_embedded_fonts,
+        }
+    }
+
+    /// Whether a font is selected by `--only`, `--include` and `--exclude`.
+    ///
+    /// The regular expressions are matched against the family, the PostScript name and the full
+    /// name of the font.
+    fn font_filter(&self) -> Result<impl Fn(&FontInfo, &FontFile) -> bool + Sync + '_> {
+        let regex = |pattern: &str| {
+            let pattern = if self.full_match {
+                format!("^(?:{pattern})$")
+            } else {
+                pattern.to_owned()
+            };
+            RegexBuilder::new(&pattern)
+                .case_insensitive(self.ignore_case)
+                .build()
+        };
+        let include_regex = self
+            .include
+            .as_deref()
+            .or(self.include_glob.as_ref().map(Glob::regex))
+            .map(regex)
+            .transpose()
+            .wrap_err("failed to compile include regex")?;
+        let exclude_regex = self
+            .exclude
+            .as_deref()
+            .or(self.exclude_glob.as_ref().map(Glob::regex))
+            .map(regex)
+            .transpose()
+            .wrap_err("failed to compile exclude regex")?;
+        Ok(move |font: &FontInfo, file: &FontFile| {
+            (self.only.is_empty()
+                || self
+                    .only
+                    .iter()
+                    .any(|only| only.eq_ignore_ascii_case(&font.family)))
+                && include_regex.as_ref().map_or(true, |include_regex| {
+                    file.names(font).any(|name| include_regex.is_match(name))
+                })
+                && exclude_regex.as_ref().map_or(true, |exclude_regex| {
+                    !file.names(font).any(|name| exclude_regex.is_match(name))
+                })
+        })
+    }
+
+    /// The fonts matching the selection, sorted by family first and variant second.
+    ///
+    /// `file` returns the file of the font at an index of the book.
+    fn select<'a>(
+        &self,
+        book: &FontBook,
+        file: impl Fn(usize) -> &'a FontFile,
+    ) -> Result<Vec<FontInfo>> {
+        let is_selected = self.font_filter()?;
+        warn_ambiguous(book, &file, &is_selected);
+        let mut fonts: Vec<FontInfo> = book
+            .families()
+            .flat_map(|(_, fonts)| {
+                // Filter out excluded fonts and variants.
+                let mut fonts = fonts.filter(|font| {
+                    let selected = book
+                        .select(&font.family.to_lowercase(), font.variant)
+                        .is_some_and(|index| is_selected(font, file(index)));
+                    let fitting_style = self
+                        .style
+                        .iter()
+                        .any(|&style| font.variant.style == style.into());
+                    let fitting_weight = self.weight.is_empty()
+                        || self.weight.contains(&font.variant.weight.to_number());
+                    let fitting_stretch = self.stretch.is_empty()
+                        || self
+                            .stretch
+                            .iter()
+                            .any(|&stretch| font.variant.stretch == stretch.into());
+                    selected && fitting_style && fitting_weight && fitting_stretch
+                });
+                // Only iterate over one font if `--variants` is not set.
+                fonts
+                    .next()
+                    .into_iter()
+                    .chain(fonts.take_while(|_| self.variants))
+            })
+            .cloned()
+            .collect();
+
+        if let Some(missing) = self.only.iter().find(|only| {
+            !fonts
+                .iter()
+                .any(|font| font.family.eq_ignore_ascii_case(only))
+        }) {
+            return Err(eyre!("font family {missing} was not found"));
+        }
+
+        fonts.sort_by(|a, b| a.family.cmp(&b.family).then(a.variant.cmp(&b.variant)));
+        Ok(fonts)
+    }
+}
+
+/// Warns about selected fonts that share their family and variant with other fonts.
+///
+/// Typst looks fonts up by family and variant, so only the first of them can be compared.
+/// Embedded fonts are left out, as installed copies of them are common and take precedence.
+fn warn_ambiguous<'a>(
+    book: &FontBook,
+    file: impl Fn(usize) -> &'a FontFile,
+    is_selected: impl Fn(&FontInfo, &FontFile) -> bool,
+) {
+    let mut groups: HashMap<(String, FontVariant), Vec<usize>> = HashMap::new();
+    for (index, info) in (0..)
+        .map_while(|index| Some((index, book.info(index)?)))
+        .filter(|&(index, _)| file(index).path.is_some())
+    {
+        groups
+            .entry((info.family.to_lowercase(), info.variant))
+            .or_default()
+            .push(index);
+    }
+    let mut groups: Vec<Vec<usize>> = groups
+        .into_values()
+        .filter(|indices| {
+            indices.len() > 1
+                && indices.iter().any(|&index| {
+                    book.info(index)
+                        .is_some_and(|info| is_selected(info, file(index)))
+                })
+        })
+        .collect();
+    groups.sort();
+    let location = |index: usize| match &file(index).path {
+        Some(path) => path.display().to_string(),
+        None => "the embedded fonts".to_owned(),
+    };
+    for indices in groups {
+        let Some((used, info)) = book
+            .info(indices[0])
+            .and_then(|info| book.select(&info.family.to_lowercase(), info.variant))
+            .and_then(|used| Some((used, book.info(used)?)))
+        else {
+            continue;
+        };
+        let ignored: Vec<String> = indices
+            .iter()
+            .filter(|&&index| index != used)
+            .map(|&index| location(index))
+            .collect();
+        Event::new(
+            Level::Warn,
+            "ambiguous-font",
+            format!(
+                "font {} {:?} is installed {} times; only the one in {} is compared, not those in {}",
+                info.family,
+                info.variant,
+                indices.len(),
+                location(used),
+                ignored.join(", "),
+            ),
+        )
+        .field("family", info.family.as_str())
+        .field("used", location(used))
+        .field("ignored", ignored)
+        .emit();
+    }
 }
 
 fn main() -> Result<()> {
     color_eyre::install()?;
-    let args = Args::parse();
-    let mut world = SystemWorld::new(&args)?;
-    let render = render_collection(&mut world, &args).wrap_err("while rendering collection")?;
-    let output = args.input.with_extension("variants.pdf");
-    std::fs::write(output, render)?;
+    let mut cli: Vec<OsString> = std::env::args_os().collect();
+    // Without a subcommand, the arguments are those of `compare`.
+    match cli.get(1).and_then(|arg| arg.to_str()) {
+        Some("compare") => {
+            cli.remove(1);
+            compare(Config::load(cli)?)
+        }
+        Some("list") => list::run(cli.drain(1..)),
+        Some("diff") => diff::run(cli.drain(1..)),
+        Some("test") => snapshot::run(cli.drain(1..)),
+        Some("serve") => {
+            cli.remove(1);
+            let Some(args) = prepare(Config::load(cli)?)? else {
+                return Ok(());
+            };
+            serve::run(args)
+        }
+        Some("specimen") => sheet::run(cli.drain(1..)),
+        Some("glyphs") => glyphs::run(cli.drain(1..)),
+        Some("cache") => cache::run(cli.drain(1..)),
+        _ => compare(Config::load(cli)?),
+    }
+}
+
+/// Compares the fonts and writes the output.
+fn compare(config: Config) -> Result<()> {
+    let start = Instant::now();
+    let Some(args) = prepare(config)? else {
+        return Ok(());
+    };
+    if args.trace.is_some() {
+        timings::start_trace();
+    }
+    let outputs = write_comparison(&args)?;
+    if !args.dry_run {
+        timings::summarize(start.elapsed());
+    }
+    if let Some(path) = &args.trace {
+        timings::write_trace(path)?;
+    }
+    if let Some(viewer) = &args.open {
+        for output in &outputs {
+            open(output, viewer.as_deref());
+        }
+    }
+    if interrupt::requested() {
+        // Signal the interruption to calling scripts despite the partial output.
+        std::process::exit(130);
+    }
+    Ok(())
+}
+
+/// Sets up logging and the interrupt handler and checks the arguments.
+///
+/// Returns `None` if the configuration or the template was printed instead.
+fn prepare(config: Config) -> Result<Option<Args>> {
+    if config.args.dump_template {
+        print!("{}", template::DEFAULT);
+        return Ok(None);
+    }
+    if config.args.print_config {
+        config.print();
+        return Ok(None);
+    }
+    let mut args = config.args;
+    let verbosity = if args.quiet { -1 } else { args.verbose as i8 };
+    logging::init(verbosity, args.log_format);
+    args.fonts.expand_font_paths()?;
+    if args.split_by_family && !args.format.contains(&Format::Pdf) {
+        return Err(eyre!(
+            "--split-by-family is only supported for the PDF format"
+        ));
+    }
+    if args.output.as_deref() == Some(Path::new("-")) {
+        if !matches!(args.format[..], [Format::Pdf | Format::Zip]) || args.split_by_family {
+            return Err(eyre!(
+                "only a single PDF or ZIP archive can be written to stdout, as the other outputs consist of several files"
+            ));
+        }
+        if args.open.is_some() {
+            return Err(eyre!("--open needs an output file instead of stdout"));
+        }
+    }
+    if args.html_theme.is_some() && !args.format.contains(&Format::Html) {
+        return Err(eyre!("--html-theme is only supported for the HTML format"));
+    }
+    if args.landscape && args.paper == Paper::Auto {
+        return Err(eyre!("--landscape needs a --paper size"));
+    }
+    if args.blink && !args.format.contains(&Format::Pdf) {
+        return Err(eyre!("--blink is only supported for the PDF format"));
+    }
+    if let Some(path) = &args.template {
+        // Fail before rendering rather than when compiling the collection.
+        template::load(Some(path))?;
+    }
+    interrupt::install();
+    Ok(Some(args))
+}
+
+/// Renders the variants and writes them in the output formats, returning the outputs' paths.
+fn write_comparison(args: &Args) -> Result<Vec<PathBuf>> {
+    let mut world = timings::time(Phase::FontScan, || SystemWorld::new(args))?;
+    let fonts = selected_fonts(&world, args)?;
+    if args.dry_run {
+        plan::print(args, &fonts);
+        return Ok(Vec::new());
+    }
+    plan::check_size(&world, args, &fonts)?;
+    // Restored before rendering, so that renders made with other settings are refused early.
+    let restored = match (&args.diagnostics, args.retry_failed) {
+        (Some(path), true) => retry::restore(path, args, &world.book)?,
+        _ => Vec::new(),
+    };
+    // Kept alive until the output is written, as it owns the renders moved to disk.
+    let memory = args.max_memory.map(MemoryLimit::new);
+    let (mut variants, skipped) = render_variants(world.clone(), args, fonts, memory.as_ref())
+        .wrap_err("while rendering variants")?;
+    if !restored.is_empty() {
+        variants.extend(restored);
+        variants.sort_by(|a, b| {
+            (a.font.family.cmp(&b.font.family)).then(a.font.variant.cmp(&b.font.variant))
+        });
+    }
+    let partial = interrupt::requested();
+    if partial {
+        Event::new(
+            Level::Warn,
+            "interrupted",
+            format!(
+                "interrupted, assembling a partial collection of {} variants",
+                variants.len()
+            ),
+        )
+        .emit();
+    }
+
+    if let Some(baseline) = &args.baseline {
+        let page_diffs = args.page_diffs.as_deref().map(|dir| (dir, args.palette));
+        metrics::compare_to_baseline(&mut variants, baseline, page_diffs)
+            .wrap_err("while comparing to baseline")?;
+    }
+    if let Some(path) = &args.metrics_csv {
+        metrics::write_csv(&variants, path)?;
+    }
+    if let Some(path) = &args.junit {
+        junit::write_junit(args, &variants, &skipped, path)?;
+    }
+    if let Some(path) = &args.diagnostics {
+        diagnostics::write_diagnostics(args, &variants, &skipped, world.sandbox(), path)?;
+        // The renders are only needed to add the fonts that failed to them with a retry.
+        if skipped.is_empty() {
+            retry::discard(path)
+        } else {
+            retry::keep(path, args, &variants)
+        }
+        .wrap_err("while keeping the renders for --retry-failed")?;
+    }
+    // Trimmed only now, so that the scores above and the kept renders don't depend on it.
+    if args.autocrop {
+        let inset = args.zoom.is_some() || args.zoom_label.is_some();
+        autocrop::apply(&mut variants, args.ppi, inset, memory.as_ref())
+            .wrap_err("while trimming the renders")?;
+    }
+    let mut formats: Vec<Format> = Vec::new();
+    for &format in &args.format {
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+    let mut outputs = Vec::new();
+    if !args.fonts.only.is_empty() && formats.contains(&Format::Pdf) && !args.blink {
+        formats.retain(|&format| format != Format::Pdf);
+        // The other formats are written from the same renders.
+        let pair = find_families(variants.clone(), &skipped, &args.fonts.only)?;
+        let output = output_path(args, Format::Pdf);
+        let render = render_pair(&mut world, args, pair).wrap_err("while rendering collection")?;
+        write_output(&output, &render)?;
+        outputs.push(output);
+        if formats.is_empty() {
+            return Ok(outputs);
+        }
+    }
+    // The reports above still list every font.
+    let mut variants = dedup::deduplicate(variants).wrap_err("while merging identical renders")?;
+    if let (Some(threshold), true) = (args.merge_similar, args.fonts.variants) {
+        variants =
+            dedup::merge_similar(variants, threshold).wrap_err("while merging similar variants")?;
+    }
+
+    // Each format but the last takes a copy of the renders, which shares their image data.
+    let last = formats.len() - 1;
+    for (f, format) in formats.into_iter().enumerate() {
+        let variants = if f == last {
+            std::mem::take(&mut variants)
+        } else {
+            variants.clone()
+        };
+        let output = output_path(args, format);
+        write_format(
+            &mut world, args, format, variants, &skipped, partial, &output,
+        )?;
+        outputs.push(output);
+    }
+    Ok(outputs)
+}
+
+/// The path of the output in a format, from `--output` or next to the input.
+fn output_path(args: &Args, format: Format) -> PathBuf {
+    match &args.output {
+        Some(output) if args.format.len() > 1 => output.with_extension(format.file_extension()),
+        Some(output) => output.clone(),
+        None => args.input().with_extension(format.extension()),
+    }
+}
+
+/// Writes the variants in one of the output formats.
+fn write_format(
+    world: &mut SystemWorld,
+    args: &Args,
+    format: Format,
+    variants: Vec<Render>,
+    skipped: &[Skipped],
+    partial: bool,
+    output: &Path,
+) -> Result<()> {
+    match format {
+        Format::Pdf if args.blink => {
+            let render = blink::render_blink(world, args, variants)
+                .wrap_err("while rendering blink comparator")?;
+            write_output(output, &render)?;
+        }
+        Format::Pdf if args.split_by_family => {
+            write_families(world, args, variants, skipped, partial, output)?
+        }
+        Format::Pdf => {
+            let render = render_collection(world, args, variants, skipped, partial)
+                .wrap_err("while rendering collection")?;
+            write_output(output, &render)?;
+        }
+        Format::Zip => bundle::write_zip(world, args, variants, skipped, partial, output)
+            .wrap_err("while writing archive")?,
+        Format::Html => {
+            html::write_html(args, variants, partial, output).wrap_err("while writing gallery")?
+        }
+        Format::Markdown => markdown::write_markdown(world, args, variants, partial, output)
+            .wrap_err("while writing report")?,
+    }
+    Ok(())
+}
+
+/// The render of each of the families, in the given order.
+///
+/// Fails if a family wasn't found or couldn't be rendered.
+fn find_families(
+    mut variants: Vec<Render>,
+    skipped: &[Skipped],
+    families: &[String],
+) -> Result<Vec<Render>> {
+    families
+        .iter()
+        .map(|family| {
+            let is_family = |font: &FontInfo| font.family.eq_ignore_ascii_case(family);
+            if let Some(skipped) = skipped.iter().find(|skipped| is_family(&skipped.font)) {
+                return Err(eyre!("failed to render font {family}: {}", skipped.reason));
+            }
+            let index = variants
+                .iter()
+                .position(|render| is_family(&render.font))
+                .ok_or_else(|| eyre!("font family {family} was not found"))?;
+            Ok(variants.swap_remove(index))
+        })
+        .collect()
+}
+
+/// Open a file or URL with the given viewer or the system's default application.
+///
+/// Failing to open it is only a warning, as the output was written successfully.
+fn open(target: impl AsRef<OsStr>, viewer: Option<&str>) {
+    let target = target.as_ref();
+    let mut command = match viewer {
+        Some(viewer) => Command::new(viewer),
+        None if cfg!(target_os = "macos") => Command::new("open"),
+        None if cfg!(windows) => {
+            let mut command = Command::new("cmd");
+            command.args(["/C", "start", ""]);
+            command
+        }
+        None => Command::new("xdg-open"),
+    };
+    if let Err(err) = command.arg(target).spawn() {
+        Event::new(
+            Level::Warn,
+            "open-failed",
+            format!(
+                "failed to open {} with {}: {err}",
+                target.to_string_lossy(),
+                command.get_program().to_string_lossy(),
+            ),
+        )
+        .emit();
+    }
+}
+
+/// Write a collection for each family into the `variants` directory next to the output,
+/// and an index of the families to the output itself.
+fn write_families(
+    world: &mut SystemWorld,
+    args: &Args,
+    variants: Vec<Render>,
+    skipped: &[Skipped],
+    partial: bool,
+    output: &Path,
+) -> Result<()> {
+    let dir = output.with_file_name("variants");
+    std::fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("failed to create directory {}", dir.display()))?;
+
+    // The variants are sorted by family, so each family is a consecutive run.
+    let mut families: Vec<Vec<Render>> = Vec::new();
+    for render in variants {
+        match families.last_mut() {
+            Some(family) if family[0].font.family == render.font.family => family.push(render),
+            _ => families.push(vec![render]),
+        }
+    }
+
+    let strings = args.locale.strings();
+    let mut index = String::new();
+    write!(
+        index,
+        r#"
+        #set document(author: "{pkg_name}")
+        #set page(height: auto, margin: 1cm)
+        #set text(lang: "{lang}")
+
+        = {fonts}
+        {created_per_family} #link("{pkg_homepage}")[`{pkg_name} v{pkg_version}`].
+        "#,
+        pkg_name = env!("CARGO_PKG_NAME"),
+        pkg_version = env!("CARGO_PKG_VERSION"),
+        pkg_homepage = env!("CARGO_PKG_HOMEPAGE"),
+        lang = strings.lang,
+        fonts = strings.fonts,
+        created_per_family = strings.created_per_family,
+    )?;
+    if partial {
+        writeln!(
+            index,
+            "*{}* {}\n",
+            strings.partial, strings.partial_families
+        )?;
+    }
+    writeln!(
+        index,
+        "#table(columns: 3, stroke: none, [*{}*], [*{}*], [*{}*],",
+        strings.family, strings.variants, strings.file
+    )?;
+    // Families that only differ in the characters replaced for the file name, or in case on
+    // case-insensitive file systems, are told apart by a counter.
+    let mut files = HashSet::new();
+    for family in families {
+        let name = family[0].font.family.clone();
+        let stem = file_name(&name);
+        let file = (1..)
+            .map(|n| match n {
+                1 => format!("{stem}.pdf"),
+                n => format!("{stem}-{n}.pdf"),
+            })
+            .find(|file| files.insert(file.to_lowercase()))
+            .expect("a counter is unused");
+        writeln!(
+            index,
+            "{family}, [{count}], link({link}, raw({file})),",
+            family = specimen::string(&name),
+            count = family.len(),
+            link = specimen::string(&format!("variants/{file}")),
+            file = specimen::string(&file),
+        )?;
+        let family_skipped: Vec<Skipped> = skipped
+            .iter()
+            .filter(|skipped| skipped.font.family == name)
+            .cloned()
+            .collect();
+        let render = render_collection(world, args, family, &family_skipped, partial)
+            .wrap_err_with(|| format!("while rendering collection of family {name}"))?;
+        write_output(&dir.join(&file), &render)?;
+    }
+    writeln!(index, ")")?;
+    write_skipped(&mut index, skipped, args.locale)?;
+
+    world.replace_files(index, Vec::new());
+    let mut tracer = Tracer::new();
+    let document = timings::time(Phase::Collection, || typst::compile(world, &mut tracer))
+        .map_err(|diag| eyre!("failed to compile index: {diag:?}"))?;
+    let pdf = timings::time(Phase::Write, || {
+        typst_pdf::pdf(&document, Smart::Auto, source_date::pdf_timestamp())
+    });
+    write_output(output, &pdf)?;
     Ok(())
 }
 
-/// Render all the variants and return PDF.
-fn render_collection(world: &mut SystemWorld, args: &Args) -> Result<Vec<u8>> {
-    let variants = render_variants(world.clone(), args).wrap_err("while rendering variants")?;
+/// Replace the characters of a family name that are unsuitable for file names.
+fn file_name(family: &str) -> String {
+    family
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || " -_".contains(c) => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// A name for the files of a font variant that doesn't depend on which other fonts are
+/// rendered, like `EB-Garamond-italic-400-1000`.
+fn variant_file_name(font: &FontInfo) -> String {
+    let variant = font.variant;
+    format!(
+        "{}-{}-{}-{}",
+        file_name(&font.family).replace(' ', "-"),
+        style_name(variant.style),
+        variant.weight.to_number(),
+        (variant.stretch.to_ratio().get() * 1000.0).round(),
+    )
+}
+
+/// Write files next to the output, creating the directories in their relative paths.
+fn write_files(output: &Path, files: Vec<(String, &FileData)>) -> Result<()> {
+    timings::time(Phase::Write, || {
+        for (name, data) in files {
+            let path = output.with_file_name(&name);
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)
+                    .wrap_err_with(|| format!("failed to create directory {}", dir.display()))?;
+            }
+            let bytes = data
+                .bytes()
+                .map_err(|err| eyre!("failed to read render {name}: {err}"))?;
+            std::fs::write(&path, bytes)
+                .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+        }
+        Ok(())
+    })
+}
+
+/// Writes the output file, or streams it to stdout if its path is `-`.
+fn write_output(output: &Path, bytes: &[u8]) -> Result<()> {
+    timings::time(Phase::Write, || {
+        if output == Path::new("-") {
+            use std::io::Write as _;
+            let mut stdout = std::io::stdout().lock();
+            stdout
+                .write_all(bytes)
+                .wrap_err("failed to write to stdout")?;
+            return stdout.flush().wrap_err("failed to write to stdout");
+        }
+        std::fs::write(output, bytes)
+            .wrap_err_with(|| format!("failed to write {}", output.display()))
+    })
+}
+
+/// Assemble the rendered variants into a collection and return it as PDF.
+fn render_collection(
+    world: &mut SystemWorld,
+    args: &Args,
+    variants: Vec<Render>,
+    skipped: &[Skipped],
+    partial: bool,
+) -> Result<Vec<u8>> {
+    Event::new(Level::Info, "collection-start", "Compiling collection...")
+        .field("variants", variants.len())
+        .emit();
+    let start = Instant::now();
+
+    let map_pixels = |x| (x as f32) / args.ppi * 72.0;
+    let layout = PageLayout::new(args);
+    let strings = args.locale.strings();
+    let [paper, ink, accent] = args.theme.colors();
+    let page_width = variants
+        .iter()
+        .flat_map(|render| {
+            let output = match &render.output {
+                Output::Image(image) => vec![map_pixels(image.width)],
+                Output::Pages(pages) => pages
+                    .iter()
+                    .map(|page| page.frame.width().to_pt() as f32)
+                    .collect(),
+            };
+            let specimens = render.specimens.iter();
+            output
+                .into_iter()
+                .chain(specimens.map(|(_, image)| map_pixels(image.width)))
+        })
+        .max_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap_or(0.0);
+
+    // Pages as wide as the renders, or of a paper size that the renders are scaled down to fit,
+    // leaving room for the labels above them.
+    let (page_size, render) = match args.paper.name() {
+        None => (
+            format!(
+                "width: calc.max({page_width}pt * {columns} + margin * {gutters}, 20cm) + 2 * margin, height: auto",
+                columns = args.columns,
+                gutters = args.columns - 1,
+            ),
+            "#let render(path, width, height) = image(path, width: width, height: height)",
+        ),
+        Some(paper) => (
+            format!("paper: \"{paper}\", flipped: {}", args.landscape),
+            "#let render(path, width, height) = layout(size => {
+                let scale = calc.min(1.0, size.width / width, (size.height - 8 * text-size) / height)
+                image(path, width: width * scale, height: height * scale)
+            })",
+        ),
+    };
+    let notice = if partial {
+        format!(
+            "*{partial}*\n{interrupted}",
+            partial = strings.partial,
+            interrupted = strings
+                .partial_variants
+                .replace("{count}", &variants.len().to_string()),
+        )
+    } else {
+        String::new()
+    };
+
+    // A contact sheet of thumbnails after the outline, each linking to its variant's page.
+    let thumbnails: Vec<Option<Vec<u8>>> = variants
+        .par_iter()
+        .map(|render| {
+            let Output::Image(image) = &render.output else {
+                return Ok(None);
+            };
+            if !args.overview {
+                return Ok(None);
+            }
+            let bytes = image
+                .data
+                .bytes()
+                .map_err(|err| eyre!("failed to read render: {err}"))?;
+            Ok(Some(raster::thumbnail(&bytes, raster::THUMBNAIL_WIDTH)?.0))
+        })
+        .collect::<Result<_>>()?;
+    let mut overview = String::new();
+    if thumbnails.iter().any(Option::is_some) {
+        writeln!(
+            overview,
+            "#page[\n*{}*\n#grid(columns: (1fr,) * {}, gutter: spacing,",
+            strings.overview, layout.thumbnails
+        )?;
+        for (n, render) in variants.iter().enumerate() {
+            if thumbnails[n].is_some() {
+                writeln!(
+                    overview,
+                    r#"link(<variant-{n}>, stack(spacing: 0.5em, image("thumbnail-{n}.png", width: 100%), text(size: 0.75em, {name}))),"#,
+                    name = specimen::string(&format!(
+                        "{} {:?}",
+                        render.font.family, render.font.variant
+                    )),
+                )?;
+            }
+        }
+        writeln!(overview, ")\n]")?;
+    }
+
+    // With a baseline, rank the fonts by their similarity to it, so that the closest come first.
+    let mut ranked: Vec<(usize, f64)> = variants
+        .iter()
+        .enumerate()
+        .filter_map(|(n, render)| Some((n, render.metrics.similarity?)))
+        .collect();
+    let mut ranking = String::new();
+    if let (Some(baseline), false) = (&args.baseline, ranked.is_empty()) {
+        ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        writeln!(
+            ranking,
+            "#page[\n*{similarity_to} #{baseline}*\n\
+             #table(columns: 3, stroke: none, [*{rank}*], [*{font}*], [*{similarity}*],",
+            similarity_to = strings.similarity_to,
+            baseline = specimen::string(baseline),
+            rank = strings.rank,
+            font = strings.font,
+            similarity = strings.similarity,
+        )?;
+        for (rank, (n, similarity)) in ranked.into_iter().enumerate() {
+            let render = &variants[n];
+            writeln!(
+                ranking,
+                "[{rank}], link(<variant-{n}>, {name}), [{similarity:.1} %],",
+                rank = rank + 1,
+                name =
+                    specimen::string(&format!("{} {:?}", render.font.family, render.font.variant)),
+                similarity = similarity * 100.0,
+            )?;
+        }
+        writeln!(ranking, ")\n]")?;
+    }
+
+    // The variants on each page, side by side with `--columns`.
+    let columns = args.columns as usize;
+    let chunks: Vec<Range<usize>> = (0..variants.len())
+        .step_by(columns)
+        .map(|first| first..(first + columns).min(variants.len()))
+        .collect();
+    let mut variant_pages = String::new();
+    let mut last_family = None;
+    for (c, chunk) in chunks.iter().enumerate() {
+        // Links to the neighbouring pages and the outline, to page through without it.
+        let mut links = Vec::new();
+        if c > 0 {
+            links.push(format!(
+                "link(<variant-{}>)[{}]",
+                chunks[c - 1].start,
+                strings.previous
+            ));
+        }
+        links.push(format!("link(<overview>)[{}]", strings.overview));
+        if let Some(next) = chunks.get(c + 1) {
+            links.push(format!("link(<variant-{}>)[{}]", next.start, strings.next));
+        }
+        let navigation = format!(
+            "[#text(size: 0.75em, ({},).join(h(1em))) #h(1em) #counter(page).display()]",
+            links.join(", ")
+        );
+
+        let mut cells = Vec::new();
+        let mut placeholders = String::new();
+        let mut new_family = false;
+        for n in chunk.clone() {
+            let render = &variants[n];
+            let first_of_family = last_family != Some(&render.font.family);
+            new_family |= first_of_family;
+            let merged = render.note(args.locale).map_or(String::new(), |note| {
+                format!("#{}", specimen::string(&note))
+            });
+            let mut specimens = String::new();
+            for (kind, image) in &render.specimens {
+                write!(
+                    specimens,
+                    r#"
+                    #v(spacing)
+                    *{title}*
+                    #render("render-{n}-{kind:?}.png", {width}pt, {height}pt)
+                    "#,
+                    title = args.locale.specimen_title(*kind),
+                    width = map_pixels(image.width),
+                    height = map_pixels(image.height),
+                )?;
+            }
+            let body = match &render.output {
+                Output::Image(image) => format!(
+                    r#"#render("render-{n}.png", {width}pt, {height}pt)"#,
+                    width = map_pixels(image.width),
+                    height = map_pixels(image.height),
+                ),
+                // The pages are replaced with the variant's pages after compiling the collection.
+                Output::Pages(pages) => {
+                    for (p, page) in pages.iter().enumerate() {
+                        writeln!(
+                            placeholders,
+                            "#page(width: {}pt, height: {}pt, margin: 0pt)[#metadata(none) <variant-{n}-{p}>]",
+                            page.frame.width().to_pt(),
+                            page.frame.height().to_pt(),
+                        )?;
+                    }
+                    String::new()
+                }
+            };
+            let title = format!(
+                "text(size: 1.2em, [*#counter(heading).display((n, ..) => n) {family}*])",
+                family = render.font.family
+            );
+            // A single variant shares its title row with the navigation, unless it has a header.
+            let (title, header) = if columns == 1 && args.header.is_none() {
+                (
+                    String::new(),
+                    format!("#grid(columns: 2, column-gutter: 1fr, {title}, {navigation})"),
+                )
+            } else {
+                (format!("#{title}"), String::new())
+            };
+            cells.push(format!(
+                r#"
+                #metadata(none) <variant-{n}>
+                #if {first_of_family} [
+                    // Necessary for the outline.
+                    #place(hide[= {family}])
+                ]
+                {header}{title}
+                == {variant:?}
+                {merged}
+                {body}
+                {specimens}
+                "#,
+                family = render.font.family,
+                variant = render.font.variant,
+            ));
+            last_family = Some(&render.font.family);
+        }
+
+        let families: Vec<&str> = chunk
+            .clone()
+            .map(|n| variants[n].font.family.as_str())
+            .collect();
+        let variant_names: Vec<String> = chunk
+            .clone()
+            .map(|n| format!("{:?}", variants[n].font.variant))
+            .collect();
+        let mut header = match &args.header {
+            Some(template) => format!("#{}\n", template.typst(&families, &variant_names)),
+            None if columns == 1 => String::new(),
+            None => format!("#align(right, {navigation})\n"),
+        };
+        // Before anything on the page displays the page number.
+        if args.restart_page_numbers && new_family {
+            header.insert_str(
+                0,
+                &format!("#counter(page).update({})\n", args.first_page_number),
+            );
+        }
+        let footer = match &args.footer {
+            Some(template) => format!("footer: {}", template.typst(&families, &variant_names)),
+            None => String::new(),
+        };
+        if columns == 1 {
+            writeln!(
+                variant_pages,
+                "#page({footer})[{header}{}]
+{placeholders}",
+                cells[0]
+            )?;
+        } else {
+            writeln!(
+                variant_pages,
+                "#page({footer})[\n{header}#grid(columns: (1fr,) * {columns}, column-gutter: margin, {})\n]\n{placeholders}",
+                cells
+                    .iter()
+                    .map(|cell| format!("[{cell}]"))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )?;
+        }
+    }
+
+    let mut appendices = String::new();
+    if args.font_info {
+        write_font_info(&mut appendices, world, &variants, args.locale)?;
+    }
+    write_skipped(&mut appendices, skipped, args.locale)?;
+    write_coverage(&mut appendices, &variants, args.locale)?;
+
+    let main = template::fill(
+        &template::load(args.template.as_deref())?,
+        &[
+            ("margin", format!("{}pt", layout.margin.to_pt())),
+            ("text-size", format!("{}pt", layout.label_size.to_pt())),
+            ("spacing", format!("{}em", layout.spacing)),
+            ("page-size", page_size),
+            ("numbering", specimen::string(&args.page_numbering)),
+            ("first-page", args.first_page_number.to_string()),
+            ("lang", strings.lang.to_owned()),
+            ("background", typst_color(paper)),
+            ("foreground", typst_color(ink)),
+            ("accent", typst_color(accent)),
+            ("render", render.to_owned()),
+            ("fonts", strings.fonts.to_owned()),
+            ("created-using", strings.created_using.to_owned()),
+            ("tool", env!("CARGO_PKG_NAME").to_owned()),
+            ("version", env!("CARGO_PKG_VERSION").to_owned()),
+            ("homepage", env!("CARGO_PKG_HOMEPAGE").to_owned()),
+            ("partial", notice),
+            ("overview", overview),
+            ("ranking", ranking),
+            ("variants", variant_pages),
+            ("appendices", appendices),
+        ],
+    );
+
+    let mut files: Vec<(PathBuf, FileData)> = Vec::new();
+    let mut merged = Vec::new();
+    for (n, thumbnail) in thumbnails.into_iter().enumerate() {
+        if let Some(thumbnail) = thumbnail {
+            files.push((
+                format!("thumbnail-{n}.png").into(),
+                FileData::Bytes(Bytes::from(thumbnail)),
+            ));
+        }
+    }
+    for (n, render) in variants.into_iter().enumerate() {
+        match render.output {
+            Output::Image(image) => files.push((format!("render-{n}.png").into(), image.data)),
+            Output::Pages(pages) => merged.extend(
+                pages
+                    .into_iter()
+                    .enumerate()
+                    .map(|(p, page)| (format!("variant-{n}-{p}"), page)),
+            ),
+        }
+        for (kind, image) in render.specimens {
+            files.push((format!("render-{n}-{kind:?}.png").into(), image.data));
+        }
+    }
+    // Recolor the images for the theme. This brings the images that were moved to disk
+    // back into memory.
+    if args.theme != Theme::Light {
+        files.par_iter_mut().try_for_each(|(_, data)| {
+            let bytes = data
+                .bytes()
+                .map_err(|err| eyre!("failed to read render: {err}"))?;
+            *data = FileData::Bytes(Bytes::from(raster::tint(&bytes, paper, ink)?));
+            Ok::<_, color_eyre::Report>(())
+        })?;
+    }
+    let images: Vec<FileData> = files.iter().map(|(_, data)| data.clone()).collect();
+    world.replace_files(main, files);
+
+    // The single-threaded compile runs alongside the encoding of the images on the other
+    // threads, which it finds in the cache once it gets to them.
+    let mut tracer = Tracer::new();
+    let (compiled, ()) = rayon::join(
+        || timings::time(Phase::Collection, || typst::compile(world, &mut tracer)),
+        || timings::time(Phase::Encode, || encode_images(&images)),
+    );
+    let mut document = compiled.map_err(|diag| eyre!("failed to compile collection: {diag:?}"))?;
+
+    // Swap the placeholders for the vector pages. The introspector still describes the
+    // placeholders, so that the outline and bookmarks refer to the collection.
+    // Links within the variants would lead to the wrong pages, so they are removed.
+    for (label, page) in merged {
+        let location = document
+            .introspector
+            .query_label(Label::new(label.as_str()))
+            .ok()
+            .and_then(|placeholder| placeholder.location())
+            .ok_or_else(|| eyre!("missing placeholder page {label}"))?;
+        let index = document.introspector.page(location).get() - 1;
+        document.pages[index].frame = without_internal_links(page.frame);
+    }
+    let pdf = timings::time(Phase::Write, || {
+        typst_pdf::pdf(&document, Smart::Auto, source_date::pdf_timestamp())
+    });
+    Event::new(
+        Level::Debug,
+        "collection-finish",
+        format!("Compiled collection in {:.2?}", start.elapsed()),
+    )
+    .field("pages", document.pages.len())
+    .duration("duration", start.elapsed())
+    .emit();
+    Ok(pdf)
+}
+
+/// Decodes the images of the collection in parallel and encodes them for the PDF, both of
+/// which Typst caches, so that compiling and writing the collection doesn't wait for them.
+///
+/// The encoding is cached for the image, so it is started by writing a throwaway PDF that
+/// contains all of them. Images that were moved to disk stay there to keep the memory usage
+/// low.
+fn encode_images(files: &[FileData]) {
+    let images: Vec<visualize::Image> = files
+        .par_iter()
+        .filter(|data| !matches!(data, FileData::Path(_)))
+        .filter_map(|data| {
+            let format = ImageFormat::Raster(RasterFormat::Png);
+            visualize::Image::new(data.bytes().ok()?, format, None).ok()
+        })
+        .collect();
+    let mut frame = Frame::soft(Size::zero());
+    for image in images {
+        let size = Size::new(Abs::pt(1.0), Abs::pt(1.0));
+        frame.push(
+            Point::zero(),
+            FrameItem::Image(image, size, Span::detached()),
+        );
+    }
+    let document = Document {
+        pages: vec![Page {
+            frame,
+            numbering: None,
+            number: 1,
+        }],
+        ..Document::default()
+    };
+    typst_pdf::pdf(&document, Smart::Auto, None);
+}
+
+/// Removes the links to locations within the document from a frame, keeping links to URLs.
+fn without_internal_links(frame: Frame) -> Frame {
+    let mut stripped = Frame::new(frame.size(), frame.kind());
+    if frame.has_baseline() {
+        stripped.set_baseline(frame.baseline());
+    }
+    for (pos, item) in frame.items() {
+        let item = match item {
+            FrameItem::Meta(Meta::Link(Destination::Position(_) | Destination::Location(_)), _) => {
+                continue
+            }
+            FrameItem::Group(group) => FrameItem::Group(GroupItem {
+                frame: without_internal_links(group.frame.clone()),
+                ..group.clone()
+            }),
+            item => item.clone(),
+        };
+        stripped.push(*pos, item);
+    }
+    stripped
+}
+
+/// Compile the renders of two fonts side by side on a single page, without the outline,
+/// overview and appendices of the collection.
+fn render_pair(world: &mut SystemWorld, args: &Args, pair: Vec<Render>) -> Result<Vec<u8>> {
+    let start = Instant::now();
+    let layout = PageLayout::new(args);
+    let map_pixels = |x| (x as f32) / args.ppi * 72.0;
+    let mut columns = Vec::new();
+    let mut files: Vec<(PathBuf, FileData)> = Vec::new();
+    let mut width = 0.0;
+    for (n, render) in pair.into_iter().enumerate() {
+        let merged = render.note(args.locale).map_or(String::new(), |note| {
+            format!(r"\ #text(size: 0.75em, {})", specimen::string(&note))
+        });
+        let Output::Image(image) = render.output else {
+            return Err(eyre!("--only needs rasterized renders"));
+        };
+        let mut column = format!(
+            r#"[*{family}* {variant:?} {merged}
+            #image(width: {width}pt, height: {height}pt, "render-{n}.png")"#,
+            family = render.font.family,
+            variant = render.font.variant,
+            width = map_pixels(image.width),
+            height = map_pixels(image.height),
+        );
+        let mut column_width = map_pixels(image.width);
+        files.push((format!("render-{n}.png").into(), image.data));
+        for (kind, image) in render.specimens {
+            write!(
+                column,
+                r#"
+                #v(spacing)
+                *{title}*
+                #image(width: {width}pt, height: {height}pt, "render-{n}-{kind:?}.png")"#,
+                title = args.locale.specimen_title(kind),
+                width = map_pixels(image.width),
+                height = map_pixels(image.height),
+            )?;
+            column_width = column_width.max(map_pixels(image.width));
+            files.push((format!("render-{n}-{kind:?}.png").into(), image.data));
+        }
+        column.push(']');
+        columns.push(column);
+        width += column_width;
+    }
+
+    let main = format!(
+        r#"
+        #let margin = {margin}pt
+        #let spacing = {spacing}em
+        #set document(author: "{pkg_name}")
+        #set page(width: {width}pt + 3 * margin, height: auto, margin: margin)
+        #set text(size: {label_size}pt, lang: "{lang}")
+        #grid(columns: 2, column-gutter: margin, {columns})
+        "#,
+        pkg_name = env!("CARGO_PKG_NAME"),
+        lang = args.locale.strings().lang,
+        columns = columns.join(", "),
+        margin = layout.margin.to_pt(),
+        label_size = layout.label_size.to_pt(),
+        spacing = layout.spacing,
+    );
+    world.replace_files(main, files);
+    let mut tracer = Tracer::new();
+    let document = timings::time(Phase::Collection, || typst::compile(world, &mut tracer))
+        .map_err(|diag| eyre!("failed to compile collection: {diag:?}"))?;
+    let pdf = timings::time(Phase::Write, || {
+        typst_pdf::pdf(&document, Smart::Auto, source_date::pdf_timestamp())
+    });
+    Event::new(
+        Level::Debug,
+        "collection-finish",
+        format!("Compiled comparison in {:.2?}", start.elapsed()),
+    )
+    .field("pages", document.pages.len())
+    .duration("duration", start.elapsed())
+    .emit();
+    Ok(pdf)
+}
+
+/// Append a page listing technical information about the file of each rendered font.
+fn write_font_info(
+    main: &mut String,
+    world: &SystemWorld,
+    variants: &[Render],
+    locale: Locale,
+) -> Result<()> {
+    let strings = locale.strings();
+    write!(
+        main,
+        r#"
+        #page[
+            = {font_files}
+            #set text(size: 0.75em)
+            #table(
+                columns: 5,
+                stroke: none,
+                [*{font}*], [*{format}*], [*{glyphs}*], [*{tables}*], [*{size}*],
+        "#,
+        font_files = strings.font_files,
+        font = strings.font,
+        format = strings.format,
+        glyphs = strings.glyphs,
+        tables = strings.tables,
+        size = strings.size,
+    )?;
+    for render in variants {
+        let Some(file) = world
+            .font_index(&render.font)
+            .map(|index| world.font_file(index))
+        else {
+            continue;
+        };
+        let path = match &file.path {
+            Some(path) => path.display().to_string(),
+            None => strings.embedded.to_owned(),
+        };
+        writeln!(
+            main,
+            r#"[*{family}* {variant:?} \ #text(size: 0.7em, raw({path}))], [{file}], [{glyphs}], [{tables}], [{size}],"#,
+            family = render.font.family,
+            variant = render.font.variant,
+            path = specimen::string(&path),
+            glyphs = file.glyphs,
+            tables = file.tables.join(", "),
+            size = format_size(file.size),
+        )?;
+    }
+    writeln!(main, ")]")?;
+    Ok(())
+}
+
+/// Append a page listing the fonts that matched the filters but were skipped, and why.
+fn write_skipped(main: &mut String, skipped: &[Skipped], locale: Locale) -> Result<()> {
+    if skipped.is_empty() {
+        return Ok(());
+    }
+    let strings = locale.strings();
+    write!(
+        main,
+        r#"
+        #page[
+            = {excluded}
+            {description}
+            #set text(size: 0.75em)
+            #table(
+                columns: 2,
+                stroke: none,
+                [*{font}*], [*{reason}*],
+        "#,
+        excluded = strings.excluded,
+        description = strings.excluded_description,
+        font = strings.font,
+        reason = strings.reason,
+    )?;
+    for skipped in skipped {
+        writeln!(
+            main,
+            "[*#{family}* {variant:?}], {reason},",
+            family = specimen::string(&skipped.font.family),
+            variant = skipped.font.variant,
+            reason = specimen::string(&skipped.reason.to_string()),
+        )?;
+    }
+    writeln!(main, ")]")?;
+    Ok(())
+}
+
+/// Append a page listing the characters each font is missing, if any font misses some.
+fn write_coverage(main: &mut String, variants: &[Render], locale: Locale) -> Result<()> {
+    let incomplete: Vec<(usize, &Render)> = variants
+        .iter()
+        .enumerate()
+        .filter(|(_, render)| render.metrics.coverage.missing > 0)
+        .collect();
+    if incomplete.is_empty() {
+        return Ok(());
+    }
+    let strings = locale.strings();
+    write!(
+        main,
+        r#"
+        #page[
+            = {missing}
+            {description}
+            #set text(size: 0.75em)
+            #table(
+                columns: 3,
+                stroke: none,
+                [*{font}*], [*{coverage}*], [*{characters}*],
+        "#,
+        missing = strings.missing,
+        description = strings.missing_description,
+        font = strings.font,
+        coverage = strings.coverage,
+        characters = strings.characters,
+    )?;
+    for (n, render) in incomplete {
+        let coverage = &render.metrics.coverage;
+        writeln!(
+            main,
+            "link(<variant-{n}>)[*#{family}* {variant:?}], [{ratio:.1} %], {chars},",
+            family = specimen::string(&render.font.family),
+            variant = render.font.variant,
+            ratio = coverage.ratio() * 100.0,
+            chars = specimen::string(&coverage.missing_chars.iter().collect::<String>()),
+        )?;
+    }
+    writeln!(main, ")]")?;
+    Ok(())
+}
+
+/// Formats a color as a Typst `rgb` call.
+fn typst_color([r, g, b]: [u8; 3]) -> String {
+    format!("rgb({r}, {g}, {b})")
+}
+
+/// Format a number of bytes using binary units.
+fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
+
+/// The fonts to render in order: the selected ones, or the ones that failed in the previous run
+/// with `--retry-failed`, narrowed down with `--pick`.
+fn selected_fonts(world: &SystemWorld, args: &Args) -> Result<Vec<FontInfo>> {
+    let fonts = match (&args.diagnostics, args.retry_failed) {
+        (Some(path), true) => {
+            let fonts = diagnostics::failed_fonts(path, &world.book)?;
+            if fonts.is_empty() {
+                return Err(eyre!(
+                    "no fonts failed in the run that wrote {}",
+                    path.display()
+                ));
+            }
+            fonts
+        }
+        _ => args
+            .fonts
+            .select(&world.book, |index| world.font_file(index))?,
+    };
+    #[cfg(unix)]
+    let fonts = if args.pick {
+        picker::pick(fonts)?
+    } else {
+        fonts
+    };
+    #[cfg(not(unix))]
+    if args.pick {
+        return Err(eyre!("--pick is only supported on Unix"));
+    }
+    Ok(fonts)
+}
+
+/// Render a PNG image for each font (variant).
+fn render_variants(
+    world: SystemWorld,
+    args: &Args,
+    fonts: Vec<FontInfo>,
+    memory: Option<&MemoryLimit>,
+) -> Result<(Vec<Render>, Vec<Skipped>)> {
+    let main_path = world.main_path();
+    let options = RenderOptions::new(args);
+    for family in &args.fallback_list {
+        let family = family.trim();
+        if world
+            .book
+            .select_family(&family.to_lowercase())
+            .next()
+            .is_none()
+        {
+            Event::new(
+                Level::Warn,
+                "unknown-fallback",
+                format!("fallback family {family} was not found"),
+            )
+            .field("family", family)
+            .emit();
+        }
+    }
+    let (kinds, settings) = specimen_settings(args);
+    let preamble = match &args.preamble {
+        Some(path) => std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read preamble {}", path.display()))?,
+        None => String::new(),
+    };
+    let overrides = match &args.overrides {
+        Some(path) => Overrides::load(path)?,
+        None => Overrides::default(),
+    };
+    let post_render = args.post_render.clone().map(PostRender::new);
+    let scaled = size_scales(&world, args, &fonts);
+
+    let total = fonts.len();
+    let skipped = Mutex::new(Vec::new());
+    let images: Result<Vec<_>> = fonts
+        .par_iter()
+        .enumerate()
+        .map(|(index, font)| {
+            // Skip the remaining fonts once interrupted.
+            if interrupt::requested() {
+                return Ok(None);
+            }
+
+            let font_event = |level, name, message| {
+                Event::new(level, name, message)
+                    .field("family", font.family.as_str())
+                    .field("variant", format!("{:?}", font.variant))
+                    .field("index", index)
+                    .field("total", total)
+            };
+            font_event(
+                Level::Info,
+                "font-start",
+                format!("Compiling for font {} {:?}", font.family, font.variant),
+            )
+            .emit();
+            let start = Instant::now();
+
+            // Wrap the document in set rules for the font instead of setting them in the
+            // library, so that the fonts share the library and its evaluation of the document
+            // unless they differ in `sys.inputs`.
+            let scale = scaled[index].as_ref().map_or(1.0, |scaled| scaled.factor);
+            let styles = font_styles(args, font, scale) + &overrides.styles(&font.family);
+            let document = format!(
+                "{preamble}\n{styles}#include {}\n",
+                specimen::string(&main_path)
+            );
+
+            // Compile document to PNG or vector pages.
+            let guard = memory.and_then(MemoryLimit::begin_compile);
+            let mut world_for_font = world
+                .with_main_source(document)
+                .with_placeholders(placeholders(font));
+            if args.font_inputs {
+                world_for_font = world_for_font.with_inputs(args, font_inputs(font));
+            }
+            let options = RenderOptions {
+                font: world.font_index(font).and_then(|index| world.font(index)),
+                ..options.clone()
+            };
+            let skip = |event, reason: SkipReason| {
+                font_event(
+                    Level::Warn,
+                    event,
+                    format!("skipping font {} {:?}, {reason}", font.family, font.variant),
+                )
+                .emit();
+                skipped.lock().unwrap().push(Skipped {
+                    font: font.clone(),
+                    reason,
+                });
+                Ok(None)
+            };
+            let retrying = |err: &Report, attempt| {
+                font_event(
+                    Level::Warn,
+                    "font-retry",
+                    format!(
+                        "retrying font {} {:?} after a transient error: {err:#}",
+                        font.family, font.variant
+                    ),
+                )
+                .field("attempt", attempt)
+                .emit();
+            };
+            // A panic in Typst or the renderer only fails the font that caused it.
+            let rendered = panic::catch_unwind(AssertUnwindSafe(|| {
+                let compiled = retry(args, retrying, || {
+                    compile_with_timeout(&world_for_font, &options, compile_variant)
+                });
+                let compiled = match compiled {
+                    Ok(Some(compiled)) => compiled,
+                    Ok(None) => return skip("font-timeout", SkipReason::Timeout),
+                    Err(err) if err.is::<limits::Exceeded>() => {
+                        return skip(
+                            "font-memory",
+                            SkipReason::Error(err.to_string(), Vec::new()),
+                        )
+                    }
+                    Err(err) => {
+                        let diagnostics = err
+                            .chain()
+                            .find_map(|cause| cause.downcast_ref::<CompileError>())
+                            .map_or(Vec::new(), |error| error.0.clone());
+                        return skip(
+                            "font-error",
+                            SkipReason::Error(format!("{err:#}"), diagnostics),
+                        );
+                    }
+                };
+                let output = compiled.output;
+
+                // Compile the requested specimens for the font.
+                let face = world.font_index(font).and_then(|index| world.font(index));
+                let mut settings = settings.clone();
+                if settings.charmap.is_empty() {
+                    settings.charmap = specimen::rows(&compiled.coverage.text_chars);
+                }
+                // Show samples in the document's languages instead of English ones.
+                let languages: Vec<_> = compiled
+                    .coverage
+                    .languages_by_use()
+                    .into_iter()
+                    .filter_map(samples::find)
+                    .collect();
+                if args.sample_lang.is_empty() && !languages.is_empty() {
+                    settings.languages = languages.clone();
+                }
+                if let (None, None, Some(sample)) =
+                    (&args.waterfall, args.pangram, languages.first())
+                {
+                    if sample.lang != "en" {
+                        settings.waterfall = sample.text.to_owned();
+                    }
+                }
+                let mut specimens = Vec::new();
+                for (kind, source) in kinds
+                    .iter()
+                    .filter_map(|&kind| Some((kind, kind.source(face.as_ref()?, &settings)?)))
+                {
+                    font_event(
+                        Level::Trace,
+                        "specimen-start",
+                        format!("Compiling {} specimen", kind.title()),
+                    )
+                    .field("specimen", format!("{kind:?}"))
+                    .emit();
+                    let specimen_options = RenderOptions {
+                        pages: Vec::new(),
+                        ..options.clone()
+                    };
+                    let source = format!("{styles}{source}");
+                    let world = world.with_main_source(source);
+                    let image = match retry(args, retrying, || {
+                        compile_with_timeout(&world, &specimen_options, rasterize)
+                    }) {
+                        Err(err) if err.is::<limits::Exceeded>() => {
+                            return skip(
+                                "font-memory",
+                                SkipReason::Error(err.to_string(), Vec::new()),
+                            )
+                        }
+                        image => image.wrap_err_with(|| {
+                            format!("failed to compile {kind:?} specimen for font {font:?}")
+                        })?,
+                    };
+                    match image {
+                        Some(image) => specimens.push((kind, image)),
+                        None => font_event(
+                            Level::Warn,
+                            "specimen-timeout",
+                            format!(
+                                "skipping {} specimen of font {} {:?}, compilation timed out",
+                                kind.title(),
+                                font.family,
+                                font.variant
+                            ),
+                        )
+                        .emit(),
+                    }
+                }
+
+                drop(guard);
+                if args.cache_policy == CachePolicy::Aggressive {
+                    comemo::evict(0);
+                }
 
-    eprintln!("Compiling collection...");
+                let (output, specimens) = match &post_render {
+                    Some(post_render) => match post_render.process(font, output, specimens) {
+                        Ok(processed) => processed,
+                        Err(err) => {
+                            return skip(
+                                "font-post-render",
+                                SkipReason::Error(format!("{err:#}"), Vec::new()),
+                            )
+                        }
+                    },
+                    None => (output, specimens),
+                };
 
-    let map_pixels = |x| (x as f32) / args.ppi * 72.0;
-    let page_width = variants
-        .iter()
-        .map(|render| map_pixels(render.width))
-        .max_by(|a, b| a.partial_cmp(b).unwrap())
-        .unwrap_or(0.0);
+                // Move the finished renders out of memory if it is scarce.
+                let output = match output {
+                    Output::Image(image) => Output::Image(image.offload(memory)?),
+                    pages => pages,
+                };
+                let specimens = specimens
+                    .into_iter()
+                    .map(|(kind, image)| Ok((kind, image.offload(memory)?)))
+                    .collect::<Result<_>>()?;
 
-    let mut main = String::new();
-    write!(
-        main,
-        r#"
-        #let margin = 1cm
-        #let text-size = 16pt
+                font_event(
+                    Level::Debug,
+                    "font-finish",
+                    format!(
+                        "Finished font {} {:?} in {:.2?}",
+                        font.family,
+                        font.variant,
+                        start.elapsed()
+                    ),
+                )
+                .duration("duration", start.elapsed())
+                .emit();
+                timings::font(font, start);
 
-        #set document(author: "{pkg_name}")
-        #set page(
-            width: calc.max({page_width}pt, 20cm) + 2 * margin,
-            height: auto,
-            margin: margin,
-        )
-        #set text(size: text-size)
-        #set heading(numbering: "1.1")
-        #show heading: set text(size: text-size)
+                Ok(Some(Render {
+                    font: font.clone(),
+                    output,
+                    specimens,
+                    warnings: compiled.warnings,
+                    metrics: Metrics {
+                        pages: compiled.pages,
+                        duration: start.elapsed(),
+                        coverage: compiled.coverage,
+                        similarity: None,
+                        // The document's size is the one measured before scaling.
+                        scaled: scaled[index].clone().map(|scaled| Scaled {
+                            size: compiled.body_size.map(|size| size / scaled.factor),
+                            ..scaled
+                        }),
+                    },
+                    aliases: Vec::new(),
+                    similar: Vec::new(),
+                }))
+            }));
+            rendered.unwrap_or_else(|payload| {
+                skip(
+                    "font-panic",
+                    SkipReason::Error(
+                        format!("panicked: {}", panic_message(&*payload)),
+                        Vec::new(),
+                    ),
+                )
+            })
+        })
+        .collect();
 
-        #outline(indent: auto, title: [Fonts])
+    args.cache_policy.evict();
 
-        Comparison created using #link("{pkg_homepage}")[`{pkg_name} v{pkg_version}` ({pkg_homepage})]. \
-        "#,
-        pkg_name = env!("CARGO_PKG_NAME"),
-        pkg_version = env!("CARGO_PKG_VERSION"),
-        pkg_homepage = env!("CARGO_PKG_HOMEPAGE"),
-    )?;
+    let renders: Vec<_> = images?.into_iter().flatten().collect();
+    let skipped = skipped.into_inner().unwrap();
+    report_sizes(&renders, args.locale);
+    // A document that fails to compile with every font is most likely broken itself.
+    if renders.is_empty() {
+        if let Some(Skipped {
+            font,
+            reason: SkipReason::Error(error, _),
+        }) = skipped
+            .iter()
+            .find(|skipped| matches!(skipped.reason, SkipReason::Error(..)))
+        {
+            return Err(eyre!(
+                "the document failed to compile with every font, e.g. with {} {:?}: {error}",
+                font.family,
+                font.variant
+            ));
+        }
+    }
+    Ok((renders, skipped))
+}
 
-    let mut last_family = None;
-    for (n, render) in variants.iter().enumerate() {
-        let first_of_family = last_family != Some(&render.font.family);
-        write!(
-            main,
-            r#"
-            #page[
-                #if {first_of_family} [
-                    // Necessary for the outline.
-                    #place(hide[= {family}])
-                ]
-                #grid(
-                    columns: 2,
-                    column-gutter: 1fr,
-                    text(size: 1.2em, [*#counter(heading).display((n, ..) => n) {family}*]),
-                    counter(page).display(),
-                )
-                == {variant:?}
-                #image(width: {width}pt, height: {height}pt, "render-{n}.png")
-            ]
-            "#,
-            width = map_pixels(render.width),
-            height = map_pixels(render.height),
-            family = render.font.family,
-            variant = render.font.variant,
-        )?;
-        last_family = Some(&render.font.family);
+/// The specimens to render for each font and their settings.
+fn specimen_settings(args: &Args) -> (Vec<SpecimenKind>, specimen::Settings) {
+    let mut kinds = args.specimen.clone();
+    let mut settings = specimen::Settings::default();
+    let waterfall = args
+        .waterfall
+        .clone()
+        .or_else(|| args.pangram.map(|script| script.pangram().to_owned()));
+    if let Some(text) = waterfall {
+        if !kinds.contains(&SpecimenKind::Waterfall) {
+            kinds.push(SpecimenKind::Waterfall);
+        }
+        settings.waterfall = text;
+    }
+    if let Some(ranges) = &args.charmap {
+        if !kinds.contains(&SpecimenKind::Charmap) {
+            kinds.push(SpecimenKind::Charmap);
+        }
+        settings.charmap = ranges.clone();
     }
+    if let Some(pairs) = &args.kerning {
+        if !kinds.contains(&SpecimenKind::Kerning) {
+            kinds.push(SpecimenKind::Kerning);
+        }
+        if !pairs.is_empty() {
+            settings.kerning = pairs.clone();
+        }
+    }
+    if !args.sample_lang.is_empty() {
+        if !kinds.contains(&SpecimenKind::Languages) {
+            kinds.push(SpecimenKind::Languages);
+        }
+        settings.languages = args
+            .sample_lang
+            .iter()
+            .filter_map(|lang| samples::find(lang))
+            .collect();
+    }
+    (kinds, settings)
+}
 
-    world.replace_files(
-        main,
-        variants
-            .into_iter()
-            .enumerate()
-            .map(|(n, render)| (format!("render-{n}.png").into(), render.bytes)),
+/// The font provided to the document through `sys.inputs` with `--font-inputs`.
+fn font_inputs(font: &FontInfo) -> Dict {
+    let variant = font.variant;
+    let mut inputs = Dict::new();
+    inputs.insert("family".into(), font.family.as_str().into_value());
+    inputs.insert("style".into(), style_name(variant.style).into_value());
+    inputs.insert(
+        "weight".into(),
+        i64::from(variant.weight.to_number()).into_value(),
     );
+    inputs.insert("stretch".into(), variant.stretch.to_ratio().into_value());
+    inputs
+}
 
-    let mut tracer = Tracer::new();
-    let document = typst::compile(world, &mut tracer)
-        .map_err(|diag| eyre!("failed to compile collection: {diag:?}"))?;
-    Ok(typst_pdf::pdf(&document, Smart::Auto, None))
+/// The placeholders that the document can use to show which font it is set in, e.g. in a
+/// header, and their values for a font.
+fn placeholders(font: &FontInfo) -> Vec<(String, String)> {
+    let variant = font.variant;
+    vec![
+        ("{{family}}".to_owned(), font.family.clone()),
+        (
+            "{{variant}}".to_owned(),
+            format!(
+                "{} {} {}%",
+                style_name(variant.style),
+                variant.weight.to_number(),
+                variant.stretch.to_ratio().get() * 100.0
+            ),
+        ),
+    ]
 }
 
-/// Render a PNG image for each font (variant).
-fn render_variants(mut world: SystemWorld, args: &Args) -> Result<Vec<Render>> {
-    let default_styles = world.library.styles.clone();
-    let include_regex = args
-        .include
-        .as_ref()
-        .map(|regex| Regex::new(regex))
-        .transpose()
-        .wrap_err("failed to compile include regex")?;
-    let exclude_regex = args
-        .exclude
-        .as_ref()
-        .map(|regex| Regex::new(regex))
-        .transpose()
-        .wrap_err("failed to compile exclude regex")?;
-
-    let mut fonts: Vec<_> = world
-        .book
-        .families()
-        .filter(|(family, _)| {
-            include_regex
-                .as_ref()
-                .map_or(true, |include_regex| include_regex.is_match(family))
-        })
-        .filter(|(family, _)| {
-            exclude_regex
-                .as_ref()
-                .map_or(true, |exclude_regex| !exclude_regex.is_match(family))
+/// Generate the set rules that select a font variant, applied to the document and its specimens.
+fn font_styles(args: &Args, font: &FontInfo, scale: f64) -> String {
+    let fonts: Vec<String> = std::iter::once(font.family.as_str())
+        .chain(args.fallback_list.iter().map(|family| family.trim()))
+        .filter(|family| !family.is_empty())
+        .map(specimen::string)
+        .collect();
+    // A list of fallback families replaces Typst's fallback.
+    let mut styles = format!(
+        "#set text(font: ({},), fallback: {})\n",
+        fonts.join(", "),
+        args.fallback && args.fallback_list.is_empty()
+    );
+    // Only set variant information if `--variants` is set.
+    if args.fonts.variants {
+        let variant = font.variant;
+        if args
+            .fonts
+            .style
+            .iter()
+            .any(|&style| variant.style == style.into())
+        {
+            let style = style_name(variant.style);
+            writeln!(styles, "#set text(style: \"{style}\")").unwrap();
+        }
+        if args.fonts.weight.is_empty() || args.fonts.weight.contains(&variant.weight.to_number()) {
+            writeln!(styles, "#set text(weight: {})", variant.weight.to_number()).unwrap();
+        }
+        if args.fonts.stretch.is_empty()
+            || args
+                .fonts
+                .stretch
+                .iter()
+                .any(|&stretch| variant.stretch == stretch.into())
+        {
+            let percent = variant.stretch.to_ratio().get() * 100.0;
+            writeln!(styles, "#set text(stretch: {percent}%)").unwrap();
+        }
+    }
+    // A show rule also scales the sizes the document sets itself, unlike setting the base size.
+    // It applies to equations and to the leading and paragraph spacing, which are resolved
+    // with the text's size, but not to the `em` lengths of other elements, like `#v(1em)`.
+    if scale != 1.0 {
+        writeln!(styles, "#show text: set text(size: {scale}em)").unwrap();
+    }
+    styles
+}
+
+/// How to scale the size of each font, so that all have the same height with `--normalize`.
+///
+/// The height of the `--baseline` is kept, otherwise the fonts are scaled to their average.
+/// Fonts whose height is unknown aren't scaled.
+fn size_scales(world: &SystemWorld, args: &Args, fonts: &[FontInfo]) -> Vec<Option<Scaled>> {
+    let Some(normalize) = args.normalize else {
+        return vec![None; fonts.len()];
+    };
+    let heights: Vec<Option<f64>> = fonts
+        .iter()
+        .map(|font| {
+            let face = world.font(world.font_index(font)?)?;
+            Some(normalize.height(&face)).filter(|&height| height > 0.0)
         })
-        .flat_map(|(_, fonts)| {
-            // Filter out excluded fonts variants.
-            let mut fonts = fonts.filter(|font| {
-                let fitting_style = args
-                    .style
-                    .iter()
-                    .any(|&style| font.variant.style == style.into());
-                let fitting_weight = args.weight.is_empty()
-                    || args.weight.contains(&font.variant.weight.to_number());
-                let fitting_stretch = args.stretch.is_empty()
-                    || args
-                        .stretch
-                        .iter()
-                        .any(|&stretch| font.variant.stretch == stretch.into());
-                fitting_style && fitting_weight && fitting_stretch
-            });
-            // Only iterate over one font if `--variants` is not set.
-            fonts
-                .next()
-                .into_iter()
-                .chain(fonts.take_while(|_| args.variants))
+        .collect();
+    let baseline = args.baseline.as_ref().and_then(|baseline| {
+        fonts
+            .iter()
+            .zip(&heights)
+            .find(|(font, _)| font.family.eq_ignore_ascii_case(baseline))
+            .and_then(|(font, &height)| Some((font.family.clone(), height?)))
+    });
+    let known: Vec<f64> = heights.iter().flatten().copied().collect();
+    let Some(reference) = baseline
+        .as_ref()
+        .map(|&(_, height)| height)
+        .or_else(|| (!known.is_empty()).then(|| known.iter().sum::<f64>() / known.len() as f64))
+    else {
+        return vec![None; fonts.len()];
+    };
+    Event::new(
+        Level::Debug,
+        "normalize",
+        format!("scaling the fonts to a common height of {reference:.3}em"),
+    )
+    .field("reference", reference)
+    .emit();
+    heights
+        .into_iter()
+        .map(|height| {
+            Some(Scaled {
+                factor: reference / height?,
+                height: normalize,
+                baseline: baseline.as_ref().map(|(family, _)| family.clone()),
+                size: None,
+            })
         })
+        .collect()
+}
+
+/// Prints how each font was scaled with `--normalize`, and which size to use instead of the
+/// document's for the same height, in the language of the collection like its notes.
+fn report_sizes(renders: &[Render], locale: Locale) {
+    let scaled: Vec<(&Render, &Scaled)> = renders
+        .iter()
+        .filter_map(|render| Some((render, render.metrics.scaled.as_ref()?)))
         .collect();
+    let Some((_, first)) = scaled.first() else {
+        return;
+    };
+    let strings = locale.strings();
+    let height = match first.height {
+        Normalize::XHeight => strings.x_height,
+        Normalize::CapHeight => strings.cap_height,
+    };
+    let mut message = match &first.baseline {
+        Some(baseline) => strings.sizes_for_baseline.replace("{baseline}", baseline),
+        None => strings.sizes_for_average.to_owned(),
+    }
+    .replace("{height}", height);
+    let mut sizes = Vec::new();
+    for (render, scaled) in scaled {
+        let size = match (scaled.matching_size(), scaled.size) {
+            (Some(size), Some(original)) => strings
+                .size_instead
+                .replace("{size}", &metrics::points(size))
+                .replace("{original}", &metrics::points(original)),
+            _ => String::new(),
+        };
+        message += &format!(
+            "\n  {:>6.1} %  {size:<22}  {} {:?}",
+            scaled.factor * 100.0,
+            render.font.family,
+            render.font.variant
+        );
+        sizes.push(json!({
+            "family": render.font.family,
+            "variant": format!("{:?}", render.font.variant),
+            "factor": scaled.factor,
+            "size": scaled.matching_size().map(Abs::to_pt),
+            "original": scaled.size.map(Abs::to_pt),
+        }));
+    }
+    Event::new(Level::Info, "normalized-sizes", message)
+        .field("sizes", sizes)
+        .emit();
+}
 
-    // Sort fonts by family first and variant second.
-    fonts.sort_by(|a, b| a.family.cmp(&b.family).then(a.variant.cmp(&b.variant)));
+/// The name of a font style in Typst.
+fn style_name(style: typst::text::FontStyle) -> &'static str {
+    match style {
+        typst::text::FontStyle::Normal => "normal",
+        typst::text::FontStyle::Italic => "italic",
+        typst::text::FontStyle::Oblique => "oblique",
+    }
+}
 
-    let images: Result<_> = fonts
-        .into_par_iter()
-        .map_init(
-            || world.clone(),
-            |world, font| {
-                eprintln!("Compiling for font {} {:?}", font.family, font.variant);
+/// Compile the world's main file with `compile`, abandoning it after the timeout.
+///
+/// Returns `None` if the compilation timed out.
+/// Typst can't cancel a compilation, so it keeps running in the background until the process exits.
+fn compile_with_timeout<T: Send + 'static>(
+    world: &SystemWorld,
+    options: &RenderOptions,
+    compile: fn(&SystemWorld, &RenderOptions) -> Result<T>,
+) -> Result<Option<T>> {
+    let Some(timeout) = options.timeout else {
+        return limits::with_limit(options.max_memory, || compile(world, options))?.map(Some);
+    };
+    let (sender, receiver) = mpsc::channel();
+    let (world, options) = (world.clone(), options.clone());
+    std::thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            limits::with_limit(options.max_memory, || compile(&world, &options))
+        }));
+        let _ = sender.send(result);
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(Ok(result)) => result?.map(Some),
+        // Continue unwinding on this thread, so that the font is skipped.
+        Ok(Err(payload)) => panic::resume_unwind(payload),
+        Err(RecvTimeoutError::Timeout) => Ok(None),
+        Err(RecvTimeoutError::Disconnected) => Err(eyre!("compilation panicked")),
+    }
+}
 
-                // Set specified font.
-                world.library.update(|library| {
-                    default_styles.clone_into(&mut library.styles);
+/// Runs the compilation again up to `--retries` times while it fails with a transient error.
+///
+/// The caches are only cleared before each attempt with `--cache-policy aggressive`, as they
+/// are shared with the other fonts being compiled.
+fn retry<T>(
+    args: &Args,
+    retrying: impl Fn(&Report, u32),
+    mut compile: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        match compile() {
+            Err(err) if attempt < args.retries && is_transient(&err) => {
+                attempt += 1;
+                retrying(&err, attempt);
+                if args.cache_policy == CachePolicy::Aggressive {
+                    comemo::evict(0);
+                }
+            }
+            result => return result,
+        }
+    }
+}
 
-                    library.styles.set(TextElem::set_fallback(args.fallback));
+/// Whether a failed compilation might succeed when tried again, as it failed to read a file
+/// rather than because of the document or the font.
+fn is_transient(err: &Report) -> bool {
+    err.chain().any(|cause| {
+        cause.is::<std::io::Error>()
+            || cause.downcast_ref::<CompileError>().is_some_and(|error| {
+                error.0.iter().any(|diagnostic| {
+                    diagnostic.message.starts_with("failed to load file")
+                        && !diagnostic.message.contains("access denied")
+                        && !diagnostic.message.contains("is a directory")
+                })
+            })
+    })
+}
 
-                    library
-                        .styles
-                        .set_family(FontFamily::new(&font.family), StyleChain::default());
+/// The message a panic was started with, if it is a string.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
 
-                    // Only set variant information if `--variants` is set.
-                    if args.variants {
-                        if args
-                            .style
-                            .iter()
-                            .any(|&style| font.variant.style == style.into())
-                        {
-                            library.styles.set(TextElem::set_style(font.variant.style));
-                        }
-                        if args.weight.is_empty()
-                            || args.weight.contains(&font.variant.weight.to_number())
-                        {
-                            library
-                                .styles
-                                .set(TextElem::set_weight(font.variant.weight));
-                        }
-                        if args.stretch.is_empty()
-                            || args
-                                .stretch
-                                .iter()
-                                .any(|&stretch| font.variant.stretch == stretch.into())
-                        {
-                            library
-                                .styles
-                                .set(TextElem::set_stretch(font.variant.stretch));
-                        }
-                    }
-                });
+/// Compile and return the selected pages together with the warnings.
+fn compile_pages(
+    world: &SystemWorld,
+    options: &RenderOptions,
+) -> Result<(Vec<Page>, Vec<Diagnostic>)> {
+    let (document, warnings) = compile_document(world)?;
+    Ok((select_pages(document.pages, &options.pages)?, warnings))
+}
 
-                // Compile document to PNG.
-                let mut tracer = Tracer::new();
-                let document = typst::compile(world, &mut tracer)
-                    .map_err(|diag| eyre!("failed to compile for font {font:?}: {diag:?}"))?;
-                let rendered = typst_render::render_merged(
-                    &document,
-                    args.ppi / 72.0,
-                    Color::WHITE,
-                    Abs::pt(4.0),
-                    Color::BLACK,
-                );
-                Ok(Render {
-                    font: font.clone(),
-                    bytes: Bytes::from(rendered.encode_png()?),
-                    width: rendered.width(),
-                    height: rendered.height(),
-                })
-            },
-        )
-        .collect();
+/// Compile the whole document together with the warnings.
+fn compile_document(world: &SystemWorld) -> Result<(Document, Vec<Diagnostic>)> {
+    let mut tracer = Tracer::new();
+    let document = timings::time(Phase::Compile, || typst::compile(world, &mut tracer))
+        .map_err(|diag| CompileError(Diagnostic::resolve(world, &diag)))?;
+    let warnings = Diagnostic::resolve(world, &tracer.warnings());
+    Ok((document, warnings))
+}
 
-    // Reset default styles.
-    world.library.update(|library| {
-        default_styles.clone_into(&mut library.styles);
-    });
+/// Keep only the selected pages, or all pages if none are selected.
+fn select_pages(mut pages: Vec<Page>, selected: &[PageRange]) -> Result<Vec<Page>> {
+    if !selected.is_empty() {
+        let mut number = 0;
+        pages.retain(|_| {
+            number += 1;
+            selected.iter().any(|range| range.contains(number))
+        });
+        if pages.is_empty() {
+            return Err(eyre!("none of the selected pages exist"));
+        }
+    }
+    Ok(pages)
+}
+
+/// Cut the region to zoom into out of its page of the document.
+///
+/// Returns `None` if no zoom was requested.
+fn zoom_inset(document: &Document, options: &RenderOptions) -> Result<Option<Frame>> {
+    let (page, region) = match (&options.zoom_label, options.zoom) {
+        (None, None) => return Ok(None),
+        (None, Some(region)) => {
+            let first = options.pages.iter().map(|range| range.start).min();
+            (first.unwrap_or(1), region)
+        }
+        (Some(label), region) => {
+            let location = document
+                .introspector
+                .query_label(Label::new(label.as_str()))
+                .ok()
+                .and_then(|element| element.location())
+                .ok_or_else(|| eyre!("label <{label}> was not found"))?;
+            let position = document.introspector.position(location);
+            let region = region.unwrap_or(Region {
+                origin: Point::zero(),
+                size: Size::new(Abs::cm(6.0), Abs::cm(2.0)),
+            });
+            (position.page.get(), region.translate(position.point))
+        }
+    };
+    let page = document
+        .pages
+        .get(page - 1)
+        .ok_or_else(|| eyre!("page {page} to zoom into does not exist"))?;
+    Ok(Some(region.crop(&page.frame)))
+}
+
+/// Compile the variant's document into its output.
+fn compile_variant(world: &SystemWorld, options: &RenderOptions) -> Result<Compiled> {
+    let original = world;
+    let justified;
+    let world = if options.compare_justify {
+        justified = world.with_prelude("#set par(justify: true)\n")?;
+        &justified
+    } else {
+        world
+    };
+    let (document, warnings) = compile_document(world)?;
+    let inset = zoom_inset(&document, options)?;
+    let mut pages = select_pages(document.pages, &options.pages)?;
+    let count = pages.len();
+    let font = options
+        .font
+        .as_ref()
+        .expect("the font is set for each variant");
+    let coverage = Coverage::measure(&pages, font);
+    let body_size = metrics::body_size(&pages, &font.info().family);
+    if options.compare_justify {
+        // Follow each justified page with its ragged-right counterpart.
+        let ragged = original.with_prelude("#set par(justify: false)\n")?;
+        let ragged = select_pages(compile_document(&ragged)?.0.pages, &options.pages)?;
+        pages = pages
+            .into_iter()
+            .zip(ragged)
+            .flat_map(|(justified, ragged)| [justified, ragged])
+            .collect();
+    }
+    if options.show_metrics {
+        // Without text in the font, there is no line to draw the metrics of.
+        if let Some(size) = body_size {
+            guides::draw_metrics(&mut pages, &font.info().family, size);
+        }
+    }
+    if options.show_measurements {
+        guides::annotate_measurements(world, &mut pages)?;
+    }
+    if let Some(region) = options.crop {
+        for page in &mut pages {
+            page.frame = region.crop(&page.frame);
+        }
+    }
+    let output = if options.vector {
+        Output::Pages(pages)
+    } else {
+        Output::Image(timings::time(Phase::Encode, || {
+            rasterize_pages(pages, inset, options)
+        })?)
+    };
+    Ok(Compiled {
+        output,
+        pages: count,
+        coverage,
+        body_size,
+        warnings,
+    })
+}
+
+/// The result of compiling a variant's document.
+struct Compiled {
+    output: Output,
+    /// The number of selected pages.
+    pages: usize,
+    coverage: Coverage,
+    /// The size most of the text set in the font is set in.
+    body_size: Option<Abs>,
+    warnings: Vec<Diagnostic>,
+}
+
+/// Compile and render the selected pages into a single PNG.
+fn rasterize(world: &SystemWorld, options: &RenderOptions) -> Result<Image> {
+    let pages = compile_pages(world, options)?.0;
+    timings::time(Phase::Encode, || rasterize_pages(pages, None, options))
+}
+
+/// Render pages into a single PNG, with a magnified inset to the right of them.
+///
+/// The inset is scaled to the width of the first page.
+fn rasterize_pages(
+    pages: Vec<Page>,
+    inset: Option<Frame>,
+    options: &RenderOptions,
+) -> Result<Image> {
+    let pixel_per_pt = options.ppi * options.supersample as f32 / 72.0;
+    let padding = Abs::pt(4.0);
+    let pixmaps: Vec<_> = pages
+        .iter()
+        .map(|page| typst_render::render(&page.frame, pixel_per_pt, Color::WHITE))
+        .collect();
+    let page_width = pages.first().map(|page| page.frame.width());
+    let (mut rendered, pages) = raster::stack(
+        &pixmaps,
+        (pixel_per_pt * padding.to_pt() as f32).round() as u32,
+    );
+    if let (Some(inset), Some(page_width)) = (inset, page_width) {
+        let scale = page_width / inset.width();
+        let document = Document {
+            pages: vec![Page {
+                frame: inset,
+                numbering: None,
+                number: 1,
+            }],
+            ..Document::default()
+        };
+        // Shrink the padding by the scale, so that it is as wide as around the pages.
+        let zoomed = typst_render::render_merged(
+            &document,
+            pixel_per_pt * scale as f32,
+            Color::WHITE,
+            padding / scale,
+            Color::BLACK,
+        );
+        let overlap = (pixel_per_pt * padding.to_pt() as f32).round() as u32;
+        rendered = raster::beside(&rendered, &zoomed, overlap);
+    }
+    let rendered = raster::downsample(rendered, options.supersample);
+    let factor = options.supersample;
+    Ok(Image {
+        width: rendered.width(),
+        height: rendered.height(),
+        data: FileData::pixmap(rendered),
+        pages: pages
+            .into_iter()
+            .map(|area| raster::downsample_area(area, factor))
+            .collect(),
+    })
+}
 
-    comemo::evict(1);
+/// Settings for compiling and rasterizing a single document.
+#[derive(Clone)]
+struct RenderOptions {
+    ppi: f32,
+    supersample: u32,
+    /// The pages to render, or all pages if empty.
+    pages: Vec<PageRange>,
+    /// How long the compilation may take before it is abandoned.
+    timeout: Option<Duration>,
+    /// How many bytes the compilation may allocate before it fails.
+    max_memory: Option<u64>,
+    /// Whether to keep the pages as vector graphics instead of rasterizing them.
+    vector: bool,
+    /// The font whose coverage of the text is measured, set for each variant.
+    font: Option<Font>,
+    /// Whether to draw the vertical metrics over the first line of text.
+    show_metrics: bool,
+    /// Whether to print the measurements of the text block into the margin.
+    show_measurements: bool,
+    /// Whether to follow each page with a ragged-right version of it.
+    compare_justify: bool,
+    /// The region to add a magnified inset of, relative to the labelled element if one is given.
+    zoom: Option<Region>,
+    zoom_label: Option<String>,
+    /// The region of each page to render, or the whole page if not given.
+    crop: Option<Region>,
+}
 
-    images
+impl RenderOptions {
+    fn new(args: &Args) -> RenderOptions {
+        RenderOptions {
+            ppi: args.ppi,
+            supersample: args.supersample,
+            pages: args.pages.clone(),
+            timeout: args.timeout,
+            max_memory: args.max_compile_memory,
+            vector: args.vector,
+            show_metrics: args.show_metrics,
+            show_measurements: args.show_measurements,
+            compare_justify: args.compare_justify,
+            zoom: args.zoom,
+            zoom_label: args.zoom_label.clone(),
+            crop: args.crop,
+            font: None,
+        }
+    }
 }
 
+#[derive(Clone)]
 struct Render {
     font: FontInfo,
-    bytes: Bytes,
+    output: Output,
+    specimens: Vec<(SpecimenKind, Image)>,
+    /// The warnings of compiling the document.
+    warnings: Vec<Diagnostic>,
+    metrics: Metrics,
+    /// The fonts whose renders are identical to this one and were merged into it.
+    aliases: Vec<FontInfo>,
+    /// The variants of the family that were merged into this one for looking nearly the same,
+    /// with their similarity to it.
+    similar: Vec<(FontInfo, f64)>,
+}
+
+impl Render {
+    /// The path of the render's image without extension when it is written out individually.
+    ///
+    /// The paths of its specimens append the specimen name to it.
+    fn file_stem(&self, n: usize) -> String {
+        format!(
+            "renders/{n:03}-{}",
+            file_name(&self.font.family).replace(' ', "-")
+        )
+    }
+
+    /// The names of the fonts merged into this render, like `Family Normal-400-FontStretch(1000)`.
+    fn alias_names(&self) -> Vec<String> {
+        self.aliases
+            .iter()
+            .map(|font| format!("{} {:?}", font.family, font.variant))
+            .collect()
+    }
+
+    /// Sentences naming the fonts merged into this render and how it was scaled with
+    /// `--normalize`, if there are any.
+    fn note(&self, locale: Locale) -> Option<String> {
+        let strings = locale.strings();
+        let mut sentences = Vec::new();
+        if !self.aliases.is_empty() {
+            sentences.push(
+                strings
+                    .identical
+                    .replace("{fonts}", &self.alias_names().join(", ")),
+            );
+        }
+        if !self.similar.is_empty() {
+            let variants: Vec<String> = self
+                .similar
+                .iter()
+                .map(|(font, similarity)| {
+                    format!("{:?} ({:.1} %)", font.variant, similarity * 100.0)
+                })
+                .collect();
+            sentences.push(strings.similar.replace("{variants}", &variants.join(", ")));
+        }
+        if let Some(scaled) = &self.metrics.scaled {
+            let height = match scaled.height {
+                Normalize::XHeight => strings.x_height,
+                Normalize::CapHeight => strings.cap_height,
+            };
+            let factor = format!("{:.1} %", scaled.factor * 100.0);
+            sentences.push(
+                match &scaled.baseline {
+                    Some(baseline) => strings.scaled_to_baseline.replace("{baseline}", baseline),
+                    None => strings.scaled_to_average.to_owned(),
+                }
+                .replace("{factor}", &factor)
+                .replace("{height}", height),
+            );
+            if let (Some(size), Some(original)) = (scaled.matching_size(), scaled.size) {
+                sentences.push(
+                    strings
+                        .scaled_size
+                        .replace("{size}", &metrics::points(size))
+                        .replace("{original}", &metrics::points(original))
+                        .replace("{height}", height),
+                );
+            }
+        }
+        (!sentences.is_empty()).then(|| sentences.join(" "))
+    }
+}
+
+/// A selected font that couldn't be rendered.
+#[derive(Clone)]
+struct Skipped {
+    font: FontInfo,
+    reason: SkipReason,
+}
+
+#[derive(Clone)]
+enum SkipReason {
+    /// The compilation took longer than the timeout.
+    Timeout,
+    /// The compilation failed with this error, caused by these diagnostics if it was Typst's.
+    Error(String, Vec<Diagnostic>),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Timeout => write!(f, "compilation timed out"),
+            SkipReason::Error(error, _) => write!(f, "compilation failed: {error}"),
+        }
+    }
+}
+
+/// The rendered document of a variant.
+#[derive(Clone)]
+enum Output {
+    /// The selected pages merged into a single image.
+    Image(Image),
+    /// The selected pages as vector graphics.
+    Pages(Vec<Page>),
+}
+
+#[derive(Clone)]
+struct Image {
+    data: FileData,
     width: u32,
     height: u32,
+    /// The area of the image that each page covers.
+    pages: Vec<IntRect>,
+}
+
+impl Image {
+    /// Move the image data to disk if memory is scarce.
+    fn offload(self, memory: Option<&MemoryLimit>) -> Result<Image> {
+        let Some(memory) = memory else {
+            return Ok(self);
+        };
+        Ok(Image {
+            data: memory.offload(self.data)?,
+            ..self
+        })
+    }
 }
 
 #[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
@@ -343,3 +3004,340 @@ impl From<FontStretch> for typst::text::FontStretch {
         }
     }
 }
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// A PDF collection of all variants.
+    Pdf,
+    /// A ZIP archive with the collection, the individual renders, their metadata and an HTML index.
+    Zip,
+    /// A Markdown report with the individual renders and metrics for each font.
+    Markdown,
+    /// An HTML gallery of the individual renders, in which two fonts can be compared with a slider.
+    Html,
+}
+
+impl Format {
+    /// The extension of the default output path.
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Pdf => "variants.pdf",
+            Format::Zip => "variants.zip",
+            Format::Markdown => "variants.md",
+            Format::Html => "variants.html",
+        }
+    }
+
+    /// The extension of an output path given for several formats.
+    fn file_extension(self) -> &'static str {
+        match self {
+            Format::Pdf => "pdf",
+            Format::Zip => "zip",
+            Format::Markdown => "md",
+            Format::Html => "html",
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Preset {
+    /// 96 PPI, only the first page and a compact layout starting with a contact sheet, for a
+    /// fast overview.
+    Quick,
+    /// 150 PPI with 2× supersampling, written as a PDF and an HTML gallery, for reviewing on
+    /// screen.
+    Review,
+    /// 300 PPI with 2× supersampling on A4 pages, for printing.
+    Print,
+}
+
+impl Preset {
+    /// The values this preset provides, by the long name of their option.
+    ///
+    /// Flags are given without a value.
+    fn defaults(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Preset::Quick => &[
+                ("ppi", "96"),
+                ("supersample", "1"),
+                ("pages", "1"),
+                ("layout", "compact"),
+                ("overview", ""),
+            ],
+            Preset::Review => &[("ppi", "150"), ("supersample", "2"), ("format", "pdf,html")],
+            Preset::Print => &[("ppi", "300"), ("supersample", "2"), ("paper", "a4")],
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Paper {
+    /// Pages as wide as the renders and as tall as their content.
+    Auto,
+    A4,
+    Letter,
+}
+
+impl Paper {
+    /// The name of the paper size in Typst, or `None` for pages fitted to the content.
+    fn name(self) -> Option<&'static str> {
+        match self {
+            Paper::Auto => None,
+            Paper::A4 => Some("a4"),
+            Paper::Letter => Some("us-letter"),
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Theme {
+    /// Black text on white pages.
+    Light,
+    /// Light text and blue accents on dark gray pages.
+    Dark,
+    /// Brown text and accents on cream pages.
+    Sepia,
+}
+
+impl Theme {
+    /// The colors of the pages, the text and the links and headings.
+    fn colors(self) -> [[u8; 3]; 3] {
+        match self {
+            Theme::Light => [[255, 255, 255], [0, 0, 0], [0, 0, 0]],
+            Theme::Dark => [[30, 30, 30], [230, 230, 230], [102, 179, 255]],
+            Theme::Sepia => [[244, 236, 216], [91, 70, 54], [156, 79, 31]],
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// Narrow margins and small labels, fitting more on each page.
+    Compact,
+    /// 1 cm margins and 16 pt labels.
+    Regular,
+    /// Wide margins and generous spacing, for presentation.
+    Spacious,
+}
+
+/// The spacing and sizes of the collection's pages.
+struct PageLayout {
+    margin: Abs,
+    /// The size of the labels and headings.
+    label_size: Abs,
+    /// The space between the renders and specimens, in ems of the label size.
+    spacing: f64,
+    /// The number of thumbnails in each row of the overview.
+    thumbnails: usize,
+}
+
+impl PageLayout {
+    /// The layout of the arguments, with the lengths given explicitly taking precedence.
+    fn new(args: &Args) -> PageLayout {
+        let (margin, label_size, spacing, thumbnails) = match args.layout {
+            Layout::Compact => (Abs::cm(0.5), Abs::pt(11.0), 0.5, 6),
+            Layout::Regular => (Abs::cm(1.0), Abs::pt(16.0), 1.0, 4),
+            Layout::Spacious => (Abs::cm(2.0), Abs::pt(16.0), 2.0, 3),
+        };
+        PageLayout {
+            margin: args.collection_margin.unwrap_or(margin),
+            label_size: args.label_size.unwrap_or(label_size),
+            spacing,
+            thumbnails,
+        }
+    }
+}
+
+/// The height that `--normalize` makes the same for all fonts.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Normalize {
+    /// The height of lowercase letters like x.
+    XHeight,
+    /// The height of capital letters.
+    CapHeight,
+}
+
+impl Normalize {
+    /// The height of the font, relative to its size.
+    ///
+    /// Many fonts don't state their heights, and Typst uses the ascender in their place, so
+    /// the height of a typical glyph is measured instead.
+    fn height(self, font: &Font) -> f64 {
+        let ttf = font.ttf();
+        let (stated, glyph) = match self {
+            Normalize::XHeight => (ttf.x_height(), 'x'),
+            Normalize::CapHeight => (ttf.capital_height(), 'H'),
+        };
+        let measured = || {
+            let bbox = ttf.glyph_bounding_box(ttf.glyph_index(glyph)?)?;
+            Some(bbox.y_max)
+        };
+        stated
+            .filter(|&height| height > 0)
+            .or_else(measured)
+            .map_or(0.0, |height| font.to_em(height).get())
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum CachePolicy {
+    /// Clear all caches after each font, keeping the memory usage low.
+    Aggressive,
+    /// Drop cache entries that were unused while rendering the variants.
+    Balanced,
+    /// Never evict, keeping caches warm for the collection and repeated compilations.
+    Keep,
+}
+
+impl CachePolicy {
+    /// Evict the caches after all variants are rendered.
+    fn evict(self) {
+        match self {
+            CachePolicy::Aggressive => comemo::evict(0),
+            CachePolicy::Balanced => comemo::evict(1),
+            CachePolicy::Keep => {}
+        }
+    }
+}
+
+/// An inclusive range of page numbers, starting at one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PageRange {
+    start: usize,
+    end: Option<usize>,
+}
+
+impl PageRange {
+    fn single(page: usize) -> PageRange {
+        PageRange {
+            start: page,
+            end: Some(page),
+        }
+    }
+
+    fn contains(&self, page: usize) -> bool {
+        self.start <= page && self.end.map_or(true, |end| page <= end)
+    }
+}
+
+impl FromStr for PageRange {
+    type Err = String;
+
+    /// Parses a single page `3`, a closed range `3-5`, or an open range `3-`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |number: &str| match number.trim().parse() {
+            Ok(0) | Err(_) => Err(format!("invalid page number `{number}`")),
+            Ok(number) => Ok(number),
+        };
+        match s.split_once('-') {
+            None => Ok(PageRange::single(parse(s)?)),
+            Some((start, "")) => Ok(PageRange {
+                start: parse(start)?,
+                end: None,
+            }),
+            Some((start, end)) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if end < start {
+                    return Err(format!("page range `{s}` ends before it starts"));
+                }
+                Ok(PageRange {
+                    start,
+                    end: Some(end),
+                })
+            }
+        }
+    }
+}
+
+/// Parses a code point like `U+20AC` or a range of them like `U+0370-U+03FF`.
+fn parse_code_points(s: &str) -> Result<RangeInclusive<u32>, String> {
+    let code_point = |s: &str| {
+        let hex = s.trim();
+        let hex = hex
+            .strip_prefix("U+")
+            .or(hex.strip_prefix("u+"))
+            .unwrap_or(hex);
+        u32::from_str_radix(hex, 16).map_err(|_| format!("invalid code point `{s}`"))
+    };
+    let (start, end) = match s.split_once('-') {
+        Some((start, end)) => (code_point(start)?, code_point(end)?),
+        None => (code_point(s)?, code_point(s)?),
+    };
+    if start > end {
+        return Err(format!("code point range `{s}` ends before it starts"));
+    }
+    Ok(start..=end)
+}
+
+/// Parses the code of a language with a bundled sample, like `de`.
+fn parse_language(s: &str) -> Result<String, String> {
+    match samples::find(s.trim()) {
+        Some(sample) => Ok(sample.lang.to_owned()),
+        None => Err(format!(
+            "no sample for language `{s}`, available are {}",
+            samples::SAMPLES
+                .iter()
+                .map(|sample| sample.lang)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Parses an ISO 639 language code like `de`.
+fn parse_lang(s: &str) -> Result<Lang, String> {
+    s.trim()
+        .parse()
+        .map_err(|err| format!("invalid language `{s}`: {err}"))
+}
+
+/// Checks a Typst numbering pattern like `i` or `1 of 1`, keeping it as written.
+fn parse_numbering(s: &str) -> Result<String, String> {
+    s.parse::<NumberingPattern>()
+        .map(|_| s.to_owned())
+        .map_err(|err| format!("{err} `{s}`"))
+}
+
+/// Parses a length relative to the font size like `0.7em`, or an absolute one like `3mm`.
+fn parse_leading(s: &str) -> Result<Length, String> {
+    match s.trim().strip_suffix("em") {
+        Some(em) => em
+            .trim()
+            .parse()
+            .map(|em| Em::new(em).into())
+            .map_err(|_| format!("invalid length `{s}`")),
+        None => region::parse_length(s).map(Length::from),
+    }
+}
+
+/// Parses a similarity score between 0 and 1.
+fn parse_similarity(s: &str) -> Result<f64, String> {
+    let similarity: f64 = s
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid similarity `{s}`"))?;
+    if !(0.0..=1.0).contains(&similarity) {
+        return Err(format!("similarity `{s}` is not between 0 and 1"));
+    }
+    Ok(similarity)
+}
+
+/// Parses a duration like `500ms`, `90s`, `2m` or `1h`, defaulting to seconds without a unit.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration `{s}`"))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" | "min" => number * 60.0,
+        "h" => number * 3600.0,
+        unit => return Err(format!("unknown duration unit `{unit}`")),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|err| format!("invalid duration `{s}`: {err}"))
+}
@@ -0,0 +1,109 @@
+//! A Markdown report with image links and metrics for each font, e.g. for GitHub issues.
+
+use std::{fmt::Write, path::Path};
+
+use color_eyre::eyre::Result;
+
+use crate::{format_size, locale::Locale, world::SystemWorld, write_files, Args, Output, Render};
+
+/// Write the report to `output` and the renders it links to into a `renders` directory next to it.
+pub(crate) fn write_markdown(
+    world: &SystemWorld,
+    args: &Args,
+    variants: Vec<Render>,
+    partial: bool,
+    output: &Path,
+) -> Result<()> {
+    let mut report = String::new();
+    writeln!(
+        report,
+        "# Font comparison of `{input}`\n\n\
+         Created using [{pkg_name} v{pkg_version}]({pkg_homepage}).",
+        input = args.input().display(),
+        pkg_name = env!("CARGO_PKG_NAME"),
+        pkg_version = env!("CARGO_PKG_VERSION"),
+        pkg_homepage = env!("CARGO_PKG_HOMEPAGE"),
+    )?;
+    if partial {
+        writeln!(
+            report,
+            "\n**This comparison is partial.** Rendering was interrupted, \
+             so only the {} variants rendered until then are included.",
+            variants.len(),
+        )?;
+    }
+
+    let mut files = Vec::new();
+    let mut last_family = None;
+    for (n, render) in variants.iter().enumerate() {
+        let family = escape(&render.font.family);
+        if last_family != Some(&render.font.family) {
+            writeln!(report, "\n## {family}")?;
+        }
+        last_family = Some(&render.font.family);
+        let variant = render.font.variant;
+        writeln!(report, "\n### {variant:?}\n")?;
+        if let Some(note) = render.note(Locale::En) {
+            writeln!(report, "*{}*\n", escape(&note))?;
+        }
+
+        let stem = render.file_stem(n);
+        let size = match &render.output {
+            Output::Image(image) => {
+                let name = format!("{stem}.png");
+                writeln!(report, "![{family} {variant:?}]({name})\n")?;
+                files.push((name, &image.data));
+                format!("{} × {} px", image.width, image.height)
+            }
+            Output::Pages(_) => {
+                writeln!(
+                    report,
+                    "*Rendered as vector pages, which are only part of the PDF.*\n"
+                )?;
+                "vector".to_owned()
+            }
+        };
+
+        let file = world
+            .font_index(&render.font)
+            .map(|index| world.font_file(index));
+        writeln!(
+            report,
+            "| Pages | Render size | Coverage | Compile time | File format | Glyphs | File size |\n\
+             | ---: | ---: | ---: | ---: | --- | ---: | ---: |\n\
+             | {pages} | {size} | {coverage:.1} % | {duration:.2?} | {format} | {glyphs} | {file_size} |",
+            pages = render.metrics.pages,
+            coverage = render.metrics.coverage.ratio() * 100.0,
+            duration = render.metrics.duration,
+            format = file.map_or("unknown".to_owned(), |file| file.to_string()),
+            glyphs = file.map_or("?".to_owned(), |file| file.glyphs.to_string()),
+            file_size = file.map_or("?".to_owned(), |file| format_size(file.size)),
+        )?;
+
+        for (kind, image) in &render.specimens {
+            let name = format!("{stem}-{}.png", kind.name());
+            writeln!(
+                report,
+                "\n#### {title}\n\n![{title}]({name})",
+                title = kind.title(),
+            )?;
+            files.push((name, &image.data));
+        }
+    }
+
+    write_files(output, files)?;
+    std::fs::write(output, report)?;
+    Ok(())
+}
+
+/// Escape the characters that Markdown would interpret in a heading or table cell.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]<>|#".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,145 @@
+//! Keeping the memory usage below a limit by trading speed for memory.
+
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+use crate::{
+    cache,
+    logging::{Event, Level},
+    world::FileData,
+};
+use color_eyre::eyre::{eyre, Context, Result};
+
+/// The fraction of the limit at which memory is considered scarce.
+const THRESHOLD: f64 = 0.8;
+
+/// A memory limit that the rendering adapts to once it is approached.
+pub(crate) struct MemoryLimit {
+    limit: u64,
+    /// Held during compilations while memory is scarce, so that they run one at a time.
+    serial: Mutex<()>,
+    warned: AtomicBool,
+    spill_dir: PathBuf,
+    spilled: AtomicUsize,
+}
+
+impl MemoryLimit {
+    pub(crate) fn new(limit: u64) -> MemoryLimit {
+        if resident().is_none() {
+            Event::new(
+                Level::Warn,
+                "memory-unsupported",
+                "memory usage can't be measured on this platform, ignoring the memory limit",
+            )
+            .emit();
+        }
+        MemoryLimit {
+            limit,
+            serial: Mutex::new(()),
+            warned: AtomicBool::new(false),
+            spill_dir: cache::run_dir().join("spill"),
+            spilled: AtomicUsize::new(0),
+        }
+    }
+
+    /// Whether the memory usage is close to the limit.
+    pub(crate) fn is_scarce(&self) -> bool {
+        let Some(resident) = resident() else {
+            return false;
+        };
+        let scarce = resident as f64 >= self.limit as f64 * THRESHOLD;
+        if scarce && !self.warned.swap(true, Ordering::SeqCst) {
+            Event::new(
+                Level::Warn,
+                "memory-scarce",
+                format!(
+                    "memory usage of {resident} bytes is approaching the limit, \
+                     compiling one font at a time and moving renders to disk"
+                ),
+            )
+            .field("resident", resident)
+            .field("limit", self.limit)
+            .emit();
+        }
+        scarce
+    }
+
+    /// Prepares a compilation, returning a guard that must be held while compiling.
+    ///
+    /// While memory is scarce, caches are evicted and compilations are serialized.
+    pub(crate) fn begin_compile(&self) -> Option<MutexGuard<'_, ()>> {
+        if !self.is_scarce() {
+            return None;
+        }
+        let guard = self.serial.lock().unwrap_or_else(|err| err.into_inner());
+        comemo::evict(0);
+        Some(guard)
+    }
+
+    /// Moves file data to disk while memory is scarce.
+    pub(crate) fn offload(&self, data: FileData) -> Result<FileData> {
+        if matches!(data, FileData::Path(_)) || !self.is_scarce() {
+            return Ok(data);
+        }
+        let bytes = data
+            .bytes()
+            .map_err(|err| eyre!("failed to encode render: {err}"))?;
+        fs::create_dir_all(&self.spill_dir).wrap_err("failed to create spill directory")?;
+        let n = self.spilled.fetch_add(1, Ordering::SeqCst);
+        let path = self.spill_dir.join(format!("{n}.png"));
+        fs::write(&path, bytes.as_slice())
+            .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+        Ok(FileData::Path(path))
+    }
+}
+
+impl Drop for MemoryLimit {
+    fn drop(&mut self) {
+        if self.spilled.load(Ordering::SeqCst) > 0 {
+            let _ = fs::remove_dir_all(&self.spill_dir);
+            // Only succeeds if the run keeps no other files.
+            let _ = fs::remove_dir(cache::run_dir());
+        }
+    }
+}
+
+/// Returns the resident memory of this process in bytes, if it can be measured.
+#[cfg(target_os = "linux")]
+fn resident() -> Option<u64> {
+    // The second field is the number of resident pages.
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * u64::try_from(page_size).ok()?)
+}
+
+/// Returns the resident memory of this process in bytes, if it can be measured.
+#[cfg(not(target_os = "linux"))]
+fn resident() -> Option<u64> {
+    None
+}
+
+/// Parses a size like `4G`, `512MiB` or `1.5g` into bytes, using binary units.
+pub(crate) fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("invalid size `{s}`"))?;
+    let exponent = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 1,
+        "m" | "mb" | "mib" => 2,
+        "g" | "gb" | "gib" => 3,
+        "t" | "tb" | "tib" => 4,
+        unit => return Err(format!("unknown size unit `{unit}`")),
+    };
+    Ok((number * 1024f64.powi(exponent)) as u64)
+}
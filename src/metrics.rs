@@ -0,0 +1,296 @@
+//! Measurements of the rendered variants and their export as CSV.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::Path,
+    time::Duration,
+};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use rayon::prelude::*;
+use tiny_skia::Pixmap;
+use typst::{
+    layout::{Abs, Frame, FrameItem, Page},
+    text::Font,
+};
+
+use crate::{
+    diff,
+    logging::{Event, Level},
+    raster::{self, Palette},
+    style_name, Normalize, Output, Render,
+};
+
+/// Measurements of a variant's render, shown in reports.
+#[derive(Clone)]
+pub(crate) struct Metrics {
+    /// The number of selected pages.
+    pub(crate) pages: usize,
+    /// How long compiling the variant and its specimens took.
+    pub(crate) duration: Duration,
+    pub(crate) coverage: Coverage,
+    /// How similar the render is to the render of the baseline, if one is set.
+    pub(crate) similarity: Option<f64>,
+    /// How the font was scaled with `--normalize`.
+    pub(crate) scaled: Option<Scaled>,
+}
+
+/// How `--normalize` scaled a font to match the height of the others.
+#[derive(Clone)]
+pub(crate) struct Scaled {
+    /// The factor the font's size was scaled by.
+    pub(crate) factor: f64,
+    pub(crate) height: Normalize,
+    /// The family whose height was matched, or `None` for the average of the fonts.
+    pub(crate) baseline: Option<String>,
+    /// The size that most of the document's text is set in before scaling, unless the font
+    /// set none of it.
+    pub(crate) size: Option<Abs>,
+}
+
+impl Scaled {
+    /// The size to use instead of the document's for the same height.
+    pub(crate) fn matching_size(&self) -> Option<Abs> {
+        Some(self.size? * self.factor)
+    }
+}
+
+/// A size in points, rounded to a tenth, like `10.6pt`.
+pub(crate) fn points(size: Abs) -> String {
+    format!("{}pt", (size.to_pt() * 10.0).round() / 10.0)
+}
+
+/// The size that most of the text set in the font is set in.
+pub(crate) fn body_size(pages: &[Page], family: &str) -> Option<Abs> {
+    /// Adds the number of characters of each size in the frame, in hundredths of a point.
+    fn add_frame(sizes: &mut BTreeMap<i64, usize>, frame: &Frame, family: &str) {
+        for (_, item) in frame.items() {
+            match item {
+                FrameItem::Group(group) => add_frame(sizes, &group.frame, family),
+                FrameItem::Text(text) if text.font.info().family.eq_ignore_ascii_case(family) => {
+                    let size = (text.size.to_pt() * 100.0).round() as i64;
+                    *sizes.entry(size).or_default() += text.text.chars().count();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut sizes = BTreeMap::new();
+    for page in pages {
+        add_frame(&mut sizes, &page.frame, family);
+    }
+    let (size, _) = sizes.into_iter().max_by_key(|&(_, chars)| chars)?;
+    Some(Abs::pt(size as f64 / 100.0))
+}
+
+/// How much of the document's text a font provides glyphs for.
+#[derive(Clone)]
+pub(crate) struct Coverage {
+    /// The number of characters in the text, excluding whitespace.
+    pub(crate) chars: usize,
+    /// The number of characters the font provides no glyph for.
+    pub(crate) missing: usize,
+    /// The distinct characters the font provides no glyph for.
+    pub(crate) missing_chars: BTreeSet<char>,
+    /// The distinct characters of the text, excluding whitespace.
+    pub(crate) text_chars: BTreeSet<char>,
+    /// The number of characters of the text in each language set with `text.lang`.
+    pub(crate) languages: BTreeMap<String, usize>,
+}
+
+impl Coverage {
+    /// Measures the coverage of the text on the pages by a font.
+    ///
+    /// Characters are looked up in the font itself, so text that falls back to another font
+    /// counts as missing. Text in another font that the font has glyphs for was set in it
+    /// explicitly, like raw text or math, and is left out.
+    pub(crate) fn measure(pages: &[Page], font: &Font) -> Coverage {
+        let mut coverage = Coverage {
+            chars: 0,
+            missing: 0,
+            missing_chars: BTreeSet::new(),
+            text_chars: BTreeSet::new(),
+            languages: BTreeMap::new(),
+        };
+        for page in pages {
+            coverage.add_frame(&page.frame, font);
+        }
+        coverage
+    }
+
+    fn add_frame(&mut self, frame: &Frame, font: &Font) {
+        let family = &font.info().family;
+        let ttf = font.ttf();
+        for (_, item) in frame.items() {
+            match item {
+                FrameItem::Group(group) => self.add_frame(&group.frame, font),
+                FrameItem::Text(text) => {
+                    let own_font = text.font.info().family.eq_ignore_ascii_case(family);
+                    // Fallback only sets the characters the font lacks, so any other
+                    // character means that the text's font was chosen by its styles.
+                    let styled = !own_font
+                        && (text.font.ttf().tables().math.is_some()
+                            || text
+                                .text
+                                .chars()
+                                .any(|c| !c.is_whitespace() && ttf.glyph_index(c).is_some()));
+                    if styled {
+                        continue;
+                    }
+                    for glyph in &text.glyphs {
+                        let chars = text.text[glyph.range()]
+                            .chars()
+                            .filter(|c| !c.is_whitespace());
+                        for c in chars {
+                            self.chars += 1;
+                            self.text_chars.insert(c);
+                            *self
+                                .languages
+                                .entry(text.lang.as_str().to_owned())
+                                .or_default() += 1;
+                            let missing = if own_font {
+                                glyph.id == 0
+                            } else {
+                                ttf.glyph_index(c).is_none()
+                            };
+                            if missing {
+                                self.missing += 1;
+                                self.missing_chars.insert(c);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The languages of the text, the most used first.
+    pub(crate) fn languages_by_use(&self) -> Vec<&str> {
+        let mut languages: Vec<(&str, usize)> = self
+            .languages
+            .iter()
+            .map(|(lang, &chars)| (lang.as_str(), chars))
+            .collect();
+        languages.sort_by(|(_, a), (_, b)| b.cmp(a));
+        languages.into_iter().map(|(lang, _)| lang).collect()
+    }
+
+    /// The fraction of characters the font provides, or 1 if there is no text.
+    pub(crate) fn ratio(&self) -> f64 {
+        if self.chars == 0 {
+            return 1.0;
+        }
+        1.0 - self.missing as f64 / self.chars as f64
+    }
+}
+
+/// Sets the similarity of each rasterized variant to the first variant of the baseline family,
+/// and writes the differences of each of their pages into a directory if one is given.
+pub(crate) fn compare_to_baseline(
+    variants: &mut [Render],
+    baseline: &str,
+    page_diffs: Option<(&Path, Palette)>,
+) -> Result<()> {
+    let Some(reference) = variants
+        .iter()
+        .find(|render| render.font.family.eq_ignore_ascii_case(baseline))
+    else {
+        Event::new(
+            Level::Warn,
+            "baseline-missing",
+            format!("baseline {baseline} was not rendered, skipping the similarity scores"),
+        )
+        .emit();
+        return Ok(());
+    };
+    let (reference_font, reference_pages) =
+        (reference.font.clone(), diff::page_areas(reference).to_vec());
+    let Some(reference) = decode(reference)? else {
+        Event::new(
+            Level::Warn,
+            "baseline-vector",
+            "the similarity score is only measured for images, not vector pages",
+        )
+        .emit();
+        return Ok(());
+    };
+
+    variants.par_iter_mut().try_for_each(|render| {
+        let Some(image) = decode(render)? else {
+            return Ok(());
+        };
+        render.metrics.similarity = Some(raster::similarity(&reference, &image));
+        if let (Some((dir, palette)), false) = (page_diffs, render.font == reference_font) {
+            diff::write_page_diffs(
+                dir,
+                &render.font,
+                palette,
+                [&reference, &image],
+                [&reference_pages, diff::page_areas(render)],
+            )?;
+        }
+        Ok(())
+    })
+}
+
+/// Decodes the image of a render, or returns `None` for vector pages.
+pub(crate) fn decode(render: &Render) -> Result<Option<Pixmap>> {
+    let Output::Image(image) = &render.output else {
+        return Ok(None);
+    };
+    let bytes = image
+        .data
+        .bytes()
+        .map_err(|err| eyre!("failed to read render: {err}"))?;
+    let pixmap = Pixmap::decode_png(&bytes).wrap_err_with(|| {
+        format!(
+            "failed to decode render of font {} {:?}",
+            render.font.family, render.font.variant
+        )
+    })?;
+    Ok(Some(pixmap))
+}
+
+/// Writes a row with the metrics of each variant.
+pub(crate) fn write_csv(variants: &[Render], path: &Path) -> Result<()> {
+    let mut csv = String::from(
+        "family,style,weight,stretch,pages,width,height,coverage_percent,missing_chars,compile_seconds,similarity\n",
+    );
+    for render in variants {
+        let variant = render.font.variant;
+        let metrics = &render.metrics;
+        let (width, height) = match &render.output {
+            Output::Image(image) => (image.width.to_string(), image.height.to_string()),
+            Output::Pages(_) => (String::new(), String::new()),
+        };
+        writeln!(
+            csv,
+            "{family},{style},{weight},{stretch},{pages},{width},{height},{coverage:.2},{missing},{duration:.3},{similarity}",
+            family = field(&render.font.family),
+            style = style_name(variant.style),
+            weight = variant.weight.to_number(),
+            stretch = variant.stretch.to_ratio().get(),
+            pages = metrics.pages,
+            coverage = metrics.coverage.ratio() * 100.0,
+            missing = field(&metrics.coverage.missing_chars.iter().collect::<String>()),
+            duration = metrics.duration.as_secs_f64(),
+            similarity = metrics
+                .similarity
+                .map_or(String::new(), |similarity| format!("{similarity:.4}")),
+        )?;
+    }
+    std::fs::write(path, csv)
+        .wrap_err_with(|| format!("failed to write metrics to {}", path.display()))
+}
+
+/// Quotes a CSV field if necessary.
+fn field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
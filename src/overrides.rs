@@ -0,0 +1,67 @@
+//! Extra styles for some fonts, e.g. a larger size for fonts that appear small.
+//!
+//! They are read from a TOML file with an `[[override]]` table for each pattern:
+//!
+//! ```toml
+//! [[override]]
+//! family = "^EB Garamond"
+//! styles = "#set text(size: 1.1em)"
+//! ```
+
+use std::{fs, path::Path};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use regex::Regex;
+
+/// The styles to apply to the fonts whose family matches a pattern, in the order of the file.
+#[derive(Default)]
+pub(crate) struct Overrides(Vec<(Regex, String)>);
+
+impl Overrides {
+    /// Reads the overrides from a TOML file.
+    pub(crate) fn load(path: &Path) -> Result<Overrides> {
+        let table: toml::Table = fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read overrides file {}", path.display()))?
+            .parse()
+            .wrap_err_with(|| format!("failed to parse overrides file {}", path.display()))?;
+        let invalid = |message: &str| eyre!("invalid overrides file {}: {message}", path.display());
+
+        let mut overrides = Vec::new();
+        for (key, value) in table {
+            let (Some(entries), "override") = (value.as_array(), key.as_str()) else {
+                return Err(invalid(&format!("unknown key `{key}`")));
+            };
+            for entry in entries {
+                let entry = entry
+                    .as_table()
+                    .ok_or_else(|| invalid("each override must be a table"))?;
+                if let Some(key) = entry
+                    .keys()
+                    .find(|key| !["family", "styles"].contains(&key.as_str()))
+                {
+                    return Err(invalid(&format!("unknown key `{key}` in override")));
+                }
+                let get = |key: &str| {
+                    entry
+                        .get(key)
+                        .and_then(|value| value.as_str())
+                        .ok_or_else(|| invalid(&format!("each override needs a `{key}` string")))
+                };
+                let family = get("family")?;
+                let regex = Regex::new(family)
+                    .wrap_err_with(|| format!("failed to compile override pattern `{family}`"))?;
+                overrides.push((regex, get("styles")?.to_owned()));
+            }
+        }
+        Ok(Overrides(overrides))
+    }
+
+    /// The styles of all overrides whose pattern matches the family, one per line.
+    pub(crate) fn styles(&self, family: &str) -> String {
+        self.0
+            .iter()
+            .filter(|(regex, _)| regex.is_match(family))
+            .map(|(_, styles)| format!("{styles}\n"))
+            .collect()
+    }
+}
@@ -0,0 +1,61 @@
+//! Expansion of user-provided paths.
+
+use std::path::PathBuf;
+
+use color_eyre::eyre::{eyre, Result};
+
+/// Expands a leading `~` to the home directory and `$VAR` or `${VAR}` to the value of an
+/// environment variable.
+pub(crate) fn expand(path: &str) -> Result<PathBuf> {
+    let mut expanded = String::new();
+    let mut rest = path;
+    if let Some(after) = rest.strip_prefix('~') {
+        if after.is_empty() || after.starts_with(std::path::is_separator) {
+            let home = dirs::home_dir().ok_or_else(|| eyre!("failed to find home directory"))?;
+            expanded.push_str(&home.to_string_lossy());
+            rest = after;
+        }
+    }
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, remainder) = match after.strip_prefix('{') {
+            Some(braced) => braced
+                .split_once('}')
+                .ok_or_else(|| eyre!("unclosed `${{` in path `{path}`"))?,
+            None => {
+                let end = after
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(after.len());
+                after.split_at(end)
+            }
+        };
+        if name.is_empty() {
+            // A lone `$` is kept as is.
+            expanded.push('$');
+        } else {
+            let value = std::env::var(name)
+                .map_err(|_| eyre!("environment variable `{name}` in path `{path}` is not set"))?;
+            expanded.push_str(&value);
+        }
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+    Ok(PathBuf::from(expanded))
+}
+
+/// Splits comma-separated paths and expands each of them.
+pub(crate) fn expand_list(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    paths
+        .iter()
+        .flat_map(|path| match path.to_str() {
+            Some(path) => path
+                .split(',')
+                .filter(|path| !path.is_empty())
+                .map(expand)
+                .collect(),
+            None => vec![Ok(path.clone())],
+        })
+        .collect()
+}
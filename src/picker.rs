@@ -0,0 +1,312 @@
+//! The interactive picker of `--pick`, curating the matched fonts in the terminal.
+//!
+//! Typing filters the list by fuzzy search, like fzf: each word of the query has to appear
+//! in a font's name in order, but not necessarily consecutively.
+//! The terminal is driven directly in raw mode, which is only supported on Unix.
+
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    os::fd::AsRawFd,
+};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use typst::text::FontInfo;
+
+use crate::style_name;
+
+/// The number of fonts shown at once.
+const HEIGHT: usize = 15;
+
+/// A key pressed in the picker.
+enum Key {
+    Char(char),
+    Backspace,
+    Up,
+    Down,
+    /// Tab or Ctrl-Space, choosing the current font.
+    Toggle,
+    /// Ctrl-A, choosing all matching fonts.
+    ToggleAll,
+    Enter,
+    /// Escape or Ctrl-C.
+    Cancel,
+    Other,
+}
+
+/// Lets the user choose among the fonts, and returns the chosen ones in their original order.
+///
+/// If none were chosen explicitly, the font under the cursor is returned.
+pub(crate) fn pick(fonts: Vec<FontInfo>) -> Result<Vec<FontInfo>> {
+    let labels: Vec<String> = fonts.iter().map(label).collect();
+    let mut terminal = Terminal::open()?;
+    let mut query = String::new();
+    let mut matches: Vec<usize> = (0..fonts.len()).collect();
+    let mut chosen = vec![false; fonts.len()];
+    let mut cursor: usize = 0;
+    let mut offset = 0;
+    loop {
+        // Scroll just far enough to keep the cursor visible.
+        offset = offset.clamp(cursor.saturating_sub(HEIGHT - 1), cursor);
+        let mut lines = Vec::new();
+        for (row, &index) in matches.iter().enumerate().skip(offset).take(HEIGHT) {
+            lines.push(format!(
+                "{pointer} {check} {label}",
+                pointer = if row == cursor { '>' } else { ' ' },
+                check = if chosen[index] { "[x]" } else { "[ ]" },
+                label = labels[index],
+            ));
+        }
+        lines.push(format!(
+            "  {matched}/{total}, {count} chosen (Tab: choose, Ctrl-A: all, Enter: confirm, Esc: cancel)",
+            matched = matches.len(),
+            total = fonts.len(),
+            count = chosen.iter().filter(|&&chosen| chosen).count(),
+        ));
+        terminal.draw(&lines, &format!("> {query}"))?;
+
+        match terminal.key()? {
+            Key::Char(c) => query.push(c),
+            Key::Backspace => {
+                if query.pop().is_none() {
+                    continue;
+                }
+            }
+            Key::Up => {
+                cursor = cursor.saturating_sub(1);
+                continue;
+            }
+            Key::Down => {
+                cursor = (cursor + 1).min(matches.len().saturating_sub(1));
+                continue;
+            }
+            Key::Toggle => {
+                if let Some(&index) = matches.get(cursor) {
+                    chosen[index] = !chosen[index];
+                    cursor = (cursor + 1).min(matches.len() - 1);
+                }
+                continue;
+            }
+            Key::ToggleAll => {
+                let all = matches.iter().all(|&index| chosen[index]);
+                for &index in &matches {
+                    chosen[index] = !all;
+                }
+                continue;
+            }
+            Key::Enter => break,
+            Key::Cancel => {
+                terminal.clear()?;
+                return Err(eyre!("picking the fonts was cancelled"));
+            }
+            Key::Other => continue,
+        }
+        // The query changed.
+        matches = search(&labels, &query);
+        cursor = 0;
+        offset = 0;
+    }
+    terminal.clear()?;
+
+    if !chosen.contains(&true) {
+        match matches.get(cursor) {
+            Some(&index) => chosen[index] = true,
+            None => return Err(eyre!("no fonts were picked")),
+        }
+    }
+    Ok(fonts
+        .into_iter()
+        .zip(chosen)
+        .filter_map(|(font, chosen)| chosen.then_some(font))
+        .collect())
+}
+
+/// The name of a font in the list, like `Noto Sans  italic 700 100%`.
+fn label(font: &FontInfo) -> String {
+    format!(
+        "{family}  {style} {weight} {stretch}%",
+        family = font.family,
+        style = style_name(font.variant.style),
+        weight = font.variant.weight.to_number(),
+        stretch = font.variant.stretch.to_ratio().get() * 100.0,
+    )
+}
+
+/// The indices of the labels matching the query, best matches first.
+fn search(labels: &[String], query: &str) -> Vec<usize> {
+    let mut scored: Vec<(usize, usize)> = labels
+        .iter()
+        .enumerate()
+        .filter_map(|(index, label)| {
+            let label = label.to_lowercase();
+            let score = query
+                .to_lowercase()
+                .split_whitespace()
+                .map(|word| gaps(&label, word))
+                .sum::<Option<usize>>()?;
+            Some((score, index))
+        })
+        .collect();
+    scored.sort();
+    scored.into_iter().map(|(_, index)| index).collect()
+}
+
+/// The number of characters skipped between the first and the last character of the word
+/// when finding it in the text in order, or `None` if it doesn't appear.
+fn gaps(text: &str, word: &str) -> Option<usize> {
+    let mut chars = text.chars().enumerate();
+    let mut start = None;
+    let mut end = 0;
+    for c in word.chars() {
+        let (position, _) = chars.find(|&(_, t)| t == c)?;
+        start.get_or_insert(position);
+        end = position;
+    }
+    Some(start.map_or(0, |start| end - start + 1 - word.chars().count()))
+}
+
+/// The controlling terminal in raw mode, restored when dropped.
+///
+/// It is used directly, so that the picker works while stdout is redirected.
+struct Terminal {
+    tty: File,
+    original: libc::termios,
+    /// The number of lines drawn above the prompt.
+    drawn: usize,
+}
+
+impl Terminal {
+    fn open() -> Result<Terminal> {
+        let tty = File::options()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .wrap_err("--pick needs a terminal")?;
+        let fd = tty.as_raw_fd();
+        let mut original = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+            return Err(io::Error::last_os_error()).wrap_err("--pick needs a terminal");
+        }
+        // Read each key as it is pressed, without echoing it or interpreting Ctrl-C.
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(fd, libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error()).wrap_err("failed to set up the terminal");
+        }
+        Ok(Terminal {
+            tty,
+            original,
+            drawn: 0,
+        })
+    }
+
+    /// Replaces what was drawn before with the lines and the prompt below them.
+    fn draw(&mut self, lines: &[String], prompt: &str) -> Result<()> {
+        let width = self.width();
+        let mut out = self.rewind();
+        for line in lines {
+            out.extend(line.chars().take(width));
+            out.push_str("\r\n");
+        }
+        out.push_str(prompt);
+        self.drawn = lines.len();
+        self.tty.write_all(out.as_bytes())?;
+        Ok(())
+    }
+
+    /// Removes what was drawn.
+    fn clear(&mut self) -> Result<()> {
+        let out = self.rewind();
+        self.drawn = 0;
+        self.tty.write_all(out.as_bytes())?;
+        Ok(())
+    }
+
+    /// The escape codes moving to the first drawn line and clearing everything below.
+    fn rewind(&self) -> String {
+        match self.drawn {
+            0 => "\r\x1b[J".to_owned(),
+            drawn => format!("\r\x1b[{drawn}A\x1b[J"),
+        }
+    }
+
+    /// The number of columns, or 80 if it is unknown.
+    fn width(&self) -> usize {
+        let mut size = unsafe { std::mem::zeroed::<libc::winsize>() };
+        let ok = unsafe { libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, &mut size) } == 0;
+        if ok && size.ws_col > 0 {
+            usize::from(size.ws_col)
+        } else {
+            80
+        }
+    }
+
+    /// Waits for the next key.
+    fn key(&mut self) -> Result<Key> {
+        let key = match self.byte()? {
+            b'\r' | b'\n' => Key::Enter,
+            b'\t' | 0 => Key::Toggle,
+            0x01 => Key::ToggleAll,
+            0x03 => Key::Cancel,
+            0x08 | 0x7f => Key::Backspace,
+            0x0e => Key::Down,
+            0x10 => Key::Up,
+            0x1b => {
+                // A lone escape is a key of its own, otherwise it starts a sequence.
+                if !self.pending()? {
+                    return Ok(Key::Cancel);
+                }
+                match (self.byte()?, self.byte()?) {
+                    (b'[' | b'O', b'A') => Key::Up,
+                    (b'[' | b'O', b'B') => Key::Down,
+                    _ => Key::Other,
+                }
+            }
+            byte if byte < 0x20 => Key::Other,
+            byte => {
+                // Collect the continuation bytes of a multi-byte character.
+                let len = byte.leading_ones().max(1) as usize;
+                let mut bytes = vec![byte];
+                for _ in 1..len {
+                    bytes.push(self.byte()?);
+                }
+                match std::str::from_utf8(&bytes)
+                    .ok()
+                    .and_then(|s| s.chars().next())
+                {
+                    Some(c) => Key::Char(c),
+                    None => Key::Other,
+                }
+            }
+        };
+        Ok(key)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let mut byte = [0];
+        self.tty.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    /// Whether another byte arrives shortly, as part of an escape sequence.
+    fn pending(&self) -> Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: self.tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ready = unsafe { libc::poll(&mut fd, 1, 50) };
+        if ready < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(ready > 0)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSAFLUSH, &self.original) };
+    }
+}
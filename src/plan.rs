@@ -0,0 +1,201 @@
+//! The plan of a comparison: what it would render and write, and how large its outputs
+//! would be.
+//!
+//! The number of pages of the document is only known after compiling it. `--dry-run` compiles
+//! nothing, so unless `--pages` selects a fixed number of them, it estimates the sizes for a
+//! single A4 page per font.
+
+use std::collections::BTreeSet;
+
+use color_eyre::eyre::{bail, Result};
+use typst::text::FontInfo;
+
+use crate::{
+    compile_document, format_size,
+    logging::{Event, Level},
+    output_path, select_pages, specimen_settings, style_name,
+    world::SystemWorld,
+    Args, Format,
+};
+
+/// The width and height of Typst's default page, A4, in points.
+const PAGE: (f64, f64) = (595.28, 841.89);
+/// The typical width and height of a specimen in points, about 16cm by 8cm.
+const SPECIMEN: (f64, f64) = (453.54, 226.77);
+/// The typical size of a rasterized page of text as a PNG, in bytes per pixel.
+const BYTES_PER_PIXEL: f64 = 0.12;
+/// The typical size of a vector page of text in the PDF, including the subset of its font.
+const VECTOR_PAGE_BYTES: f64 = 40_000.0;
+/// The estimated size of the outputs above which a warning is printed without
+/// `--max-output-size`.
+const LARGE_OUTPUT: u64 = 1 << 30;
+
+/// Estimates the size of the outputs before rendering, failing if it exceeds
+/// `--max-output-size` and warning if it is very large otherwise.
+///
+/// With `--max-output-size`, the pages are counted by compiling the document once without
+/// the fonts' styles, unless `--pages` selects a fixed number of them. Otherwise, that
+/// compilation is skipped and a single page is assumed, so that only sizes which are large
+/// regardless of the document are warned about.
+pub(crate) fn check_size(world: &SystemWorld, args: &Args, fonts: &[FontInfo]) -> Result<()> {
+    let pages = page_count(args).unwrap_or_else(|| {
+        if args.max_output_size.is_none() {
+            return 1;
+        }
+        compile_document(world)
+            .and_then(|(document, _)| select_pages(document.pages, &args.pages))
+            .map_or(1, |pages| pages.len())
+    });
+    let size: usize = output_sizes(args, fonts.len(), pages)
+        .into_iter()
+        .map(|(_, size)| size)
+        .sum();
+    match args.max_output_size {
+        Some(max) if size as u64 > max => bail!(
+            "the outputs are estimated to be {} large, more than the --max-output-size of {}",
+            format_size(size),
+            format_size(max as usize)
+        ),
+        None if size as u64 > LARGE_OUTPUT => Event::new(
+            Level::Warn,
+            "large-output",
+            format!(
+                "the outputs are estimated to be {} large, consider fewer fonts, pages or a lower --ppi",
+                format_size(size)
+            ),
+        )
+        .field("estimate", size)
+        .emit(),
+        _ => Event::new(
+            Level::Debug,
+            "output-estimate",
+            format!("the outputs are estimated to be {} large", format_size(size)),
+        )
+        .field("estimate", size)
+        .emit(),
+    }
+    Ok(())
+}
+
+/// Prints the fonts in the order they would be rendered, the images that implies, and the
+/// outputs with their estimated size.
+pub(crate) fn print(args: &Args, fonts: &[FontInfo]) {
+    let (kinds, _) = specimen_settings(args);
+    let pages = page_count(args);
+    // With `--compare-justify`, each page is followed by its ragged-right version.
+    let copies = if args.compare_justify { 2 } else { 1 };
+
+    println!("Would render {} fonts in this order:", fonts.len());
+    for (n, font) in fonts.iter().enumerate() {
+        let variant = font.variant;
+        println!(
+            "{n:>4}\t{family}\t{style}\t{weight}\t{stretch}%",
+            n = n + 1,
+            family = font.family,
+            style = style_name(variant.style),
+            weight = variant.weight.to_number(),
+            stretch = variant.stretch.to_ratio().get() * 100.0,
+        );
+    }
+
+    let rendered = match pages {
+        Some(1) => "1 page".to_owned(),
+        Some(pages) => format!("{pages} pages"),
+        None => "all pages".to_owned(),
+    };
+    let justified = if args.compare_justify {
+        ", justified and ragged-right,"
+    } else {
+        ""
+    };
+    println!("Each font renders {rendered} of the document{justified} into one image.");
+    if !kinds.is_empty() {
+        let names: Vec<_> = kinds.iter().map(|kind| kind.title()).collect();
+        println!(
+            "Each font also renders up to {} specimens: {}.",
+            kinds.len(),
+            names.join(", ")
+        );
+    }
+    let page_total = match pages {
+        Some(pages) => format!(" with {} pages in total", fonts.len() * pages * copies),
+        None => String::new(),
+    };
+    println!(
+        "That makes {} images of pages{page_total} and up to {} images of specimens.",
+        fonts.len(),
+        fonts.len() * kinds.len(),
+    );
+
+    println!("Outputs:");
+    for (format, size) in output_sizes(args, fonts.len(), pages.unwrap_or(1)) {
+        println!(
+            "    {}\tabout {}",
+            output_path(args, format).display(),
+            format_size(size)
+        );
+        if format == Format::Pdf && args.split_by_family {
+            let families: BTreeSet<_> = fonts.iter().map(|font| &font.family).collect();
+            println!(
+                "    with a PDF for each of the {} families in the variants directory",
+                families.len()
+            );
+        }
+    }
+    if pages.is_none() {
+        println!(
+            "The sizes assume a single A4 page per font, as the length of the document is only known after compiling it."
+        );
+    }
+}
+
+/// The number of pages selected with `--pages`, or `None` if it depends on the document.
+fn page_count(args: &Args) -> Option<usize> {
+    if args.pages.is_empty() {
+        return None;
+    }
+    let mut pages = BTreeSet::new();
+    for range in &args.pages {
+        pages.extend(range.start..=range.end?);
+    }
+    Some(pages.len())
+}
+
+/// The estimated size in bytes of each output format, for fonts with this many pages selected.
+fn output_sizes(args: &Args, fonts: usize, pages: usize) -> Vec<(Format, usize)> {
+    let (kinds, _) = specimen_settings(args);
+    let copies = if args.compare_justify { 2 } else { 1 };
+    let renders = renders_size(args, fonts, pages * copies, kinds.len());
+    let mut sizes: Vec<(Format, usize)> = Vec::new();
+    for &format in &args.format {
+        if sizes.iter().any(|&(other, _)| other == format) {
+            continue;
+        }
+        // The archive contains the collection besides the individual renders.
+        let size = match format {
+            Format::Zip => 2 * renders,
+            _ => renders,
+        };
+        sizes.push((format, size));
+    }
+    sizes
+}
+
+/// The estimated size in bytes of the renders of the fonts, each with this many pages and
+/// specimens.
+fn renders_size(args: &Args, fonts: usize, pages: usize, specimens: usize) -> usize {
+    let pixels = |(width, height): (f64, f64)| {
+        let pixel_per_pt = f64::from(args.ppi) / 72.0;
+        width * pixel_per_pt * height * pixel_per_pt
+    };
+    let page = match (args.vector, args.crop) {
+        (true, _) => VECTOR_PAGE_BYTES,
+        (false, Some(region)) => {
+            pixels((region.size.x.to_pt(), region.size.y.to_pt())) * BYTES_PER_PIXEL
+        }
+        (false, None) => pixels(PAGE) * BYTES_PER_PIXEL,
+    };
+    let specimen = pixels(SPECIMEN) * BYTES_PER_PIXEL;
+    let font = pages as f64 * page + specimens as f64 * specimen;
+    (fonts as f64 * font) as usize
+}
@@ -0,0 +1,156 @@
+//! The `--post-render` command, run on each rendered image before it is embedded, e.g. to
+//! optimize or annotate it.
+//!
+//! The command is run by the shell with `{png}` replaced by the path of the image, which it
+//! may change in place without changing its size, as the pages' positions in the image are
+//! kept. The font is passed in the environment variables
+//! `TYPST_FONT_COMPARE_FAMILY` and `TYPST_FONT_COMPARE_VARIANT`, and whether the image shows
+//! the document or a specimen in `TYPST_FONT_COMPARE_IMAGE`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use color_eyre::eyre::{bail, eyre, Context, Result};
+use tiny_skia::Pixmap;
+use typst::{foundations::Bytes, text::FontInfo};
+
+use crate::{cache, specimen::SpecimenKind, world::FileData, Image, Output};
+
+/// The placeholder replaced by the path of the image.
+const PLACEHOLDER: &str = "{png}";
+
+pub(crate) struct PostRender {
+    command: String,
+    dir: PathBuf,
+    written: AtomicUsize,
+}
+
+impl PostRender {
+    pub(crate) fn new(command: String) -> PostRender {
+        PostRender {
+            command,
+            dir: cache::run_dir().join("post-render"),
+            written: AtomicUsize::new(0),
+        }
+    }
+
+    /// Runs the command on the rendered document and specimens of a font.
+    pub(crate) fn process(
+        &self,
+        font: &FontInfo,
+        output: Output,
+        specimens: Vec<(SpecimenKind, Image)>,
+    ) -> Result<(Output, Vec<(SpecimenKind, Image)>)> {
+        let output = match output {
+            Output::Image(image) => Output::Image(self.run(image, font, "document")?),
+            // Vector pages aren't images.
+            pages => pages,
+        };
+        let specimens = specimens
+            .into_iter()
+            .map(|(kind, image)| {
+                let name = format!("{kind:?}").to_lowercase();
+                Ok((kind, self.run(image, font, &name)?))
+            })
+            .collect::<Result<_>>()?;
+        Ok((output, specimens))
+    }
+
+    /// Runs the command on an image of the font, returning the image as the command left it.
+    ///
+    /// `kind` is `document` or the name of a specimen.
+    fn run(&self, image: Image, font: &FontInfo, kind: &str) -> Result<Image> {
+        fs::create_dir_all(&self.dir).wrap_err("failed to create post-render directory")?;
+        let n = self.written.fetch_add(1, Ordering::SeqCst);
+        let path = self.dir.join(format!("{n}.png"));
+        let bytes = image
+            .data
+            .bytes()
+            .map_err(|err| eyre!("failed to read render: {err}"))?;
+        fs::write(&path, bytes.as_slice())
+            .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+        let processed = self.command(&path, font, kind).and_then(|()| {
+            fs::read(&path).wrap_err_with(|| format!("failed to read {}", path.display()))
+        });
+        let _ = fs::remove_file(&path);
+        let processed = processed?;
+        let pixmap = Pixmap::decode_png(&processed)
+            .wrap_err("the --post-render command didn't leave a valid PNG")?;
+        if (pixmap.width(), pixmap.height()) != (image.width, image.height) {
+            bail!(
+                "the --post-render command changed the size of the image from {}x{} to {}x{}",
+                image.width,
+                image.height,
+                pixmap.width(),
+                pixmap.height()
+            );
+        }
+        Ok(Image {
+            data: FileData::Bytes(Bytes::from(processed)),
+            ..image
+        })
+    }
+
+    /// Runs the command on the image at the path, failing with its error output if it fails.
+    fn command(&self, path: &Path, font: &FontInfo, kind: &str) -> Result<()> {
+        let command = self.command.replace(PLACEHOLDER, &quote(path));
+        let mut shell = if cfg!(windows) {
+            let mut shell = Command::new("cmd");
+            shell.arg("/C");
+            shell
+        } else {
+            let mut shell = Command::new("sh");
+            shell.arg("-c");
+            shell
+        };
+        let output = shell
+            .arg(&command)
+            .env("TYPST_FONT_COMPARE_FAMILY", &font.family)
+            .env("TYPST_FONT_COMPARE_VARIANT", format!("{:?}", font.variant))
+            .env("TYPST_FONT_COMPARE_IMAGE", kind)
+            .output()
+            .wrap_err_with(|| format!("failed to run --post-render command `{command}`"))?;
+        if !output.status.success() {
+            bail!(
+                "--post-render command `{command}` failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PostRender {
+    fn drop(&mut self) {
+        if self.written.load(Ordering::SeqCst) > 0 {
+            let _ = fs::remove_dir_all(&self.dir);
+            // Only succeeds if the run keeps no other files.
+            let _ = fs::remove_dir(cache::run_dir());
+        }
+    }
+}
+
+/// Checks that a command contains the placeholder of the image.
+pub(crate) fn parse_command(command: &str) -> Result<String, String> {
+    if !command.contains(PLACEHOLDER) {
+        return Err(format!(
+            "the command must contain {PLACEHOLDER}, which is replaced by the path of the image"
+        ));
+    }
+    Ok(command.to_owned())
+}
+
+/// Quotes a path for the shell.
+fn quote(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        format!("\"{path}\"")
+    } else {
+        format!("'{}'", path.replace('\'', r"'\''"))
+    }
+}
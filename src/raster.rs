@@ -0,0 +1,448 @@
+//! Processing of rendered pixel buffers.
+
+use clap::ValueEnum;
+use color_eyre::eyre::Result;
+use tiny_skia::{Color, IntRect, Pixmap, PixmapPaint, PremultipliedColorU8, Transform};
+
+/// Shrinks a pixmap by an integer factor, averaging each block of `factor`×`factor` pixels.
+///
+/// Partial blocks at the right and bottom edges are averaged over the pixels they contain.
+pub(crate) fn downsample(pixmap: Pixmap, factor: u32) -> Pixmap {
+    if factor <= 1 {
+        return pixmap;
+    }
+
+    let width = pixmap.width().div_ceil(factor);
+    let height = pixmap.height().div_ceil(factor);
+    let mut output = Pixmap::new(width, height).expect("downsampled pixmap must not be empty");
+    let source = pixmap.data();
+    let target = output.data_mut();
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+            for sy in y * factor..((y + 1) * factor).min(pixmap.height()) {
+                for sx in x * factor..((x + 1) * factor).min(pixmap.width()) {
+                    let offset = 4 * (sy * pixmap.width() + sx) as usize;
+                    for (sum, &channel) in sum.iter_mut().zip(&source[offset..offset + 4]) {
+                        *sum += u32::from(channel);
+                    }
+                    count += 1;
+                }
+            }
+            // Channels are premultiplied, so averaging them independently is correct.
+            let offset = 4 * (y * width + x) as usize;
+            for (target, sum) in target[offset..offset + 4].iter_mut().zip(sum) {
+                *target = ((sum + count / 2) / count) as u8;
+            }
+        }
+    }
+    output
+}
+
+/// Stacks rendered pages below each other, surrounded and separated by black padding of the
+/// given width, like Typst's merged renders.
+///
+/// Also returns the area of the image that each page covers.
+pub(crate) fn stack(pages: &[Pixmap], padding: u32) -> (Pixmap, Vec<IntRect>) {
+    let width = 2 * padding + pages.iter().map(Pixmap::width).max().unwrap_or_default();
+    let height = padding
+        + pages
+            .iter()
+            .map(|page| page.height() + padding)
+            .sum::<u32>();
+    let mut output = Pixmap::new(width, height).expect("stacked pixmap must not be empty");
+    output.fill(Color::BLACK);
+    let mut areas = Vec::with_capacity(pages.len());
+    let mut y = padding;
+    for page in pages {
+        output.draw_pixmap(
+            padding as i32,
+            y as i32,
+            page.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+        areas.extend(IntRect::from_xywh(
+            padding as i32,
+            y as i32,
+            page.width(),
+            page.height(),
+        ));
+        y += page.height() + padding;
+    }
+    (output, areas)
+}
+
+/// Cuts an area out of a pixmap, or returns an empty pixmap if there is none or it is outside.
+pub(crate) fn crop(pixmap: &Pixmap, area: Option<IntRect>) -> Pixmap {
+    area.and_then(|area| pixmap.clone_rect(area))
+        .unwrap_or_else(|| Pixmap::new(1, 1).expect("pixmap of one pixel must be valid"))
+}
+
+/// Finds the content within an area of a pixmap, i.e. the pixels that differ from the area's
+/// top left pixel, returning their bounds relative to the area.
+///
+/// Returns `None` if the area is of a single color.
+pub(crate) fn content(pixmap: &Pixmap, area: IntRect) -> Option<IntRect> {
+    let background = pixmap.pixel(area.x() as u32, area.y() as u32)?;
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for y in 0..area.height() {
+        for x in 0..area.width() {
+            let pixel = pixmap.pixel(area.x() as u32 + x, area.y() as u32 + y);
+            if pixel.map_or(true, |pixel| pixel == background) {
+                continue;
+            }
+            let (left, top, right, bottom) = bounds.get_or_insert((x, y, x, y));
+            *left = (*left).min(x);
+            *top = (*top).min(y);
+            *right = (*right).max(x);
+            *bottom = (*bottom).max(y);
+        }
+    }
+    let (left, top, right, bottom) = bounds?;
+    IntRect::from_ltrb(left as i32, top as i32, right as i32 + 1, bottom as i32 + 1)
+}
+
+/// Shrinks an area of a pixmap, like [`downsample`] shrinks the pixmap.
+///
+/// Leaves out the pixels at the edges that are only partly inside the area.
+pub(crate) fn downsample_area(area: IntRect, factor: u32) -> IntRect {
+    let (x, y) = (
+        (area.x() as u32).div_ceil(factor),
+        (area.y() as u32).div_ceil(factor),
+    );
+    let right = ((area.x() as u32 + area.width()) / factor).max(x + 1);
+    let bottom = ((area.y() as u32 + area.height()) / factor).max(y + 1);
+    IntRect::from_ltrb(x as i32, y as i32, right as i32, bottom as i32)
+        .expect("downsampled area must not be empty")
+}
+
+/// Places two pixmaps next to each other, aligned at their top edge, so that they overlap by
+/// a number of pixels, e.g. to share the padding between them.
+///
+/// The area that neither covers is black, like the padding between rendered pages.
+pub(crate) fn beside(left: &Pixmap, right: &Pixmap, overlap: u32) -> Pixmap {
+    let width = left.width() + right.width().saturating_sub(overlap);
+    let height = left.height().max(right.height());
+    let mut output = Pixmap::new(width, height).expect("combined pixmap must not be empty");
+    output.fill(Color::BLACK);
+    for (x, pixmap) in [(0, left), (left.width().saturating_sub(overlap), right)] {
+        output.draw_pixmap(
+            x as i32,
+            0,
+            pixmap.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+    }
+    output
+}
+
+/// Measures how similar two renders are, from 0 for entirely different to 1 for identical ink.
+///
+/// The renders are aligned at their top left corner and compared by the amount of ink,
+/// i.e. darkness, of each pixel, so that the white background doesn't inflate the score.
+/// Pixels that are opaque black in both renders are skipped, so that neither does the
+/// padding around the pages.
+pub(crate) fn similarity(a: &Pixmap, b: &Pixmap) -> f64 {
+    let black = PremultipliedColorU8::from_rgba(0, 0, 0, 255);
+    let (mut difference, mut total) = (0.0, 0.0);
+    for y in 0..a.height().max(b.height()) {
+        for x in 0..a.width().max(b.width()) {
+            let (a, b) = (a.pixel(x, y), b.pixel(x, y));
+            if a == b && a == black {
+                continue;
+            }
+            let (a, b) = (ink(a), ink(b));
+            difference += (a - b).abs();
+            total += a + b;
+        }
+    }
+    if total == 0.0 {
+        return 1.0;
+    }
+    1.0 - difference / total
+}
+
+/// The side length of the blocks the structural similarity is measured in, in pixels.
+const SSIM_BLOCK: u32 = 8;
+
+/// How two renders are compared, each resulting in a similarity from 0 for entirely different
+/// to 1 for identical.
+///
+/// The renders are aligned at their top left corner. Areas that are empty in both, i.e. the
+/// white background or the padding around the pages, are skipped, so that they don't inflate
+/// the score.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Metric {
+    /// The amount of ink that differs, relative to the ink of both renders, like the
+    /// similarity to the baseline.
+    Ink,
+    /// The fraction of pixels that are exactly the same.
+    Exact,
+    /// The fraction of pixels of which no channel differs by more than the tolerance,
+    /// out of 255.
+    Tolerance,
+    /// The structural similarity (SSIM) of the brightness in blocks of 8×8 pixels, which is
+    /// less sensitive to differences of anti-aliasing than comparing single pixels.
+    Ssim,
+    /// The fraction of pixels whose perceptual color difference, the CIE76 ΔE, is at most the
+    /// tolerance.
+    DeltaE,
+}
+
+impl Metric {
+    /// The tolerance of a single pixel if none is given, for the metrics that have one.
+    ///
+    /// A ΔE of 2.3 is about the smallest noticeable difference.
+    pub(crate) fn default_tolerance(self) -> f64 {
+        match self {
+            Metric::DeltaE => 2.3,
+            _ => 16.0,
+        }
+    }
+
+    /// Measures how similar two renders are, allowing each pixel to differ by the tolerance
+    /// for the `tolerance` and `delta-e` metrics.
+    pub(crate) fn similarity(self, a: &Pixmap, b: &Pixmap, tolerance: f64) -> f64 {
+        match self {
+            Metric::Ink => similarity(a, b),
+            Metric::Exact => matching_pixels(a, b, |a, b| a == b),
+            Metric::Tolerance => matching_pixels(a, b, |a, b| {
+                a.iter().zip(b).all(|(a, b)| (a - b).abs() <= tolerance)
+            }),
+            Metric::Ssim => ssim(a, b),
+            Metric::DeltaE => matching_pixels(a, b, |a, b| {
+                let (a, b) = (lab(a), lab(b));
+                let distance: f64 = a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum();
+                distance.sqrt() <= tolerance
+            }),
+        }
+    }
+}
+
+/// The fraction of the pixels that aren't empty in both renders for which the colors,
+/// composited onto white, match.
+fn matching_pixels(a: &Pixmap, b: &Pixmap, matches: impl Fn([f64; 3], [f64; 3]) -> bool) -> f64 {
+    let (mut matching, mut total) = (0usize, 0usize);
+    for y in 0..a.height().max(b.height()) {
+        for x in 0..a.width().max(b.width()) {
+            let (a, b) = (a.pixel(x, y), b.pixel(x, y));
+            if is_empty(a) && a == b {
+                continue;
+            }
+            total += 1;
+            if matches(rgb(a), rgb(b)) {
+                matching += 1;
+            }
+        }
+    }
+    if total == 0 {
+        return 1.0;
+    }
+    matching as f64 / total as f64
+}
+
+/// The mean structural similarity of the blocks that aren't empty in both renders.
+fn ssim(a: &Pixmap, b: &Pixmap) -> f64 {
+    // The constants of the original definition, for a dynamic range of 255.
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let (width, height) = (a.width().max(b.width()), a.height().max(b.height()));
+    let (mut sum, mut blocks) = (0.0, 0usize);
+    for by in (0..height).step_by(SSIM_BLOCK as usize) {
+        for bx in (0..width).step_by(SSIM_BLOCK as usize) {
+            let pixels: Vec<_> = (by..(by + SSIM_BLOCK).min(height))
+                .flat_map(|y| (bx..(bx + SSIM_BLOCK).min(width)).map(move |x| (x, y)))
+                .map(|(x, y)| (a.pixel(x, y), b.pixel(x, y)))
+                .collect();
+            if pixels.iter().all(|&(a, b)| is_empty(a) && is_empty(b)) {
+                continue;
+            }
+
+            let n = pixels.len() as f64;
+            let brightness: Vec<(f64, f64)> = pixels
+                .into_iter()
+                .map(|(a, b)| (255.0 - ink(a), 255.0 - ink(b)))
+                .collect();
+            let mean_a = brightness.iter().map(|(a, _)| a).sum::<f64>() / n;
+            let mean_b = brightness.iter().map(|(_, b)| b).sum::<f64>() / n;
+            let (mut var_a, mut var_b, mut covariance) = (0.0, 0.0, 0.0);
+            for (a, b) in &brightness {
+                var_a += (a - mean_a).powi(2) / n;
+                var_b += (b - mean_b).powi(2) / n;
+                covariance += (a - mean_a) * (b - mean_b) / n;
+            }
+            sum += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a.powi(2) + mean_b.powi(2) + C1) * (var_a + var_b + C2));
+            blocks += 1;
+        }
+    }
+    if blocks == 0 {
+        return 1.0;
+    }
+    sum / blocks as f64
+}
+
+/// Whether a pixel is part of the white background or of the black padding between pages.
+fn is_empty(pixel: Option<PremultipliedColorU8>) -> bool {
+    let Some(pixel) = pixel else {
+        return true;
+    };
+    pixel.alpha() == 0
+        || pixel == PremultipliedColorU8::from_rgba(255, 255, 255, 255).expect("white is valid")
+        || pixel == PremultipliedColorU8::from_rgba(0, 0, 0, 255).expect("black is valid")
+}
+
+/// The channels of a pixel composited onto white, from 0 to 255.
+///
+/// Pixels outside of a render are part of the white background.
+fn rgb(pixel: Option<PremultipliedColorU8>) -> [f64; 3] {
+    let Some(pixel) = pixel else {
+        return [255.0; 3];
+    };
+    let white = 255.0 - f64::from(pixel.alpha());
+    [pixel.red(), pixel.green(), pixel.blue()].map(|channel| f64::from(channel) + white)
+}
+
+/// Converts an sRGB color with channels from 0 to 255 to CIELAB, under the D65 illuminant.
+fn lab(rgb: [f64; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(|channel| {
+        let channel = channel / 255.0;
+        if channel <= 0.04045 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    // Relative to the white point of D65.
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let [x, y, z] = [x, y, z].map(|t| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    });
+    [116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z)]
+}
+
+/// The maximum width of the thumbnails in overviews, in pixels.
+pub(crate) const THUMBNAIL_WIDTH: u32 = 480;
+
+/// The colors of the ink only in the first and only in the second render in images of their
+/// differences.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Palette {
+    /// Red for the first render and cyan for the second.
+    RedCyan,
+    /// Blue for the first render and orange for the second, which stay distinguishable with
+    /// the common kinds of color blindness.
+    BlueOrange,
+}
+
+impl Palette {
+    /// The colors of ink only in the first and only in the second render.
+    fn colors(self) -> [[u8; 3]; 2] {
+        match self {
+            Palette::RedCyan => [[255, 0, 0], [0, 255, 255]],
+            // From the palette of Okabe and Ito.
+            Palette::BlueOrange => [[0, 114, 178], [230, 159, 0]],
+        }
+    }
+
+    /// The names and hexadecimal codes of the colors, for legends.
+    pub(crate) fn legend(self) -> [(&'static str, String); 2] {
+        let [first, second] = self.colors();
+        let hex = |[r, g, b]: [u8; 3]| format!("#{r:02x}{g:02x}{b:02x}");
+        match self {
+            Palette::RedCyan => [("red", hex(first)), ("cyan", hex(second))],
+            Palette::BlueOrange => [("blue", hex(first)), ("orange", hex(second))],
+        }
+    }
+}
+
+/// Overlays two renders, showing ink only in the first or only in the second in the colors of
+/// the palette and ink in both in black.
+///
+/// The renders are aligned at their top left corner, like for [`similarity`].
+pub(crate) fn diff(a: &Pixmap, b: &Pixmap, palette: Palette) -> Pixmap {
+    overlay(a, b, palette, true)
+}
+
+/// Like [`diff`], but leaves out the ink that both renders share, so that only their
+/// differences remain.
+pub(crate) fn differences(a: &Pixmap, b: &Pixmap, palette: Palette) -> Pixmap {
+    overlay(a, b, palette, false)
+}
+
+fn overlay(a: &Pixmap, b: &Pixmap, palette: Palette, shared: bool) -> Pixmap {
+    let (width, height) = (a.width().max(b.width()), a.height().max(b.height()));
+    let mut output = Pixmap::new(width, height).expect("diff pixmap must not be empty");
+    let target = output.pixels_mut();
+    let [first, second] = palette.colors();
+    for y in 0..height {
+        for x in 0..width {
+            let (a, b) = (ink(a.pixel(x, y)) / 255.0, ink(b.pixel(x, y)) / 255.0);
+            let common = a.min(b);
+            let (a, b, common) = (a - common, b - common, if shared { common } else { 0.0 });
+            let [r, g, b] = [0, 1, 2].map(|channel| {
+                let absorbed = 255.0 * common
+                    + a * (255.0 - f64::from(first[channel]))
+                    + b * (255.0 - f64::from(second[channel]));
+                (255.0 - absorbed).clamp(0.0, 255.0).round() as u8
+            });
+            target[(y * width + x) as usize] =
+                PremultipliedColorU8::from_rgba(r, g, b, 255).expect("opaque color must be valid");
+        }
+    }
+    output
+}
+
+/// The darkness of a pixel composited onto white, from 0 to 255.
+///
+/// Pixels outside of a render are part of the white background.
+fn ink(pixel: Option<PremultipliedColorU8>) -> f64 {
+    let Some(pixel) = pixel else {
+        return 0.0;
+    };
+    // Composite the premultiplied color onto white before measuring its luminance.
+    let white = 255.0 - f64::from(pixel.alpha());
+    let luminance = 0.2126 * (f64::from(pixel.red()) + white)
+        + 0.7152 * (f64::from(pixel.green()) + white)
+        + 0.0722 * (f64::from(pixel.blue()) + white);
+    255.0 - luminance
+}
+
+/// Shrinks an encoded PNG to at most about the given width, returning the PNG and its size.
+pub(crate) fn thumbnail(png: &[u8], width: u32) -> Result<(Vec<u8>, u32, u32)> {
+    let pixmap = Pixmap::decode_png(png)?;
+    let factor = pixmap.width().div_ceil(width);
+    let thumbnail = downsample(pixmap, factor);
+    Ok((
+        thumbnail.encode_png()?,
+        thumbnail.width(),
+        thumbnail.height(),
+    ))
+}
+
+/// Recolors an encoded PNG for a theme, so that white becomes the paper color and black the
+/// ink color, blending the colors in between channel by channel.
+pub(crate) fn tint(png: &[u8], paper: [u8; 3], ink: [u8; 3]) -> Result<Vec<u8>> {
+    let mut pixmap = Pixmap::decode_png(png)?;
+    for pixel in pixmap.pixels_mut() {
+        let [r, g, b] = rgb(Some(*pixel));
+        let [r, g, b] = [(r, 0), (g, 1), (b, 2)].map(|(channel, n)| {
+            let (paper, ink) = (f64::from(paper[n]), f64::from(ink[n]));
+            (ink + (paper - ink) * channel / 255.0).round() as u8
+        });
+        *pixel = PremultipliedColorU8::from_rgba(r, g, b, 255).expect("opaque color must be valid");
+    }
+    Ok(pixmap.encode_png()?)
+}
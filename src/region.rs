@@ -0,0 +1,75 @@
+//! Rectangular regions of a page, e.g. to crop the renders or zoom into their details.
+
+use std::str::FromStr;
+
+use typst::{
+    layout::{Abs, Frame, Point, Size},
+    visualize::Path,
+};
+
+/// A rectangle on a page, given by its top left corner and its size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Region {
+    pub(crate) origin: Point,
+    pub(crate) size: Size,
+}
+
+impl Region {
+    /// Returns the region moved by an offset.
+    pub(crate) fn translate(self, offset: Point) -> Region {
+        Region {
+            origin: self.origin + offset,
+            ..self
+        }
+    }
+
+    /// Cuts the region out of a frame, as a frame of the region's size.
+    pub(crate) fn crop(&self, frame: &Frame) -> Frame {
+        let mut cropped = Frame::hard(self.size);
+        cropped.push_frame(-self.origin, frame.clone());
+        cropped.clip(Path::rect(self.size));
+        cropped
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    /// Parses `x,y,width,height` with lengths like `2cm`, `15mm`, `1in` or `40pt`.
+    /// Lengths without a unit are in points.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lengths = s
+            .split(',')
+            .map(parse_length)
+            .collect::<Result<Vec<_>, _>>()?;
+        let [x, y, width, height] = lengths[..] else {
+            return Err(format!("region `{s}` must be given as `x,y,width,height`"));
+        };
+        if width <= Abs::zero() || height <= Abs::zero() {
+            return Err(format!("region `{s}` must not be empty"));
+        }
+        Ok(Region {
+            origin: Point::new(x, y),
+            size: Size::new(width, height),
+        })
+    }
+}
+
+/// Parses an absolute length like `2cm`, defaulting to points without a unit.
+pub(crate) fn parse_length(s: &str) -> Result<Abs, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.' && c != '-')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid length `{s}`"))?;
+    match unit.trim() {
+        "" | "pt" => Ok(Abs::pt(number)),
+        "mm" => Ok(Abs::mm(number)),
+        "cm" => Ok(Abs::cm(number)),
+        "in" => Ok(Abs::inches(number)),
+        unit => Err(format!("unknown length unit `{unit}`")),
+    }
+}
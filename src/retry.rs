@@ -0,0 +1,395 @@
+//! The renders kept for `--retry-failed`, so that the fonts rendered again can be added to the
+//! ones that compiled in the previous run, and the outputs written again with all of them.
+//!
+//! With `--diagnostics`, the renders of a run in which fonts failed are kept in a directory
+//! next to the diagnostics file, like `diagnostics.renders` for `diagnostics.json`, together
+//! with a `manifest.json` of the settings they were rendered with and everything else the
+//! outputs need of each render.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use color_eyre::eyre::{eyre, Context, Result};
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use tiny_skia::IntRect;
+use typst::{
+    layout::Abs,
+    text::{FontBook, FontInfo},
+};
+
+use crate::{
+    diagnostics::Diagnostic,
+    logging::{Event, Level},
+    metrics::{Coverage, Metrics, Scaled},
+    specimen::SpecimenKind,
+    style_name, variant_file_name,
+    world::FileData,
+    Args, Image, Normalize, Output, Render,
+};
+
+/// The version of the manifest's schema, increased on incompatible changes.
+const MANIFEST_VERSION: u32 = 2;
+
+/// The name of the manifest in the directory of the renders.
+const MANIFEST: &str = "manifest.json";
+
+/// The directory the renders are kept in for a diagnostics file.
+pub(crate) fn dir(diagnostics: &Path) -> PathBuf {
+    diagnostics.with_extension("renders")
+}
+
+/// Keeps the renders for a later `--retry-failed`, replacing the ones kept before.
+///
+/// Renders restored from the directory stay where they are. Vector pages can't be kept, so
+/// the manifest kept before is removed instead when the run rendered them.
+pub(crate) fn keep(diagnostics: &Path, args: &Args, variants: &[Render]) -> Result<()> {
+    let dir = dir(diagnostics);
+    if variants
+        .iter()
+        .any(|render| matches!(render.output, Output::Pages(_)))
+    {
+        return remove_manifest(&dir);
+    }
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("failed to create directory {}", dir.display()))?;
+
+    let write = |name: String, image: &Image| -> Result<Value> {
+        let path = dir.join(&name);
+        if !matches!(&image.data, FileData::Path(kept) if *kept == path) {
+            let png = image
+                .data
+                .bytes()
+                .map_err(|err| eyre!("failed to read render: {err}"))?;
+            fs::write(&path, png.as_slice())
+                .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+        }
+        Ok(json!({
+            "file": name,
+            "width": image.width,
+            "height": image.height,
+            "pages": image
+                .pages
+                .iter()
+                .map(|area| [area.x(), area.y(), area.width() as i32, area.height() as i32])
+                .collect::<Vec<_>>(),
+        }))
+    };
+    let entries: Vec<Value> = variants
+        .par_iter()
+        .filter_map(|render| match &render.output {
+            Output::Image(image) => Some((render, image)),
+            Output::Pages(_) => None,
+        })
+        .map(|(render, image)| {
+            let stem = variant_file_name(&render.font);
+            let specimens = render
+                .specimens
+                .iter()
+                .map(|(kind, image)| {
+                    let mut entry = write(format!("{stem}-{}.png", kind.name()), image)?;
+                    entry["kind"] = json!(kind.name());
+                    Ok(entry)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let metrics = &render.metrics;
+            let coverage = &metrics.coverage;
+            let variant = render.font.variant;
+            let mut entry = write(format!("{stem}.png"), image)?;
+            entry["family"] = json!(render.font.family);
+            entry["style"] = json!(style_name(variant.style));
+            entry["weight"] = json!(variant.weight.to_number());
+            entry["stretch"] = json!(variant.stretch.to_ratio().get());
+            entry["specimens"] = json!(specimens);
+            entry["warnings"] = render.warnings.iter().map(Diagnostic::to_json).collect();
+            entry["page_count"] = json!(metrics.pages);
+            entry["duration"] = json!(metrics.duration.as_secs_f64());
+            entry["coverage"] = json!({
+                "chars": coverage.chars,
+                "missing": coverage.missing,
+                "missing_chars": coverage.missing_chars.iter().collect::<String>(),
+                "text_chars": coverage.text_chars.iter().collect::<String>(),
+                "languages": coverage.languages,
+            });
+            if let Some(scaled) = &metrics.scaled {
+                let height = scaled.height.to_possible_value();
+                entry["scaled"] = json!({
+                    "factor": scaled.factor,
+                    "height": height.as_ref().map(|height| height.get_name()),
+                    "baseline": scaled.baseline,
+                    "size": scaled.size.map(Abs::to_pt),
+                });
+            }
+            Ok(entry)
+        })
+        .collect::<Result<_>>()?;
+
+    let manifest = json!({
+        "version": MANIFEST_VERSION,
+        "settings": settings(args),
+        "renders": entries,
+    });
+    let path = dir.join(MANIFEST);
+    fs::write(&path, serde_json::to_string_pretty(&manifest)? + "\n")
+        .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+    // Remove the files of fonts that were rendered before but not in this run.
+    for file in fs::read_dir(&dir).into_iter().flatten().flatten() {
+        let path = file.path();
+        let listed = |entry: &Value| {
+            entry["file"].as_str() == path.file_name().and_then(|name| name.to_str())
+        };
+        let kept = path.file_name() == Some(MANIFEST.as_ref())
+            || manifest["renders"].as_array().is_some_and(|entries| {
+                entries.iter().any(|entry| {
+                    listed(entry)
+                        || entry["specimens"]
+                            .as_array()
+                            .is_some_and(|specimens| specimens.iter().any(listed))
+                })
+            });
+        if !kept {
+            let _ = fs::remove_file(&path);
+        }
+    }
+    Ok(())
+}
+
+/// Removes the renders kept before, once no font failed and none can be retried.
+///
+/// Only the files listed in the manifest are removed, and the directory if it is empty then.
+pub(crate) fn discard(diagnostics: &Path) -> Result<()> {
+    let dir = dir(diagnostics);
+    let Ok(manifest) = fs::read_to_string(dir.join(MANIFEST)) else {
+        return Ok(());
+    };
+    let manifest: Value = serde_json::from_str(&manifest).unwrap_or_default();
+    let renders = manifest["renders"].as_array().into_iter().flatten();
+    let specimens = renders
+        .clone()
+        .flat_map(|entry| entry["specimens"].as_array().into_iter().flatten());
+    for entry in renders.chain(specimens) {
+        if let Some(file) = entry["file"].as_str() {
+            let _ = fs::remove_file(dir.join(file));
+        }
+    }
+    remove_manifest(&dir)?;
+    let _ = fs::remove_dir(&dir);
+    Ok(())
+}
+
+/// Removes the manifest, so that the renders in the directory can't be restored anymore.
+fn remove_manifest(dir: &Path) -> Result<()> {
+    let path = dir.join(MANIFEST);
+    match fs::remove_file(&path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            Err(err).wrap_err_with(|| format!("failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The settings that the renders depend on, which a retry has to use as well for the renders
+/// to fit together.
+fn settings(args: &Args) -> Value {
+    let debug = |value: &dyn fmt::Debug| format!("{value:?}");
+    json!({
+        "input": args.input(),
+        "pages": debug(&args.pages),
+        "ppi": args.ppi,
+        "supersample": args.supersample,
+        "theme": debug(&args.theme),
+        "crop": debug(&args.crop),
+        "autocrop": args.autocrop,
+        "zoom": debug(&args.zoom),
+        "zoom-label": args.zoom_label,
+        "show-metrics": args.show_metrics,
+        "show-measurements": args.show_measurements,
+        "compare-justify": args.compare_justify,
+        "specimen": debug(&args.specimen),
+        "waterfall": args.waterfall,
+        "pangram": debug(&args.pangram),
+        "charmap": debug(&args.charmap),
+        "kerning": args.kerning,
+        "sample-lang": args.sample_lang,
+        "hyphenate-lang": debug(&args.hyphenate_lang),
+        "leading": debug(&args.leading),
+        "normalize": debug(&args.normalize),
+        "baseline": args.baseline,
+        "fallback": args.fallback,
+        "fallback-list": args.fallback_list,
+        "preamble": args.preamble,
+        "overrides": args.overrides,
+        "font-inputs": args.font_inputs,
+        "post-render": args.post_render,
+    })
+}
+
+/// Restores the renders kept by the run that wrote the diagnostics file.
+///
+/// The images stay on disk until they are needed. Fonts that are no longer installed are
+/// left out with a warning. Renders made with other settings than the arguments are refused,
+/// as they wouldn't fit together with the renders of the retry.
+pub(crate) fn restore(diagnostics: &Path, args: &Args, book: &FontBook) -> Result<Vec<Render>> {
+    let dir = dir(diagnostics);
+    let path = dir.join(MANIFEST);
+    let manifest = fs::read_to_string(&path).wrap_err_with(|| {
+        format!(
+            "failed to read the renders of the previous run from {}, which keeps them if fonts failed, unless it used --vector",
+            path.display()
+        )
+    })?;
+    let mut manifest: Value = serde_json::from_str(&manifest)
+        .wrap_err_with(|| format!("failed to parse {}", path.display()))?;
+    if manifest["version"] != MANIFEST_VERSION {
+        return Err(eyre!(
+            "the renders in {} were kept by an incompatible version",
+            dir.display()
+        ));
+    }
+    let settings = settings(args);
+    let changed: Vec<&str> = settings
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(key, value)| manifest["settings"][key.as_str()] != **value)
+        .map(|(key, _)| key.as_str())
+        .collect();
+    if !changed.is_empty() {
+        return Err(eyre!(
+            "the renders in {} were made with different settings for {}, so the fonts that failed can't be added to them; compare all fonts again instead",
+            dir.display(),
+            changed.join(", ")
+        ));
+    }
+    let Value::Array(entries) = manifest["renders"].take() else {
+        return Err(eyre!("{} lists no renders", path.display()));
+    };
+
+    let mut renders = Vec::new();
+    for entry in entries {
+        let Some(font) = find_font(book, &entry) else {
+            Event::new(
+                Level::Warn,
+                "retry-font-missing",
+                format!(
+                    "leaving out {} {} {}, which is no longer installed",
+                    entry["family"].as_str().unwrap_or_default(),
+                    entry["style"].as_str().unwrap_or_default(),
+                    entry["weight"]
+                ),
+            )
+            .emit();
+            continue;
+        };
+        let specimens = entry["specimens"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|specimen| {
+                let kind = specimen["kind"]
+                    .as_str()
+                    .and_then(|kind| SpecimenKind::from_str(kind, false).ok())
+                    .ok_or_else(|| eyre!("unknown specimen in {}", path.display()))?;
+                Ok((kind, image(&dir, specimen)?))
+            })
+            .collect::<Result<_>>()?;
+        let coverage = &entry["coverage"];
+        let chars = |key: &str| coverage[key].as_str().unwrap_or_default().chars().collect();
+        let scaled = &entry["scaled"];
+        let scaled = scaled["factor"].as_f64().and_then(|factor| {
+            Some(Scaled {
+                factor,
+                height: Normalize::from_str(scaled["height"].as_str()?, false).ok()?,
+                baseline: scaled["baseline"].as_str().map(str::to_owned),
+                size: scaled["size"].as_f64().map(Abs::pt),
+            })
+        });
+        renders.push(Render {
+            font,
+            output: Output::Image(image(&dir, &entry)?),
+            specimens,
+            warnings: entry["warnings"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Diagnostic::from_json)
+                .collect(),
+            metrics: Metrics {
+                pages: entry["page_count"].as_u64().unwrap_or_default() as usize,
+                duration: Duration::from_secs_f64(entry["duration"].as_f64().unwrap_or_default()),
+                coverage: Coverage {
+                    chars: coverage["chars"].as_u64().unwrap_or_default() as usize,
+                    missing: coverage["missing"].as_u64().unwrap_or_default() as usize,
+                    missing_chars: chars("missing_chars"),
+                    text_chars: chars("text_chars"),
+                    languages: serde_json::from_value(coverage["languages"].clone())
+                        .unwrap_or_default(),
+                },
+                similarity: None,
+                scaled,
+            },
+            aliases: Vec::new(),
+            similar: Vec::new(),
+        });
+    }
+    Event::new(
+        Level::Info,
+        "retry-restored",
+        format!(
+            "Restored {} renders of the previous run from {}",
+            renders.len(),
+            dir.display()
+        ),
+    )
+    .field("renders", renders.len())
+    .emit();
+    Ok(renders)
+}
+
+/// The installed font of a manifest entry.
+fn find_font(book: &FontBook, entry: &Value) -> Option<FontInfo> {
+    book.families()
+        .flat_map(|(_, fonts)| fonts)
+        .find(|font| {
+            let variant = font.variant;
+            entry["family"] == font.family.as_str()
+                && entry["style"] == style_name(variant.style)
+                && entry["weight"] == variant.weight.to_number()
+                && entry["stretch"].as_f64() == Some(variant.stretch.to_ratio().get())
+        })
+        .cloned()
+}
+
+/// The image of a manifest entry, which is read from the directory when it is needed.
+fn image(dir: &Path, entry: &Value) -> Result<Image> {
+    let file = entry["file"]
+        .as_str()
+        .ok_or_else(|| eyre!("a render in {} has no file", dir.display()))?;
+    let size = |key: &str| {
+        entry[key]
+            .as_u64()
+            .and_then(|size| u32::try_from(size).ok())
+    };
+    let (Some(width), Some(height)) = (size("width"), size("height")) else {
+        return Err(eyre!("the render {file} in {} has no size", dir.display()));
+    };
+    let pages = entry["pages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|area| {
+            let [x, y, width, height] = [0, 1, 2, 3].map(|i| area[i].as_i64());
+            IntRect::from_xywh(x? as i32, y? as i32, width? as u32, height? as u32)
+        })
+        .collect();
+    Ok(Image {
+        data: FileData::Path(dir.join(file)),
+        width,
+        height,
+        pages,
+    })
+}
@@ -0,0 +1,168 @@
+//! Sample paragraphs in many languages and pangrams in many scripts, to compare fonts for
+//! multilingual documents.
+
+use clap::ValueEnum;
+
+/// A sample paragraph in a language.
+pub(crate) struct Sample {
+    /// The ISO 639-1 code of the language, as used by `text.lang`.
+    pub(crate) lang: &'static str,
+    /// The English name of the language.
+    pub(crate) name: &'static str,
+    pub(crate) text: &'static str,
+}
+
+/// The first article of the Universal Declaration of Human Rights in each language.
+pub(crate) const SAMPLES: &[Sample] = &[
+    Sample {
+        lang: "en",
+        name: "English",
+        text: "All human beings are born free and equal in dignity and rights. They are endowed with reason and conscience and should act towards one another in a spirit of brotherhood.",
+    },
+    Sample {
+        lang: "de",
+        name: "German",
+        text: "Alle Menschen sind frei und gleich an Würde und Rechten geboren. Sie sind mit Vernunft und Gewissen begabt und sollen einander im Geist der Brüderlichkeit begegnen.",
+    },
+    Sample {
+        lang: "fr",
+        name: "French",
+        text: "Tous les êtres humains naissent libres et égaux en dignité et en droits. Ils sont doués de raison et de conscience et doivent agir les uns envers les autres dans un esprit de fraternité.",
+    },
+    Sample {
+        lang: "es",
+        name: "Spanish",
+        text: "Todos los seres humanos nacen libres e iguales en dignidad y derechos y, dotados como están de razón y conciencia, deben comportarse fraternalmente los unos con los otros.",
+    },
+    Sample {
+        lang: "it",
+        name: "Italian",
+        text: "Tutti gli esseri umani nascono liberi ed eguali in dignità e diritti. Essi sono dotati di ragione e di coscienza e devono agire gli uni verso gli altri in spirito di fratellanza.",
+    },
+    Sample {
+        lang: "pt",
+        name: "Portuguese",
+        text: "Todos os seres humanos nascem livres e iguais em dignidade e em direitos. Dotados de razão e de consciência, devem agir uns para com os outros em espírito de fraternidade.",
+    },
+    Sample {
+        lang: "nl",
+        name: "Dutch",
+        text: "Alle mensen worden vrij en gelijk in waardigheid en rechten geboren. Zij zijn begiftigd met verstand en geweten, en behoren zich jegens elkander in een geest van broederschap te gedragen.",
+    },
+    Sample {
+        lang: "pl",
+        name: "Polish",
+        text: "Wszyscy ludzie rodzą się wolni i równi pod względem swej godności i swych praw. Są oni obdarzeni rozumem i sumieniem i powinni postępować wobec innych w duchu braterstwa.",
+    },
+    Sample {
+        lang: "cs",
+        name: "Czech",
+        text: "Všichni lidé rodí se svobodní a sobě rovní co do důstojnosti a práv. Jsou nadáni rozumem a svědomím a mají spolu jednat v duchu bratrství.",
+    },
+    Sample {
+        lang: "tr",
+        name: "Turkish",
+        text: "Bütün insanlar hür, haysiyet ve haklar bakımından eşit doğarlar. Akıl ve vicdana sahiptirler ve birbirlerine karşı kardeşlik zihniyeti ile hareket etmelidirler.",
+    },
+    Sample {
+        lang: "vi",
+        name: "Vietnamese",
+        text: "Tất cả mọi người sinh ra đều được tự do và bình đẳng về nhân phẩm và quyền. Mọi con người đều được tạo hóa ban cho lý trí và lương tâm và cần phải đối xử với nhau trong tình bằng hữu.",
+    },
+    Sample {
+        lang: "ru",
+        name: "Russian",
+        text: "Все люди рождаются свободными и равными в своем достоинстве и правах. Они наделены разумом и совестью и должны поступать в отношении друг друга в духе братства.",
+    },
+    Sample {
+        lang: "uk",
+        name: "Ukrainian",
+        text: "Всі люди народжуються вільними і рівними у своїй гідності та правах. Вони наділені розумом і совістю і повинні діяти у відношенні один до одного в дусі братерства.",
+    },
+    Sample {
+        lang: "el",
+        name: "Greek",
+        text: "Όλοι οι άνθρωποι γεννιούνται ελεύθεροι και ίσοι στην αξιοπρέπεια και τα δικαιώματα. Είναι προικισμένοι με λογική και συνείδηση, και οφείλουν να συμπεριφέρονται μεταξύ τους με πνεύμα αδελφοσύνης.",
+    },
+    Sample {
+        lang: "hy",
+        name: "Armenian",
+        text: "Բոլոր մարդիկ ծնվում են ազատ ու հավասար իրենց արժանապատվությամբ ու իրավունքներով։ Նրանք ունեն բանականություն ու խիղճ և միմյանց պետք է եղբայրաբար վերաբերվեն։",
+    },
+    Sample {
+        lang: "ka",
+        name: "Georgian",
+        text: "ყველა ადამიანი იბადება თავისუფალი და თანასწორი თავისი ღირსებითა და უფლებებით. მათ მინიჭებული აქვთ გონება და სინდისი და ერთმანეთის მიმართ უნდა იქცეოდნენ ძმობის სულისკვეთებით.",
+    },
+    Sample {
+        lang: "ar",
+        name: "Arabic",
+        text: "يولد جميع الناس أحرارًا متساوين في الكرامة والحقوق. وقد وهبوا عقلاً وضميرًا وعليهم أن يعامل بعضهم بعضًا بروح الإخاء.",
+    },
+    Sample {
+        lang: "he",
+        name: "Hebrew",
+        text: "כל בני האדם נולדו בני חורין ושווים בערכם ובזכויותיהם. כולם חוננו בתבונה ובמצפון, לפיכך חובה עליהם לנהוג איש ברעהו ברוח של אחוה.",
+    },
+    Sample {
+        lang: "hi",
+        name: "Hindi",
+        text: "सभी मनुष्यों को गौरव और अधिकारों के मामले में जन्मजात स्वतन्त्रता और समानता प्राप्त है। उन्हें बुद्धि और अन्तरात्मा की देन प्राप्त है और परस्पर उन्हें भाईचारे के भाव से बर्ताव करना चाहिए।",
+    },
+    Sample {
+        lang: "th",
+        name: "Thai",
+        text: "มนุษย์ทั้งหลายเกิดมามีอิสระและเสมอภาคกันในเกียรติศักดิ์และสิทธิ ต่างมีเหตุผลและมโนธรรม และควรปฏิบัติต่อกันด้วยเจตนารมณ์แห่งภราดรภาพ",
+    },
+    Sample {
+        lang: "zh",
+        name: "Chinese",
+        text: "人人生而自由，在尊严和权利上一律平等。他们赋有理性和良心，并应以兄弟关系的精神相对待。",
+    },
+    Sample {
+        lang: "ja",
+        name: "Japanese",
+        text: "すべての人間は、生まれながらにして自由であり、かつ、尊厳と権利とについて平等である。人間は、理性と良心とを授けられており、互いに同胞の精神をもって行動しなければならない。",
+    },
+    Sample {
+        lang: "ko",
+        name: "Korean",
+        text: "모든 인간은 태어날 때부터 자유로우며 그 존엄과 권리에 있어 동등하다. 인간은 천부적으로 이성과 양심을 부여받았으며 서로 형제애의 정신으로 행동하여야 한다.",
+    },
+];
+
+/// Finds the sample of a language by its code, ignoring case.
+pub(crate) fn find(lang: &str) -> Option<&'static Sample> {
+    SAMPLES
+        .iter()
+        .find(|sample| sample.lang.eq_ignore_ascii_case(lang))
+}
+
+/// A script with a curated pangram.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Armenian,
+    Georgian,
+    Hebrew,
+    Arabic,
+}
+
+impl Script {
+    /// A line using all letters of the script.
+    ///
+    /// Scripts without a well-known pangram show their alphabet instead.
+    pub(crate) fn pangram(self) -> &'static str {
+        match self {
+            Script::Latin => "The quick brown fox jumps over the lazy dog",
+            Script::Cyrillic => "Съешь же ещё этих мягких французских булок, да выпей чаю",
+            Script::Greek => "Ξεσκεπάζω την ψυχοφθόρα βδελυγμία",
+            Script::Armenian => "աբգդեզէըթժիլխծկհձղճմյնշոչպջռսվտրցւփքօֆ",
+            Script::Georgian => "აბგდევზთიკლმნოპჟრსტუფქღყშჩცძწჭხჯჰ",
+            Script::Hebrew => "דג סקרן שט בים מאוכזב ולפתע מצא חברה",
+            Script::Arabic => "نص حكيم له سر قاطع وذو شأن عظيم مكتوب على ثوب أخضر ومغلف بجلد أزرق",
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! The `--sandbox` for untrusted documents, which may only read files within the project root
+//! and the package directories.
+//!
+//! Typst already keeps paths within the root, but a symbolic link in it may still point
+//! anywhere, so the paths are checked after resolving all links.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use color_eyre::eyre::{Context, Result};
+use typst::diag::{FileError, FileResult};
+
+use crate::logging::{Event, Level};
+
+pub(crate) struct Sandbox {
+    /// The directories files may be read from, with all links resolved.
+    allowed: Vec<PathBuf>,
+    /// The files that were read.
+    read: Mutex<BTreeSet<PathBuf>>,
+    /// The files outside the allowed directories that the document tried to read.
+    denied: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl Sandbox {
+    /// Creates a sandbox allowing the root and the package directories.
+    pub(crate) fn new(root: &Path) -> Result<Sandbox> {
+        let root = root
+            .canonicalize()
+            .wrap_err_with(|| format!("failed to resolve root {}", root.display()))?;
+        let packages = [dirs::data_dir(), dirs::cache_dir()]
+            .into_iter()
+            .flatten()
+            .filter_map(|dir| dir.join("typst").join("packages").canonicalize().ok());
+        Ok(Sandbox {
+            allowed: std::iter::once(root).chain(packages).collect(),
+            read: Mutex::new(BTreeSet::new()),
+            denied: Mutex::new(BTreeSet::new()),
+        })
+    }
+
+    /// Checks that a file may be read and records it, returning the path to read it from.
+    ///
+    /// The file has to be read from the resolved path, as the links could change in between.
+    pub(crate) fn check(&self, path: &Path) -> FileResult<PathBuf> {
+        // A file that doesn't exist can't be read anyway.
+        let Ok(resolved) = path.canonicalize() else {
+            return Ok(path.to_owned());
+        };
+        if self.allowed.iter().any(|dir| resolved.starts_with(dir)) {
+            self.read.lock().unwrap().insert(resolved.clone());
+            return Ok(resolved);
+        }
+        if self.denied.lock().unwrap().insert(resolved.clone()) {
+            Event::new(
+                Level::Warn,
+                "sandbox-denied",
+                format!(
+                    "denied reading {}, which is outside the root and the package directories",
+                    resolved.display()
+                ),
+            )
+            .field("path", resolved.display().to_string())
+            .emit();
+        }
+        Err(FileError::AccessDenied)
+    }
+
+    /// The files that were read, in order of their paths.
+    pub(crate) fn read(&self) -> Vec<PathBuf> {
+        self.read.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// The files that the document was denied to read, in order of their paths.
+    pub(crate) fn denied(&self) -> Vec<PathBuf> {
+        self.denied.lock().unwrap().iter().cloned().collect()
+    }
+}
@@ -0,0 +1,161 @@
+//! The `serve` subcommand, showing the HTML gallery on a local web server.
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::{Component, Path},
+    thread,
+    time::Duration,
+};
+
+use color_eyre::eyre::{Context, Result};
+
+use crate::{
+    cache, interrupt,
+    logging::{Event, Level},
+    open, write_comparison, Args, Format,
+};
+
+/// How often to check for an interrupt while waiting for requests.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Writes the HTML gallery into the run's cache directory and serves it until interrupted.
+///
+/// Takes the options of `compare`, of which the output and format are replaced.
+pub(crate) fn run(mut args: Args) -> Result<()> {
+    let dir = cache::run_dir().join("gallery");
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("failed to create directory {}", dir.display()))?;
+    args.format = vec![Format::Html];
+    args.output = Some(dir.join("index.html"));
+    let served = write_comparison(&args).and_then(|_| {
+        if args.dry_run {
+            return Ok(());
+        }
+        serve(&dir, &args)
+    });
+    // Also removes the directory of the run, unless it still contains other files.
+    let _ = fs::remove_dir_all(&dir);
+    let _ = fs::remove_dir(cache::run_dir());
+    served
+}
+
+/// Serves the files in the directory on a free local port until interrupted.
+fn serve(dir: &Path, args: &Args) -> Result<()> {
+    if interrupt::requested() {
+        return Ok(());
+    }
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).wrap_err("failed to listen")?;
+    listener.set_nonblocking(true)?;
+    let url = format!("http://{}/", listener.local_addr()?);
+    Event::new(
+        Level::Warn,
+        "serve",
+        format!("Serving the gallery at {url}, press Ctrl-C to stop"),
+    )
+    .field("url", url.as_str())
+    .emit();
+    if let Some(viewer) = &args.open {
+        open(&url, viewer.as_deref());
+    }
+
+    while !interrupt::requested() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(err) = respond(dir, stream) {
+                    Event::new(
+                        Level::Debug,
+                        "serve-failed",
+                        format!("failed to respond: {err}"),
+                    )
+                    .emit();
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(err) => return Err(err).wrap_err("failed to accept connection"),
+        }
+    }
+    Ok(())
+}
+
+/// Answers a single request with the requested file.
+fn respond(dir: &Path, stream: TcpStream) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // Skip the headers.
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or("/"));
+    let path = target.split(['?', '#']).next().unwrap_or("/");
+    let path = match path.trim_start_matches('/') {
+        "" => "index.html".to_owned(),
+        path => decode(path),
+    };
+    // Refuse to leave the directory.
+    let is_safe = Path::new(&path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    let file = (method == "GET" && is_safe)
+        .then(|| fs::read(dir.join(&path)).ok())
+        .flatten();
+
+    let mut stream = &stream;
+    match file {
+        Some(body) => {
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                content_type(&path),
+                body.len(),
+            )?;
+            stream.write_all(&body)?;
+        }
+        None => write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )?,
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+/// Decodes the percent-encoded bytes of a URL path.
+fn decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The media type of a file served from the gallery.
+fn content_type(path: &str) -> &'static str {
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("png") => "image/png",
+        Some("pdf") => "application/pdf",
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    }
+}
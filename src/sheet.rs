@@ -0,0 +1,182 @@
+//! The `specimen` subcommand, writing a classic type specimen of a single family without a
+//! user document.
+
+use std::{ffi::OsString, fmt::Write, path::PathBuf};
+
+use clap::{Parser, ValueEnum};
+use color_eyre::eyre::{eyre, Result};
+use typst::{eval::Tracer, foundations::Smart, text::Font, text::FontInfo, World};
+
+use crate::{
+    logging::{Event, Level},
+    paths,
+    samples::Script,
+    source_date,
+    specimen::{self, Settings, SpecimenKind},
+    style_name,
+    world::{FontSources, SystemWorld},
+};
+
+/// The sizes of the sample paragraphs, in points.
+const PARAGRAPHS: [u32; 3] = [9, 11, 14];
+
+/// Write a type specimen of a font family: its styles, character set, a waterfall,
+/// sample paragraphs and its OpenType features.
+#[derive(Parser)]
+#[clap(bin_name = "typst-font-compare specimen")]
+struct SpecimenArgs {
+    /// The font family to show.
+    family: String,
+    /// Path to the output file, `<family>.specimen.pdf` by default.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    /// The script whose pangram is shown in each style and in the waterfall.
+    #[clap(long, value_name = "SCRIPT", default_value = "latin")]
+    pangram: Script,
+    /// Adds additional directories to search for fonts in.
+    ///
+    /// Can be given multiple times or as a comma-separated list.
+    /// A leading `~` and environment variables like `$HOME` are expanded.
+    #[clap(
+        long = "font-path",
+        env = "TYPST_FONT_PATHS",
+        value_name = "DIR",
+        value_delimiter = if cfg!(windows) { ';' } else { ':' },
+    )]
+    font_paths: Vec<PathBuf>,
+    /// Only use the fonts from `--font-path` and Typst's embedded fonts, not the ones installed
+    /// on the system, e.g. for reproducible comparisons in CI.
+    #[clap(long)]
+    ignore_system_fonts: bool,
+    /// Don't use the fonts bundled with the Typst compiler, like Linux Libertine and New
+    /// Computer Modern, which `typst compile` uses even if they are not installed.
+    #[clap(long)]
+    ignore_embedded_fonts: bool,
+}
+
+/// Runs the subcommand with the arguments following `specimen`.
+pub(crate) fn run(args: impl IntoIterator<Item = OsString>) -> Result<()> {
+    let args = SpecimenArgs::parse_from(args);
+    let world = SystemWorld::without_input(
+        &paths::expand_list(&args.font_paths)?,
+        FontSources {
+            system: !args.ignore_system_fonts,
+            embedded: !args.ignore_embedded_fonts,
+        },
+    )?;
+    let (family, styles) = world
+        .book
+        .families()
+        .find(|(family, _)| family.eq_ignore_ascii_case(&args.family))
+        .map(|(family, styles)| (family.to_owned(), styles.cloned().collect::<Vec<_>>()))
+        .ok_or_else(|| eyre!("font family {} was not found", args.family))?;
+    let font = world
+        .book
+        .select(&family.to_lowercase(), Default::default())
+        .and_then(|index| world.font(index))
+        .ok_or_else(|| eyre!("failed to load font {family}"))?;
+
+    let world = world.with_main_source(source(&family, &styles, &font, args.pangram));
+    let mut tracer = Tracer::new();
+    let document = typst::compile(&world, &mut tracer)
+        .map_err(|diag| eyre!("failed to compile specimen: {diag:?}"))?;
+    let output = args
+        .output
+        .unwrap_or_else(|| PathBuf::from(format!("{}.specimen.pdf", family.replace(' ', "-"))));
+    std::fs::write(
+        &output,
+        typst_pdf::pdf(&document, Smart::Auto, source_date::pdf_timestamp()),
+    )?;
+    Event::new(
+        Level::Info,
+        "specimen-written",
+        format!("Wrote specimen of {family} to {}", output.display()),
+    )
+    .field("family", family)
+    .field("path", output.display().to_string())
+    .emit();
+    Ok(())
+}
+
+/// Generates the Typst source of the specimen, showing the default style of the family.
+fn source(family: &str, styles: &[FontInfo], font: &Font, pangram: Script) -> String {
+    let mut source = String::new();
+    write!(
+        source,
+        r#"
+        #set document(title: {title}, author: "{pkg_name}")
+        #set page(margin: 2cm, footer: context align(center, text(size: 8pt, counter(page).display())))
+        #set text(font: {family}, size: 11pt)
+        #set par(justify: true)
+        #show heading: set block(above: 2em, below: 1em)
+        #let note(body) = text(size: 7pt, fill: luma(40%), body)
+
+        #text(size: 48pt, weight: "bold", {family})
+        #note[{styles} styles, {glyphs} glyphs]
+        "#,
+        title = specimen::string(&format!("{family} Specimen")),
+        pkg_name = env!("CARGO_PKG_NAME"),
+        family = specimen::string(family),
+        styles = styles.len(),
+        glyphs = font.ttf().number_of_glyphs(),
+    )
+    .unwrap();
+
+    source.push_str("\n= Styles\n#grid(columns: (auto, 1fr), gutter: 1em,\n");
+    for info in styles {
+        let variant = info.variant;
+        writeln!(
+            source,
+            r#"note[{style} {weight} {stretch}%], text(style: "{style}", weight: {weight}, stretch: {stretch}%, size: 16pt, {sample}),"#,
+            style = style_name(variant.style),
+            weight = variant.weight.to_number(),
+            stretch = variant.stretch.to_ratio().get() * 100.0,
+            sample = specimen::string(pangram.pangram()),
+        )
+        .unwrap();
+    }
+    source.push_str(")\n");
+
+    let settings = Settings {
+        waterfall: pangram.pangram().to_owned(),
+        charmap: vec![0..=char::MAX as u32],
+        ..Settings::default()
+    };
+    write!(
+        source,
+        "\n= Character Set\n{}\n",
+        SpecimenKind::Charmap
+            .body(font, &settings)
+            .unwrap_or_default()
+    )
+    .unwrap();
+
+    write!(
+        source,
+        "\n= Waterfall\n{}\n",
+        SpecimenKind::Waterfall
+            .body(font, &settings)
+            .unwrap_or_default()
+    )
+    .unwrap();
+
+    source.push_str("\n= Paragraphs\n");
+    for size in PARAGRAPHS {
+        writeln!(
+            source,
+            "#note[{size} pt]\n#text(size: {size}pt, lorem(80))\n"
+        )
+        .unwrap();
+    }
+
+    for kind in SpecimenKind::value_variants()
+        .iter()
+        // The sheet shows these with its own settings above.
+        .filter(|kind| !matches!(kind, SpecimenKind::Waterfall | SpecimenKind::Charmap))
+    {
+        if let Some(body) = kind.body(font, &settings) {
+            write!(source, "\n= {}\n{body}\n", kind.title()).unwrap();
+        }
+    }
+    source
+}
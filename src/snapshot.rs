@@ -0,0 +1,288 @@
+//! The `test` subcommand, comparing the renders of a document against stored reference images,
+//! so that updates of fonts or templates can be checked in CI.
+//!
+//! The reference images are kept in a directory together with a `manifest.json`, which lists
+//! the font and size of each reference and the resolution they were rendered at.
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::Parser;
+use color_eyre::eyre::{eyre, Context, Result};
+use serde_json::{json, Value};
+use tiny_skia::Pixmap;
+use typst::text::FontInfo;
+
+use crate::{
+    diff::{self, Comparison},
+    logging::{self, Event, Level},
+    metrics, parse_similarity, render_variants, selected_fonts, style_name, variant_file_name,
+    world::SystemWorld,
+    Args, Output, Render,
+};
+
+/// The version of the manifest's schema, increased on incompatible changes.
+const MANIFEST_VERSION: u32 = 1;
+
+/// The name of the manifest in the directory of the reference images.
+const MANIFEST: &str = "manifest.json";
+
+/// Render a document with each font and compare the renders against reference images.
+///
+/// Fails if a render differs from its reference by more than the threshold, or if it has no
+/// reference, and writes an image of the differences for each failing render.
+/// Accepts the options of `compare`, of which those selecting output formats and reports are
+/// ignored. The output is the directory of the difference images.
+#[derive(Parser)]
+#[clap(bin_name = "typst-font-compare test")]
+struct TestArgs {
+    #[clap(flatten)]
+    compare: Args,
+    /// The directory of the reference images [default: <INPUT>.snapshots]
+    #[clap(long, value_name = "DIR")]
+    references: Option<PathBuf>,
+    /// The similarity below which a render counts as different from its reference.
+    #[clap(long, value_name = "SIMILARITY", default_value = "0.999", value_parser = parse_similarity)]
+    threshold: f64,
+    #[clap(flatten)]
+    comparison: Comparison,
+    /// Replace the reference images with the current renders instead of comparing them,
+    /// after reviewing the differences.
+    ///
+    /// Creates the directory and updates its manifest. The references of fonts that aren't
+    /// rendered are kept.
+    #[clap(long)]
+    update_snapshots: bool,
+}
+
+/// Runs the subcommand with the arguments following `test`.
+pub(crate) fn run(args: impl IntoIterator<Item = OsString>) -> Result<()> {
+    let TestArgs {
+        compare: mut args,
+        references,
+        threshold,
+        comparison,
+        update_snapshots,
+    } = TestArgs::parse_from(args);
+    let verbosity = if args.quiet { -1 } else { args.verbose as i8 };
+    logging::init(verbosity, args.log_format);
+    args.fonts.expand_font_paths()?;
+    if args.vector {
+        return Err(eyre!("test needs rasterized renders, not --vector"));
+    }
+    args.specimen.clear();
+    args.waterfall = None;
+    let references = references.unwrap_or_else(|| args.input().with_extension("snapshots"));
+
+    let world = SystemWorld::new(&args)?;
+    let fonts = selected_fonts(&world, &args)?;
+    let (variants, skipped) =
+        render_variants(world, &args, fonts, None).wrap_err("while rendering variants")?;
+    if update_snapshots {
+        update(&args, &references, &variants)?;
+        if !skipped.is_empty() {
+            return Err(eyre!(
+                "{} fonts failed to render, their references were kept",
+                skipped.len()
+            ));
+        }
+        return Ok(());
+    }
+
+    if let Some(manifest) = read_manifest(&references)? {
+        let ppi = manifest.get("ppi").and_then(Value::as_f64);
+        if ppi.is_some_and(|ppi| ppi != f64::from(args.ppi)) {
+            Event::new(
+                Level::Warn,
+                "reference-ppi",
+                format!(
+                    "the references were rendered at {} ppi, not {} ppi",
+                    ppi.unwrap_or_default(),
+                    args.ppi
+                ),
+            )
+            .emit();
+        }
+    }
+    let diffs = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input().with_extension("diffs"));
+    let mut failed = skipped.len();
+    for render in &variants {
+        let name = reference_name(&render.font);
+        let image = metrics::decode(render)?.expect("renders of test are rasterized");
+        let path = references.join(&name);
+        let reference = match fs::read(&path) {
+            Ok(png) => Pixmap::decode_png(&png)
+                .wrap_err_with(|| format!("failed to decode reference {}", path.display()))?,
+            Err(err) => {
+                failed += 1;
+                Event::new(
+                    Level::Warn,
+                    "reference-missing",
+                    format!("no reference for {name}: {err}"),
+                )
+                .field("reference", path.display().to_string())
+                .emit();
+                continue;
+            }
+        };
+
+        let similarity = comparison.similarity(&reference, &image);
+        if similarity >= threshold {
+            Event::new(
+                Level::Info,
+                "test-passed",
+                format!("{name} matches its reference"),
+            )
+            .field("similarity", similarity)
+            .emit();
+            continue;
+        }
+        failed += 1;
+        fs::create_dir_all(&diffs)
+            .wrap_err_with(|| format!("failed to create directory {}", diffs.display()))?;
+        let diff = diffs.join(&name);
+        comparison
+            .image(&reference, &image, args.palette)
+            .save_png(&diff)
+            .wrap_err_with(|| format!("failed to write {}", diff.display()))?;
+        Event::new(
+            Level::Warn,
+            "test-failed",
+            format!(
+                "{name} differs from its reference with a similarity of {similarity:.4}, see {}",
+                diff.display()
+            ),
+        )
+        .field("similarity", similarity)
+        .field("diff", diff.display().to_string())
+        .emit();
+        if let Some(dir) = &args.page_diffs {
+            // The reference is laid out like the render, unless the pages changed in size.
+            let pages = diff::page_areas(render);
+            diff::write_page_diffs(
+                dir,
+                &render.font,
+                args.palette,
+                [&reference, &image],
+                [pages, pages],
+            )?;
+        }
+    }
+
+    let total = variants.len() + skipped.len();
+    println!(
+        "{} of {total} renders match their references",
+        total - failed
+    );
+    if failed > 0 {
+        return Err(eyre!("{failed} of {total} renders failed the test"));
+    }
+    Ok(())
+}
+
+/// Writes the renders as the new references and records them in the manifest.
+///
+/// References that are identical to their render are left untouched, so that their files
+/// don't change needlessly in version control.
+fn update(args: &Args, references: &Path, variants: &[Render]) -> Result<()> {
+    fs::create_dir_all(references)
+        .wrap_err_with(|| format!("failed to create directory {}", references.display()))?;
+    // A manifest that can't be read is replaced.
+    let mut entries: BTreeMap<String, Value> = read_manifest(references)
+        .ok()
+        .flatten()
+        .and_then(|mut manifest| manifest.get_mut("references").map(Value::take))
+        .and_then(|entries| match entries {
+            Value::Array(entries) => Some(entries),
+            _ => None,
+        })
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|entry| Some((entry.get("file")?.as_str()?.to_owned(), entry)))
+        .collect();
+
+    let (mut added, mut updated) = (0, 0);
+    for render in variants {
+        let Output::Image(image) = &render.output else {
+            unreachable!("renders of test are rasterized");
+        };
+        let png = image
+            .data
+            .bytes()
+            .map_err(|err| eyre!("failed to read render: {err}"))?;
+        let name = reference_name(&render.font);
+        let path = references.join(&name);
+        match fs::read(&path) {
+            Ok(old) if old == png.as_slice() => {}
+            old => {
+                fs::write(&path, &png)
+                    .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+                if old.is_ok() {
+                    updated += 1;
+                } else {
+                    added += 1;
+                }
+            }
+        }
+
+        let variant = render.font.variant;
+        entries.insert(
+            name.clone(),
+            json!({
+                "file": name,
+                "family": render.font.family,
+                "style": style_name(variant.style),
+                "weight": variant.weight.to_number(),
+                "stretch": variant.stretch.to_ratio().get(),
+                "width": image.width,
+                "height": image.height,
+            }),
+        );
+    }
+
+    let manifest = json!({
+        "version": MANIFEST_VERSION,
+        "input": args.input().file_name().map(|name| name.to_string_lossy()),
+        "ppi": args.ppi,
+        "references": entries.into_values().collect::<Vec<_>>(),
+    });
+    let path = references.join(MANIFEST);
+    fs::write(&path, serde_json::to_string_pretty(&manifest)? + "\n")
+        .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+    println!(
+        "{added} references added, {updated} updated and {} unchanged in {}",
+        variants.len() - added - updated,
+        references.display()
+    );
+    Ok(())
+}
+
+/// Reads the manifest of the reference images, or returns `None` if there is none yet.
+fn read_manifest(references: &Path) -> Result<Option<Value>> {
+    let path = references.join(MANIFEST);
+    let Ok(manifest) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let manifest: Value = serde_json::from_str(&manifest)
+        .wrap_err_with(|| format!("failed to parse {}", path.display()))?;
+    let version = manifest.get("version").and_then(Value::as_u64);
+    if version != Some(MANIFEST_VERSION.into()) {
+        return Err(eyre!(
+            "{} has an unsupported version, update the references with --update-snapshots",
+            path.display()
+        ));
+    }
+    Ok(Some(manifest))
+}
+
+/// The file name of a font's reference image, like `EB-Garamond-italic-400-1000.png`.
+fn reference_name(font: &FontInfo) -> String {
+    format!("{}.png", variant_file_name(font))
+}
@@ -0,0 +1,50 @@
+//! Support for `SOURCE_DATE_EPOCH`, which fixes the date of a build so that its outputs are
+//! reproducible, see <https://reproducible-builds.org/specs/source-date-epoch/>.
+//!
+//! It is used for `datetime.today()` and the creation date of the PDFs. Without it, the PDFs
+//! have no creation date.
+
+use std::sync::OnceLock;
+
+use time::OffsetDateTime;
+use typst::foundations::Datetime;
+
+use crate::logging::{Event, Level};
+
+/// The time set with `SOURCE_DATE_EPOCH`, if any.
+///
+/// An invalid value is ignored with a warning.
+pub(crate) fn get() -> Option<OffsetDateTime> {
+    static SOURCE_DATE: OnceLock<Option<OffsetDateTime>> = OnceLock::new();
+    *SOURCE_DATE.get_or_init(|| {
+        let value = std::env::var("SOURCE_DATE_EPOCH").ok()?;
+        let time = value
+            .trim()
+            .parse()
+            .ok()
+            .and_then(|seconds| OffsetDateTime::from_unix_timestamp(seconds).ok());
+        if time.is_none() {
+            Event::new(
+                Level::Warn,
+                "invalid-source-date-epoch",
+                format!("ignoring SOURCE_DATE_EPOCH={value:?}, which is not a number of seconds since 1970"),
+            )
+            .field("value", value)
+            .emit();
+        }
+        time
+    })
+}
+
+/// The creation date of the PDFs, if `SOURCE_DATE_EPOCH` is set.
+pub(crate) fn pdf_timestamp() -> Option<Datetime> {
+    let time = get()?;
+    Datetime::from_ymd_hms(
+        time.year(),
+        time.month().into(),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+    )
+}
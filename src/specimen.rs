@@ -0,0 +1,387 @@
+//! Typst sources for specimens that are rendered for each font in addition to the input document.
+
+use std::{collections::BTreeSet, fmt::Write, ops::RangeInclusive};
+
+use clap::ValueEnum;
+use typst::text::Font;
+
+use crate::{
+    features,
+    samples::{Sample, Script, SAMPLES},
+};
+
+/// The page setup shared by all specimens, sizing each page to its content.
+const PREAMBLE: &str = r#"
+#set page(width: auto, height: auto, margin: 0.5cm)
+#set par(leading: 0.8em)
+"#;
+
+/// The text shown for a feature when the characters it affects are unknown.
+const SAMPLE: &str = "The quick brown fox jumps over the lazy dog. 0123456789";
+
+/// The maximum number of affected characters shown for a single feature.
+const MAX_CHARS: usize = 48;
+
+/// The sizes of the waterfall in descending order, in points.
+const WATERFALL_SIZES: [u32; 10] = [72, 48, 36, 24, 18, 14, 12, 10, 9, 8];
+
+/// The maximum number of characters shown in the character map.
+const MAX_CHARMAP: usize = 4096;
+
+/// Pairs whose spacing commonly needs kerning.
+const KERNING_PAIRS: [&str; 24] = [
+    "AV", "AW", "AY", "AT", "Av", "Aw", "Ay", "LT", "LV", "LY", "PA", "Ta", "Te", "To", "Tr", "Ty",
+    "VA", "Va", "Vo", "WA", "Yo", "f.", "f)", "r.",
+];
+
+/// Settings of the specimens that depend on more than the font.
+#[derive(Clone)]
+pub(crate) struct Settings {
+    /// The line shown at each size of the waterfall.
+    pub(crate) waterfall: String,
+    /// The code points shown in the character map.
+    pub(crate) charmap: Vec<RangeInclusive<u32>>,
+    /// The pairs of characters whose kerning is shown.
+    pub(crate) kerning: Vec<String>,
+    /// The languages whose sample paragraphs are shown.
+    pub(crate) languages: Vec<&'static Sample>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            waterfall: Script::Latin.pangram().to_owned(),
+            charmap: Vec::new(),
+            kerning: KERNING_PAIRS.map(String::from).to_vec(),
+            languages: SAMPLES.iter().collect(),
+        }
+    }
+}
+
+/// The rows of 128 code points of the Unicode table that contain any of the characters.
+pub(crate) fn rows(chars: &BTreeSet<char>) -> Vec<RangeInclusive<u32>> {
+    let rows: BTreeSet<u32> = chars.iter().map(|&c| c as u32 / 128).collect();
+    rows.into_iter()
+        .map(|row| row * 128..=row * 128 + 127)
+        .collect()
+}
+
+/// A specimen that can additionally be rendered for each font.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum SpecimenKind {
+    /// Lining, old-style, tabular and proportional figures.
+    Numerals,
+    /// A sample line for each stylistic set (`ss01`–`ss20`).
+    StylisticSets,
+    /// The affected characters with each character variant (`cv01`–`cv99`) enabled.
+    CharacterVariants,
+    /// Swash (`swsh`), titling (`titl`) and stylistic (`salt`) alternates.
+    Alternates,
+    /// A line of text at a descending series of sizes, set with `--waterfall`.
+    Waterfall,
+    /// The glyphs for the characters of the document's script, or those set with `--charmap`,
+    /// labeled with their code points.
+    Charmap,
+    /// Pairs of characters with and without kerning, annotated with their advance and kerning,
+    /// set with `--kerning`.
+    Kerning,
+    /// A sample paragraph in each of the languages set with `--sample-lang`, or in those of
+    /// the document.
+    Languages,
+}
+
+impl SpecimenKind {
+    /// The title shown above the specimen in the collection.
+    pub(crate) fn title(self) -> &'static str {
+        match self {
+            SpecimenKind::Numerals => "Numerals",
+            SpecimenKind::StylisticSets => "Stylistic Sets",
+            SpecimenKind::CharacterVariants => "Character Variants",
+            SpecimenKind::Alternates => "Alternates",
+            SpecimenKind::Waterfall => "Waterfall",
+            SpecimenKind::Charmap => "Character Map",
+            SpecimenKind::Kerning => "Kerning",
+            SpecimenKind::Languages => "Languages",
+        }
+    }
+
+    /// The name of the specimen on the command line, e.g. `stylistic-sets`.
+    pub(crate) fn name(self) -> String {
+        self.to_possible_value()
+            .expect("specimen kinds are never skipped")
+            .get_name()
+            .to_owned()
+    }
+
+    /// Generates the Typst source of this specimen for a font.
+    ///
+    /// Returns `None` if the font provides none of the features this specimen showcases.
+    pub(crate) fn source(self, font: &Font, settings: &Settings) -> Option<String> {
+        let page = match self {
+            // Long lines are cut off instead of widening the page.
+            SpecimenKind::Waterfall => "#set page(width: 20cm)\n",
+            // Paragraphs wrap at a comfortable measure.
+            SpecimenKind::Languages => "#set page(width: 16cm)\n",
+            _ => "",
+        };
+        Some(format!("{PREAMBLE}{page}{}", self.body(font, settings)?))
+    }
+
+    /// Generates the content of this specimen without its page setup, e.g. to embed it
+    /// into a larger document.
+    pub(crate) fn body(self, font: &Font, settings: &Settings) -> Option<String> {
+        match self {
+            SpecimenKind::Numerals => numerals(font),
+            SpecimenKind::StylisticSets => {
+                features_table(font, (1..=20).map(|n| format!("ss{n:02}")))
+            }
+            SpecimenKind::CharacterVariants => {
+                features_table(font, (1..=99).map(|n| format!("cv{n:02}")))
+            }
+            SpecimenKind::Alternates => {
+                features_table(font, ["swsh", "titl", "salt"].map(String::from))
+            }
+            SpecimenKind::Waterfall => Some(waterfall(&settings.waterfall)),
+            SpecimenKind::Charmap => charmap(font, &settings.charmap),
+            SpecimenKind::Kerning => Some(kerning(&settings.kerning)),
+            SpecimenKind::Languages => Some(languages(&settings.languages)),
+        }
+    }
+}
+
+/// Shows each figure style the font has an OpenType feature for.
+fn numerals(font: &Font) -> Option<String> {
+    const STYLES: [(&str, &str, &str); 4] = [
+        ("lnum", "Lining", r#"number-type: "lining""#),
+        ("onum", "Old-style", r#"number-type: "old-style""#),
+        ("tnum", "Tabular", r#"number-width: "tabular""#),
+        ("pnum", "Proportional", r#"number-width: "proportional""#),
+    ];
+
+    let supported = features::supported(font);
+    let styles: Vec<_> = STYLES
+        .into_iter()
+        .filter(|(tag, ..)| supported.contains(&features::tag(tag)))
+        .collect();
+    if styles.is_empty() {
+        return None;
+    }
+
+    let mut source = String::new();
+    source.push_str(
+        r#"
+        #let sample = [0123456789 \ 1111111111 \ 3,141.59 · 2,718.28 · 1961–2024]
+        #grid(
+            columns: 2,
+            column-gutter: 1.5em,
+            row-gutter: 1em,
+            [Default], sample,
+        "#,
+    );
+    for (tag, label, settings) in styles {
+        writeln!(source, "[{label} (`{tag}`)], text({settings}, sample),").unwrap();
+    }
+    source.push(')');
+    Some(source)
+}
+
+/// Compares the affected characters with and without each of the given features enabled.
+///
+/// Features offering multiple alternates get a row for each of them.
+fn features_table(font: &Font, tags: impl IntoIterator<Item = String>) -> Option<String> {
+    let supported = features::supported(font);
+    let mut rows = String::new();
+    for tag in tags {
+        let feature = features::tag(&tag);
+        if !supported.contains(&feature) {
+            continue;
+        }
+
+        let name = features::ui_name(font, feature);
+        let chars = features::affected_chars(font, feature);
+        let sample = if chars.is_empty() {
+            SAMPLE.to_owned()
+        } else {
+            chars.into_iter().take(MAX_CHARS).collect()
+        };
+        let alternates = features::alternate_count(font, feature);
+        for value in 1..=alternates {
+            let mut label = match alternates {
+                1 => format!("raw({})", string(&tag)),
+                _ => format!("raw({})", string(&format!("{tag}={value}"))),
+            };
+            if let Some(name) = &name {
+                write!(label, " + h(0.5em) + {}", string(name)).unwrap();
+            }
+            writeln!(
+                rows,
+                "{label}, {sample}, text(features: ({tag}: {value}), {sample}),",
+                sample = string(&sample),
+                tag = string(&tag),
+            )
+            .unwrap();
+        }
+    }
+    if rows.is_empty() {
+        return None;
+    }
+
+    let mut source = String::new();
+    write!(
+        source,
+        r#"
+        #grid(
+            columns: 3,
+            column-gutter: 1.5em,
+            row-gutter: 1em,
+            [*Feature*], [*Default*], [*Enabled*],
+            {rows}
+        )
+        "#
+    )
+    .unwrap();
+    Some(source)
+}
+
+/// Shows the text on a single line at each size of the waterfall.
+fn waterfall(text: &str) -> String {
+    // Lines span the full height of the glyphs, so that clipping them keeps the descenders.
+    let mut source = String::from(
+        "#set text(top-edge: \"ascender\", bottom-edge: \"descender\")\n\
+         #grid(columns: (auto, 1fr), column-gutter: 1em, row-gutter: 0.5em, align: horizon,\n",
+    );
+    for size in WATERFALL_SIZES {
+        writeln!(
+            source,
+            "text(size: 0.6em, fill: luma(40%))[{size} pt], \
+             block(width: 100%, clip: true, box(width: 10 * 100%, text(size: {size}pt, hyphenate: false, {text}))),",
+            // Non-breaking spaces keep long lines from wrapping instead of being cut off.
+            text = string(&text.replace(' ', "\u{a0}")),
+        )
+        .unwrap();
+    }
+    source.push(')');
+    source
+}
+
+/// Shows the font's glyphs for the code points in the ranges, each labeled with its code point.
+fn charmap(font: &Font, ranges: &[RangeInclusive<u32>]) -> Option<String> {
+    let chars: Vec<char> = charset(font)
+        .into_iter()
+        .filter(|&c| ranges.iter().any(|range| range.contains(&(c as u32))))
+        .collect();
+    if chars.is_empty() {
+        return None;
+    }
+
+    let mut source = String::new();
+    if chars.len() > MAX_CHARMAP {
+        writeln!(
+            source,
+            "#text(size: 0.6em)[The first {MAX_CHARMAP} of {} characters.]",
+            chars.len()
+        )
+        .unwrap();
+    }
+    source.push_str(
+        "#grid(columns: (3em,) * 16, stroke: 0.25pt + luma(80%), inset: 0.3em, align: center,\n",
+    );
+    for c in chars.into_iter().take(MAX_CHARMAP) {
+        writeln!(
+            source,
+            "stack(spacing: 0.3em, text(size: 1.2em, {}), text(size: 0.5em, fill: luma(40%), raw(\"U+{:04X}\"))),",
+            string(&c.to_string()),
+            c as u32,
+        )
+        .unwrap();
+    }
+    source.push(')');
+    Some(source)
+}
+
+/// Shows each pair with and without kerning, measuring the advance of the kerned pair and the
+/// kerning between its characters in thousandths of an em.
+fn kerning(pairs: &[String]) -> String {
+    let mut source = String::from(
+        r#"
+        #set text(size: 48pt, ligatures: false)
+        #let units(length) = calc.round(length / 1pt / 48 * 1000)
+        #let advance(chars, kerning: true) = measure(text(kerning: kerning, chars)).width
+        #let pair(chars) = (
+            text(chars),
+            text(kerning: false, fill: luma(60%), chars),
+            context text(size: 10pt, str(units(advance(chars)))),
+            context text(size: 10pt, str(units(advance(chars) - advance(chars, kerning: false)))),
+        )
+        #grid(
+            columns: 4,
+            column-gutter: 1.5em,
+            row-gutter: 0.5em,
+            align: (left, left, right, right).map(align => align + horizon),
+            ..([Kerned], [Unkerned], [Advance], [Kerning]).map(text.with(size: 10pt, weight: "bold")),
+        "#,
+    );
+    for chars in pairs {
+        writeln!(source, "..pair({}),", string(chars)).unwrap();
+    }
+    source.push_str(")\n#text(size: 8pt)[Advance and kerning in thousandths of an em.]");
+    source
+}
+
+/// Shows the sample paragraph of each language, set in that language so that Typst applies its
+/// shaping, hyphenation and text direction.
+fn languages(samples: &[&Sample]) -> String {
+    let mut source = String::new();
+    for sample in samples {
+        writeln!(
+            source,
+            "#block(below: 1.2em)[#text(size: 0.6em, fill: luma(40%))[{name} (`{lang}`)] \\ \
+             #text(lang: {lang_string}, {text})]",
+            name = sample.name,
+            lang = sample.lang,
+            lang_string = string(sample.lang),
+            text = string(sample.text),
+        )
+        .unwrap();
+    }
+    source
+}
+
+/// The characters the font has glyphs for, excluding control and whitespace characters.
+pub(crate) fn charset(font: &Font) -> BTreeSet<char> {
+    let mut chars = BTreeSet::new();
+    for subtable in font
+        .ttf()
+        .tables()
+        .cmap
+        .into_iter()
+        .flat_map(|cmap| cmap.subtables)
+        .filter(|subtable| subtable.is_unicode())
+    {
+        subtable.codepoints(|codepoint| {
+            if let Some(c) = char::from_u32(codepoint) {
+                if !c.is_control() && !c.is_whitespace() {
+                    chars.insert(c);
+                }
+            }
+        });
+    }
+    chars
+}
+
+/// Quotes text as a Typst string literal.
+pub(crate) fn string(text: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c if c.is_control() => write!(quoted, "\\u{{{:x}}}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,93 @@
+//! The template of the collection, whose named slots like `{{variants}}` are filled with the
+//! settings, the renders and the pages around them.
+//!
+//! `--dump-template` prints the embedded template, and `--template` replaces it with a modified
+//! copy. Double braces that don't enclose a name like `{{name}}` are kept as they are.
+
+use std::{fs, ops::Range, path::Path};
+
+use color_eyre::eyre::{bail, Context, Result};
+
+/// The embedded template.
+pub(crate) const DEFAULT: &str = include_str!("collection.typ");
+
+/// The names of the slots a template may contain.
+pub(crate) const SLOTS: [&str; 21] = [
+    "margin",
+    "text-size",
+    "spacing",
+    "page-size",
+    "numbering",
+    "first-page",
+    "lang",
+    "background",
+    "foreground",
+    "accent",
+    "render",
+    "fonts",
+    "created-using",
+    "tool",
+    "version",
+    "homepage",
+    "partial",
+    "overview",
+    "ranking",
+    "variants",
+    "appendices",
+];
+
+/// Reads the template of `--template`, or returns the embedded one, checking its slots.
+pub(crate) fn load(path: Option<&Path>) -> Result<String> {
+    let Some(path) = path else {
+        return Ok(DEFAULT.to_owned());
+    };
+    let template = fs::read_to_string(path)
+        .wrap_err_with(|| format!("failed to read template {}", path.display()))?;
+    for (_, name) in slots(&template) {
+        if !SLOTS.contains(&name) {
+            bail!(
+                "unknown slot `{{{{{name}}}}}` in template {}, expected one of {}",
+                path.display(),
+                SLOTS.join(", ")
+            );
+        }
+    }
+    if !slots(&template).any(|(_, name)| name == "variants") {
+        bail!(
+            "template {} has no `{{{{variants}}}}` slot for the pages of the variants",
+            path.display()
+        );
+    }
+    Ok(template)
+}
+
+/// Replaces the slots of the template with their contents, leaving out slots without any.
+///
+/// The contents are inserted as they are, so slots within them are not replaced.
+pub(crate) fn fill(template: &str, contents: &[(&str, String)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut end = 0;
+    for (range, name) in slots(template) {
+        filled.push_str(&template[end..range.start]);
+        if let Some((_, content)) = contents.iter().find(|(slot, _)| *slot == name) {
+            filled.push_str(content);
+        }
+        end = range.end;
+    }
+    filled.push_str(&template[end..]);
+    filled
+}
+
+/// The slots in the template, with their positions.
+fn slots(template: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    let mut offset = 0;
+    std::iter::from_fn(move || loop {
+        let start = offset + template[offset..].find("{{")?;
+        offset = start + 2;
+        let (name, _) = template[offset..].split_once("}}")?;
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '-') {
+            offset += name.len() + 2;
+            return Some((start..offset, name));
+        }
+    })
+}
@@ -0,0 +1,212 @@
+//! The time spent in each phase of a comparison, summarized at the end of the run.
+//!
+//! The fonts are compiled in parallel, so the time of their phases is summed over all threads
+//! and can exceed the time the run took. With `--trace`, each phase and font is also recorded
+//! as a span on its thread, written in the Chrome trace format that Perfetto and
+//! `chrome://tracing` display as a timeline.
+
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{Context, Result};
+use serde_json::{json, Value};
+use typst::text::FontInfo;
+
+use crate::logging::{Event, Level};
+
+/// The number of fonts listed as the slowest.
+const SLOWEST: usize = 5;
+
+/// A phase of a comparison.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Phase {
+    /// Searching and reading the font files.
+    FontScan,
+    /// Compiling the document and the specimens with each font.
+    Compile,
+    /// Rasterizing the pages and encoding them as PNG.
+    Encode,
+    /// Compiling the collection and the other documents around the renders.
+    Collection,
+    /// Exporting the PDF and writing the outputs.
+    Write,
+}
+
+impl Phase {
+    const ALL: [Phase; 5] = [
+        Phase::FontScan,
+        Phase::Compile,
+        Phase::Encode,
+        Phase::Collection,
+        Phase::Write,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Phase::FontScan => "font scan",
+            Phase::Compile => "compiling fonts",
+            Phase::Encode => "encoding images",
+            Phase::Collection => "compiling collection",
+            Phase::Write => "writing outputs",
+        }
+    }
+}
+
+struct Timings {
+    phases: [Duration; Phase::ALL.len()],
+    /// The time each font took from the start of its compilation until its renders were done.
+    fonts: Vec<(FontInfo, Duration)>,
+    /// When the trace started, if one is recorded.
+    trace_start: Option<Instant>,
+    spans: Vec<Span>,
+}
+
+/// A span of the trace.
+struct Span {
+    name: String,
+    category: &'static str,
+    start: Instant,
+    duration: Duration,
+    thread: usize,
+    args: Value,
+}
+
+static TIMINGS: Mutex<Timings> = Mutex::new(Timings {
+    phases: [Duration::ZERO; Phase::ALL.len()],
+    fonts: Vec::new(),
+    trace_start: None,
+    spans: Vec::new(),
+});
+
+/// The number of threads that recorded a span so far.
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// A small number identifying the current thread in the trace.
+    static THREAD: usize = THREADS.fetch_add(1, Ordering::Relaxed);
+}
+
+impl Timings {
+    /// Adds a span to the trace, if one is recorded.
+    fn span(&mut self, name: String, category: &'static str, start: Instant, args: Value) {
+        if self.trace_start.is_some() {
+            self.spans.push(Span {
+                name,
+                category,
+                start,
+                duration: start.elapsed(),
+                thread: THREAD.with(|&thread| thread),
+                args,
+            });
+        }
+    }
+}
+
+/// Starts recording the spans of a trace.
+pub(crate) fn start_trace() {
+    TIMINGS.lock().unwrap().trace_start = Some(Instant::now());
+}
+
+/// Runs `f` and adds the time it took to the phase.
+pub(crate) fn time<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    let mut timings = TIMINGS.lock().unwrap();
+    timings.phases[phase as usize] += start.elapsed();
+    timings.span(phase.name().to_owned(), "phase", start, Value::Null);
+    result
+}
+
+/// Records how long a font took since it started compiling.
+pub(crate) fn font(font: &FontInfo, start: Instant) {
+    let mut timings = TIMINGS.lock().unwrap();
+    timings.fonts.push((font.clone(), start.elapsed()));
+    let args = json!({
+        "family": font.family,
+        "variant": format!("{:?}", font.variant),
+    });
+    timings.span(
+        format!("{} {:?}", font.family, font.variant),
+        "font",
+        start,
+        args,
+    );
+}
+
+/// Writes the recorded spans as a trace in the Chrome trace format.
+pub(crate) fn write_trace(path: &Path) -> Result<()> {
+    let timings = TIMINGS.lock().unwrap();
+    let Some(trace_start) = timings.trace_start else {
+        return Ok(());
+    };
+    let micros = |duration: Duration| duration.as_secs_f64() * 1e6;
+    let events: Vec<Value> = timings
+        .spans
+        .iter()
+        .map(|span| {
+            json!({
+                "name": span.name,
+                "cat": span.category,
+                "ph": "X",
+                "ts": micros(span.start.saturating_duration_since(trace_start)),
+                "dur": micros(span.duration),
+                "pid": 1,
+                "tid": span.thread,
+                "args": span.args,
+            })
+        })
+        .collect();
+    let trace = json!({ "traceEvents": events, "displayTimeUnit": "ms" });
+    std::fs::write(path, serde_json::to_string(&trace)?)
+        .wrap_err_with(|| format!("failed to write trace {}", path.display()))
+}
+
+/// Prints the time spent in each phase and the slowest fonts.
+pub(crate) fn summarize(total: Duration) {
+    let mut timings = TIMINGS.lock().unwrap();
+    timings
+        .fonts
+        .sort_by_key(|&(_, duration)| std::cmp::Reverse(duration));
+    let slowest = &timings.fonts[..timings.fonts.len().min(SLOWEST)];
+
+    let mut message = String::from("Timings:");
+    for phase in Phase::ALL {
+        let duration = timings.phases[phase as usize];
+        message += &format!("\n  {:<22}{duration:>10.2?}", phase.name());
+    }
+    message += &format!("\n  {:<22}{total:>10.2?}", "total");
+    if !slowest.is_empty() {
+        message += "\nSlowest fonts:";
+    }
+    for (font, duration) in slowest {
+        message += &format!("\n  {duration:>10.2?}  {} {:?}", font.family, font.variant);
+    }
+
+    let mut event = Event::new(Level::Info, "timings", message);
+    for phase in Phase::ALL {
+        event = event.duration(
+            &phase.name().replace(' ', "_"),
+            timings.phases[phase as usize],
+        );
+    }
+    let slowest: Vec<Value> = slowest
+        .iter()
+        .map(|(font, duration)| {
+            json!({
+                "family": font.family,
+                "variant": format!("{:?}", font.variant),
+                "duration": duration.as_secs_f64(),
+            })
+        })
+        .collect();
+    event
+        .duration("total", total)
+        .field("slowest", slowest)
+        .emit();
+}
@@ -2,23 +2,31 @@
 
 use std::{
     collections::{hash_map::Entry, HashMap},
-    fs, io,
+    fmt, fs, io,
     path::{Component, Path, PathBuf},
     sync::{Arc, Mutex, OnceLock},
 };
 
 use color_eyre::eyre::{eyre, Result};
 use comemo::Prehashed;
+use rayon::prelude::*;
 use time::{OffsetDateTime, UtcOffset};
+use tiny_skia::Pixmap;
 use typst::{
-    diag::{eco_format, FileError, FileResult},
-    foundations::{Bytes, Datetime},
-    syntax::{FileId, Source, VirtualPath},
-    text::{Font, FontBook, FontInfo},
+    diag::{eco_format, FileError, FileResult, PackageError},
+    foundations::{Bytes, Datetime, Dict, Smart},
+    model::ParElem,
+    syntax::{package::PackageSpec, FileId, LinkedNode, Source, SyntaxKind, VirtualPath},
+    text::{Coverage, Font, FontBook, FontInfo, Hyphenate, TextElem},
     Library, World,
 };
 
-use crate::Args;
+use crate::{
+    font_index::{self, FontIndex},
+    font_names, limits,
+    sandbox::Sandbox,
+    source_date, Args,
+};
 
 #[derive(Clone)]
 pub(crate) struct SystemWorld {
@@ -27,76 +35,98 @@ pub(crate) struct SystemWorld {
 
     root: PathBuf,
     main: FileId,
-    fonts: Arc<Vec<FontSlot>>,
+    /// A virtual source that replaces the main file, e.g. for specimens.
+    main_source: Option<Source>,
+    fonts: Arc<FontStore>,
     files: Arc<Mutex<HashMap<FileId, Bytes>>>,
+    /// Parsed sources, shared between all clones so that each file is only parsed once.
+    sources: Arc<Mutex<HashMap<FileId, Source>>>,
+    /// Virtual files that are provided on demand instead of being cached in `files`.
+    provided: Arc<HashMap<FileId, FileData>>,
+    /// Placeholders like `{{family}}` and their replacements, applied to the loaded sources.
+    placeholders: Arc<Vec<(String, String)>>,
+    /// The sources with the placeholders replaced, which are specific to this world.
+    substituted: Arc<Mutex<HashMap<FileId, Source>>>,
+    /// Restricts the files the document may read, with `--sandbox`.
+    sandbox: Option<Arc<Sandbox>>,
+    /// Whether downloading is forbidden with `--offline`.
+    offline: bool,
 }
 
 impl SystemWorld {
     pub(crate) fn new(args: &Args) -> Result<SystemWorld> {
-        let mut font_db = fontdb::Database::new();
-        for path in &args.font_paths {
-            font_db.load_fonts_dir(path);
-        }
-        font_db.load_system_fonts();
-
-        let mut book = FontBook::new();
-        let mut fonts = Vec::new();
-        for face in font_db.faces() {
-            let info = font_db
-                .with_face_data(face.id, FontInfo::new)
-                .ok_or_else(|| eyre!("failed to load font file"))?;
-            if let Some(info) = info {
-                book.push(info);
-                fonts.push(FontSlot {
-                    index: face.index,
-                    source: Some(face.source.clone()),
-                    font: OnceLock::new(),
-                });
-            }
-        }
-        #[cfg(feature = "embed-fonts")]
-        for data in typst_assets::fonts() {
-            let buffer = Bytes::from_static(data);
-            for (i, font) in Font::iter(buffer).enumerate() {
-                book.push(font.info().clone());
-                fonts.push(FontSlot {
-                    index: i as u32,
-                    source: None,
-                    font: OnceLock::from(Some(font)),
-                });
-            }
-        }
-
-        let root = args
+        // Without fallback, Typst only picks fonts by name, so the characters covered by
+        // unselected fonts don't have to be read.
+        let is_selected = args.fonts.font_filter()?;
+        let mut world = SystemWorld::with_fonts(
+            &args.fonts.font_paths,
+            args.fonts.sources(),
+            (!args.fallback).then_some(&is_selected),
+        )?;
+        world.library = Prehashed::new(library(args, Dict::new()));
+        world.root = args
             .root
             .clone()
-            .or_else(|| Some(args.input.canonicalize().ok()?.parent()?.to_path_buf()))
+            .or_else(|| Some(args.input().canonicalize().ok()?.parent()?.to_path_buf()))
             .unwrap_or_else(|| PathBuf::from("."));
-        let main = {
-            let input = args.input.canonicalize()?;
-            let vpath = VirtualPath::within_root(&input, &root)
+        world.main = {
+            let input = args.input().canonicalize()?;
+            let vpath = VirtualPath::within_root(&input, &world.root)
                 .ok_or_else(|| eyre!("input file is outside root directory"))?;
             FileId::new(None, vpath)
         };
+        if args.sandbox {
+            world.sandbox = Some(Arc::new(Sandbox::new(&world.root)?));
+        }
+        world.offline = args.offline;
+        Ok(world)
+    }
 
+    /// Creates a world rooted in the current directory without a main file, which has to be
+    /// provided with [`SystemWorld::with_main_source`].
+    pub(crate) fn without_input(
+        font_paths: &[PathBuf],
+        sources: FontSources,
+    ) -> Result<SystemWorld> {
+        SystemWorld::with_fonts(font_paths, sources, None)
+    }
+
+    /// Creates a world without a main file whose fonts that aren't `selected` are only read
+    /// partially.
+    fn with_fonts(
+        font_paths: &[PathBuf],
+        sources: FontSources,
+        selected: Option<&Selected<'_>>,
+    ) -> Result<SystemWorld> {
+        let (book, fonts) = search_fonts(font_paths, sources, selected)?;
         let library = Library::builder().build();
 
         Ok(SystemWorld {
             library: Prehashed::new(library),
             book: Arc::new(Prehashed::new(book)),
-            root,
-            main,
+            root: PathBuf::from("."),
+            main: FileId::new(None, VirtualPath::new("typst-font-compare.typ")),
+            main_source: None,
             fonts: Arc::new(fonts),
             files: Arc::new(Mutex::new(HashMap::new())),
+            sources: Arc::new(Mutex::new(HashMap::new())),
+            provided: Arc::new(HashMap::new()),
+            placeholders: Arc::new(Vec::new()),
+            substituted: Arc::new(Mutex::new(HashMap::new())),
+            sandbox: None,
+            offline: false,
         })
     }
 
-    /// Replaces all files with a number of virtual files.
+    /// Replaces all files with a number of virtual files, which are provided without caching them.
+    ///
+    /// Renders that are still pixels are encoded when the compilation first accesses them.
     pub(crate) fn replace_files<I>(&mut self, main: String, new_files: I)
     where
-        I: IntoIterator<Item = (PathBuf, Bytes)>,
+        I: IntoIterator<Item = (PathBuf, FileData)>,
     {
         let mut files = self.files.lock().unwrap();
+        self.sources.lock().unwrap().clear();
 
         self.root = PathBuf::from_iter([Component::RootDir.as_ref(), Path::new("virtual")]);
         self.main = {
@@ -105,11 +135,133 @@ impl SystemWorld {
             file_id
         };
 
-        for (path, content) in new_files {
-            let file_id = FileId::new(None, VirtualPath::new(&path));
-            files.insert(file_id, content);
+        self.provided = Arc::new(
+            new_files
+                .into_iter()
+                .map(|(path, content)| (FileId::new(None, VirtualPath::new(&path)), content))
+                .collect(),
+        );
+    }
+
+    /// The error for a package that isn't in the local package directories.
+    ///
+    /// Packages are never downloaded, but with `--offline` the error says that this is on
+    /// purpose, instead of the package merely not being found.
+    fn missing_package(&self, spec: &PackageSpec, dir: PathBuf) -> FileError {
+        if self.offline {
+            FileError::Package(PackageError::Other(Some(eco_format!(
+                "{spec} is not installed, and --offline forbids downloading it"
+            ))))
+        } else {
+            FileError::NotFound(dir)
+        }
+    }
+
+    /// The sandbox restricting the files the document may read, if any.
+    pub(crate) fn sandbox(&self) -> Option<&Sandbox> {
+        self.sandbox.as_deref()
+    }
+
+    /// Returns the index of the font Typst selects for the given family and variant.
+    pub(crate) fn font_index(&self, info: &FontInfo) -> Option<usize> {
+        self.book.select(&info.family.to_lowercase(), info.variant)
+    }
+
+    /// Returns technical information about the file a font was loaded from.
+    pub(crate) fn font_file(&self, index: usize) -> &FontFile {
+        &self.fonts.slots[index].file
+    }
+
+    /// Returns the path of the main file within the root, starting with a slash.
+    pub(crate) fn main_path(&self) -> String {
+        self.main
+            .vpath()
+            .as_rooted_path()
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Returns a copy of this world whose main file is a virtual source starting with the
+    /// prelude, followed by the text of the current main file.
+    pub(crate) fn with_prelude(&self, prelude: &str) -> FileResult<SystemWorld> {
+        let main = self.source(self.main)?;
+        Ok(self.with_main_source(format!("{prelude}{}", main.text())))
+    }
+
+    /// Returns a copy of this world whose library provides the inputs through `sys.inputs`.
+    pub(crate) fn with_inputs(&self, args: &Args, inputs: Dict) -> SystemWorld {
+        SystemWorld {
+            library: Prehashed::new(library(args, inputs)),
+            ..self.clone()
         }
     }
+
+    /// Returns a copy of this world that replaces the placeholders in the sources of the
+    /// project it loads, except in its virtual main source.
+    ///
+    /// The replacements are inserted before parsing, escaped for where they appear.
+    pub(crate) fn with_placeholders(&self, placeholders: Vec<(String, String)>) -> SystemWorld {
+        SystemWorld {
+            placeholders: Arc::new(placeholders),
+            substituted: Arc::new(Mutex::new(HashMap::new())),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of this world whose main file is a virtual source with the given text.
+    pub(crate) fn with_main_source(&self, text: String) -> SystemWorld {
+        let id = FileId::new(None, VirtualPath::new("typst-font-compare.typ"));
+        SystemWorld {
+            main: id,
+            main_source: Some(Source::new(id, text)),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a source with its placeholders substituted, or the main source of a variant.
+    fn read_source(&self, id: FileId) -> FileResult<Source> {
+        if let Some(source) = self.main_source.as_ref().filter(|source| source.id() == id) {
+            return Ok(source.clone());
+        }
+        let source = self.load_source(id)?;
+        // Packages are left as they are, as their placeholders aren't the document's.
+        if self.placeholders.is_empty() || id.package().is_some() || !source.text().contains("{{") {
+            return Ok(source);
+        }
+        let mut substituted = self.substituted.lock().unwrap();
+        let source = substituted
+            .entry(id)
+            .or_insert_with(|| Source::new(id, substitute(&source, &self.placeholders)));
+        Ok(source.clone())
+    }
+
+    /// Loads and parses a source, or returns it from the cache shared between all clones.
+    fn load_source(&self, id: FileId) -> FileResult<Source> {
+        if let Some(source) = self.sources.lock().unwrap().get(&id) {
+            return Ok(source.clone());
+        }
+        let bytes = self.file(id)?;
+        let string = String::from_utf8(bytes.to_vec()).map_err(|_| FileError::InvalidUtf8)?;
+        let source = Source::new(id, string);
+        self.sources.lock().unwrap().insert(id, source.clone());
+        Ok(source)
+    }
+}
+
+/// Builds the standard library with the inputs of `sys.inputs` and the styles set on the
+/// command line as its defaults, which the document can still override.
+fn library(args: &Args, inputs: Dict) -> Library {
+    let mut library = Library::builder().with_inputs(inputs).build();
+    if let Some(lang) = args.hyphenate_lang {
+        library.styles.set(TextElem::set_lang(lang));
+        library
+            .styles
+            .set(TextElem::set_hyphenate(Hyphenate(Smart::Custom(true))));
+    }
+    if let Some(leading) = args.leading {
+        library.styles.set(ParElem::set_leading(leading));
+    }
+    library
 }
 
 impl World for SystemWorld {
@@ -122,16 +274,19 @@ impl World for SystemWorld {
     }
 
     fn main(&self) -> Source {
-        self.source(self.main).unwrap()
+        self.read_source(self.main).unwrap()
     }
 
     fn source(&self, id: FileId) -> FileResult<Source> {
-        let bytes = self.file(id)?;
-        let string = String::from_utf8(bytes.to_vec()).map_err(|_| FileError::InvalidUtf8)?;
-        Ok(Source::new(id, string))
+        limits::check()?;
+        self.read_source(id)
     }
 
     fn file(&self, id: FileId) -> FileResult<Bytes> {
+        limits::check()?;
+        if let Some(data) = self.provided.get(&id) {
+            return data.bytes();
+        }
         match self.files.lock().unwrap().entry(id) {
             Entry::Occupied(entry) => Ok(entry.get().clone()),
             Entry::Vacant(entry) => {
@@ -154,17 +309,14 @@ impl World for SystemWorld {
                         .filter(|data_dir| data_dir.join(&package_dir).exists())
                         .or_else(dirs::cache_dir)
                         .filter(|cache_dir| cache_dir.join(&package_dir).exists())
-                        .ok_or(FileError::NotFound(package_dir))?;
+                        .ok_or_else(|| self.missing_package(spec, package_dir))?;
                 }
 
-                let path = id.vpath().resolve(&root).ok_or(FileError::AccessDenied)?;
-                let bytes = fs::read(&path)
-                    .map(Bytes::from)
-                    .map_err(|err| match err.kind() {
-                        io::ErrorKind::NotFound => FileError::NotFound(path),
-                        io::ErrorKind::PermissionDenied => FileError::AccessDenied,
-                        _ => FileError::Other(Some(eco_format!("{}", err))),
-                    })?;
+                let mut path = id.vpath().resolve(&root).ok_or(FileError::AccessDenied)?;
+                if let Some(sandbox) = &self.sandbox {
+                    path = sandbox.check(&path)?;
+                }
+                let bytes = read(&path)?;
                 entry.insert(bytes.clone());
                 Ok(bytes)
             }
@@ -172,7 +324,8 @@ impl World for SystemWorld {
     }
 
     fn font(&self, index: usize) -> Option<Font> {
-        let slot = &self.fonts[index];
+        limits::check().ok()?;
+        let slot = &self.fonts.slots[index];
         slot.font
             .get_or_init(|| {
                 let bytes = match slot
@@ -183,7 +336,7 @@ impl World for SystemWorld {
                     fontdb::Source::Binary(bytes) | fontdb::Source::SharedFile(_, bytes) => {
                         Bytes::from((**bytes).as_ref())
                     }
-                    fontdb::Source::File(path) => Bytes::from(fs::read(path).ok()?),
+                    fontdb::Source::File(path) => self.fonts.read(path)?,
                 };
                 Font::new(bytes, slot.index)
             })
@@ -191,7 +344,7 @@ impl World for SystemWorld {
     }
 
     fn today(&self, offset: Option<i64>) -> Option<Datetime> {
-        let mut now = OffsetDateTime::now_utc();
+        let mut now = source_date::get().unwrap_or_else(OffsetDateTime::now_utc);
         if let Some(offset) = offset {
             now = now.to_offset(UtcOffset::from_hms(offset.try_into().ok()?, 0, 0).ok()?);
         }
@@ -199,8 +352,597 @@ impl World for SystemWorld {
     }
 }
 
+/// Replaces the placeholders in the text of a source.
+///
+/// The values are escaped for where the placeholders appear: in strings and markup they are
+/// inserted as text, in code as a string, and in raw text as they are.
+fn substitute(source: &Source, placeholders: &[(String, String)]) -> String {
+    let root = LinkedNode::new(source.root());
+    let text = source.text();
+    let mut substituted = String::with_capacity(text.len());
+    let mut start = 0;
+    while let Some(found) = text[start..].find("{{") {
+        let offset = start + found;
+        let Some((placeholder, value)) = placeholders
+            .iter()
+            .find(|(placeholder, _)| text[offset..].starts_with(placeholder.as_str()))
+        else {
+            substituted.push_str(&text[start..=offset]);
+            start = offset + 1;
+            continue;
+        };
+        substituted.push_str(&text[start..offset]);
+        substituted.push_str(&escape(&root, offset, value));
+        start = offset + placeholder.len();
+    }
+    substituted.push_str(&text[start..]);
+    substituted
+}
+
+/// Escapes a value for the syntax at an offset of a source.
+fn escape(root: &LinkedNode, offset: usize, value: &str) -> String {
+    let string = || value.replace('\\', "\\\\").replace('"', "\\\"");
+    let Some(leaf) = root.leaf_at(offset + 1) else {
+        return value.to_owned();
+    };
+    let in_raw = std::iter::successors(Some(&leaf), |node| node.parent())
+        .any(|node| node.kind() == SyntaxKind::Raw);
+    match leaf.kind() {
+        SyntaxKind::Str => string(),
+        _ if in_raw => value.to_owned(),
+        SyntaxKind::Text => value
+            .chars()
+            .flat_map(|c| {
+                let escaped = "\\*_#[]$<>@`~=-+/".contains(c);
+                escaped.then_some('\\').into_iter().chain([c])
+            })
+            .collect(),
+        _ => format!("\"{}\"", string()),
+    }
+}
+
+/// Reads a file from disk.
+fn read(path: &Path) -> FileResult<Bytes> {
+    fs::read(path)
+        .map(Bytes::from)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => FileError::NotFound(path.to_owned()),
+            io::ErrorKind::PermissionDenied => FileError::AccessDenied,
+            _ => FileError::Other(Some(eco_format!("{}", err))),
+        })
+}
+
+/// The content of a virtual file.
+#[derive(Clone)]
+pub(crate) enum FileData {
+    Bytes(Bytes),
+    /// The content is read from this path whenever the file is accessed.
+    Path(PathBuf),
+    /// A render that is encoded as a PNG when it is first accessed.
+    Pixmap(Arc<LazyPng>),
+}
+
+impl FileData {
+    /// A render that is only encoded when it is first accessed.
+    pub(crate) fn pixmap(pixmap: Pixmap) -> FileData {
+        FileData::Pixmap(Arc::new(LazyPng {
+            pixmap: Mutex::new(Some(pixmap)),
+            png: OnceLock::new(),
+        }))
+    }
+
+    /// Returns the content, reading it from disk or encoding it if necessary.
+    pub(crate) fn bytes(&self) -> FileResult<Bytes> {
+        match self {
+            FileData::Bytes(bytes) => Ok(bytes.clone()),
+            FileData::Path(path) => read(path),
+            FileData::Pixmap(lazy) => lazy.encode(),
+        }
+    }
+}
+
+/// The pixels of a render until they are encoded, after which only the PNG is kept.
+pub(crate) struct LazyPng {
+    pixmap: Mutex<Option<Pixmap>>,
+    png: OnceLock<Bytes>,
+}
+
+impl LazyPng {
+    /// Encodes the pixels once, returning the PNG.
+    fn encode(&self) -> FileResult<Bytes> {
+        if let Some(png) = self.png.get() {
+            return Ok(png.clone());
+        }
+        let mut pixmap = self.pixmap.lock().unwrap();
+        if let Some(png) = self.png.get() {
+            return Ok(png.clone());
+        }
+        let png = pixmap
+            .as_ref()
+            .expect("expected the pixels to be kept until they are encoded")
+            .encode_png()
+            .map_err(|err| FileError::Other(Some(eco_format!("failed to encode render: {err}"))))?;
+        let png = self.png.get_or_init(|| Bytes::from(png));
+        *pixmap = None;
+        Ok(png.clone())
+    }
+}
+
+/// Where fonts are loaded from besides the directories given with `--font-path`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FontSources {
+    /// The fonts installed on the system.
+    pub(crate) system: bool,
+    /// The fonts bundled with the Typst compiler, like Linux Libertine and New Computer Modern,
+    /// of which a build without the `embed-fonts` feature has none.
+    #[cfg_attr(not(feature = "embed-fonts"), allow(dead_code))]
+    pub(crate) embedded: bool,
+}
+
+/// Finds the fonts in the given directories and the other sources, together with the
+/// information about the file of each face in the book.
+pub(crate) fn font_files(
+    font_paths: &[PathBuf],
+    sources: FontSources,
+) -> Result<(FontBook, Vec<FontFile>)> {
+    let (book, store) = search_fonts(font_paths, sources, None)?;
+    Ok((
+        book,
+        store.slots.into_iter().map(|slot| slot.file).collect(),
+    ))
+}
+
+/// Whether a font is selected for the comparison.
+type Selected<'a> = dyn Fn(&FontInfo, &FontFile) -> bool + Sync + 'a;
+
+/// Loads the information of the fonts in the given directories and of the chosen other
+/// sources.
+///
+/// If only some fonts are `selected`, the others lack their coverage.
+fn search_fonts(
+    font_paths: &[PathBuf],
+    sources: FontSources,
+    selected: Option<&Selected<'_>>,
+) -> Result<(FontBook, FontStore)> {
+    let mut font_index = FontIndex::load();
+    let mut font_db = fontdb::Database::new();
+    for path in font_paths {
+        load_fonts_dir(&mut font_db, &font_index, path);
+    }
+    if sources.system {
+        for dir in system_font_dirs() {
+            load_fonts_dir(&mut font_db, &font_index, &dir);
+        }
+    }
+
+    // The faces of a collection are consecutive, so each file is read by a single thread once.
+    let faces: Vec<&fontdb::FaceInfo> = font_db.faces().collect();
+    let files: Vec<&[&fontdb::FaceInfo]> = faces
+        .chunk_by(|a, b| same_file(&a.source, &b.source))
+        .collect();
+    for faces in &files {
+        if let fontdb::Source::File(path) | fontdb::Source::SharedFile(path, _) = &faces[0].source {
+            font_index.list(path, faces.iter().map(|face| face.index).collect());
+        }
+    }
+    let loaded: Vec<(&fontdb::FaceInfo, Option<Loaded>)> = files
+        .into_par_iter()
+        .flat_map_iter(|faces| {
+            faces
+                .iter()
+                .copied()
+                .zip(load_faces(faces, &font_index, selected))
+        })
+        .collect();
+
+    let mut book = FontBook::new();
+    let mut fonts = Vec::new();
+    let mut files = HashMap::new();
+    for (face, loaded) in loaded {
+        let loaded = loaded.ok_or_else(|| eyre!("failed to load font file"))?;
+        if let Some(path) = loaded.fresh {
+            font_index.insert(path, face.index, loaded.entry.clone());
+            if let Some(data) = loaded.data {
+                files.insert(path.to_owned(), data);
+            }
+        }
+        if let Some((info, file)) = loaded.entry {
+            book.push(info);
+            fonts.push(FontSlot {
+                index: face.index,
+                source: Some(face.source.clone()),
+                file,
+                font: OnceLock::new(),
+            });
+        }
+    }
+    font_index.save();
+
+    #[cfg(feature = "embed-fonts")]
+    for data in typst_assets::fonts().filter(|_| sources.embedded) {
+        let buffer = Bytes::from_static(data);
+        for (i, font) in Font::iter(buffer).enumerate() {
+            book.push(font.info().clone());
+            fonts.push(FontSlot {
+                index: i as u32,
+                source: None,
+                file: FontFile::new(None, data, i as u32),
+                font: OnceLock::from(Some(font)),
+            });
+        }
+    }
+    Ok((
+        book,
+        FontStore {
+            slots: fonts,
+            files: Mutex::new(files),
+        },
+    ))
+}
+
+/// Adds the font files in a directory and its subdirectories to the database.
+///
+/// Files that are unchanged since fontdb last scanned them get the faces listed in the index,
+/// without being read.
+fn load_fonts_dir(font_db: &mut fontdb::Database, font_index: &FontIndex, dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            load_fonts_dir(font_db, font_index, &path);
+            continue;
+        }
+        let is_font = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                ["ttf", "ttc", "otf", "otc"]
+                    .iter()
+                    .any(|font| ext.eq_ignore_ascii_case(font))
+            });
+        if !is_font || !path.is_file() {
+            continue;
+        }
+        let Some(listed) = font_index.listed(&path) else {
+            let _ = font_db.load_font_file(&path);
+            continue;
+        };
+        // Only the source and index are used, the rest is read by `load_faces`.
+        for &index in listed {
+            font_db.push_face_info(fontdb::FaceInfo {
+                id: fontdb::ID::dummy(),
+                source: fontdb::Source::File(path.clone()),
+                index,
+                families: Vec::new(),
+                post_script_name: String::new(),
+                style: fontdb::Style::Normal,
+                weight: fontdb::Weight::NORMAL,
+                stretch: fontdb::Stretch::Normal,
+                monospaced: false,
+            });
+        }
+    }
+}
+
+/// The directories fontdb's `load_system_fonts` scans for the fonts of the system.
+fn system_font_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    #[cfg(target_os = "windows")]
+    {
+        dirs.push(PathBuf::from("C:\\Windows\\Fonts\\"));
+        if let Some(home) = std::env::var_os("USERPROFILE") {
+            let home = Path::new(&home);
+            dirs.push(home.join("AppData\\Local\\Microsoft\\Windows\\Fonts"));
+            dirs.push(home.join("AppData\\Roaming\\Microsoft\\Windows\\Fonts"));
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        dirs.push(PathBuf::from("/Library/Fonts"));
+        dirs.push(PathBuf::from("/System/Library/Fonts"));
+        // Downloadable fonts, whose location varies between releases.
+        if let Ok(entries) = fs::read_dir("/System/Library/AssetsV2") {
+            dirs.extend(
+                entries
+                    .flatten()
+                    .filter(|entry| {
+                        entry
+                            .file_name()
+                            .to_string_lossy()
+                            .starts_with("com_apple_MobileAsset_Font")
+                    })
+                    .map(|entry| entry.path()),
+            );
+        }
+        dirs.push(PathBuf::from("/Network/Library/Fonts"));
+        if let Some(home) = std::env::var_os("HOME") {
+            dirs.push(Path::new(&home).join("Library/Fonts"));
+        }
+    }
+    #[cfg(target_os = "redox")]
+    dirs.push(PathBuf::from("/ui/fonts"));
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "android"))))]
+    {
+        let mut fontconfig = fontconfig_parser::FontConfig::default();
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        if let Some(file) = std::env::var_os("FONTCONFIG_FILE") {
+            let _ = fontconfig.merge_config(Path::new(&file));
+        } else {
+            let config_home = std::env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| home.as_ref().map(|home| home.join(".config")));
+            let user = config_home.is_some_and(|config_home| {
+                fontconfig
+                    .merge_config(&config_home.join("fontconfig/fonts.conf"))
+                    .is_ok()
+            });
+            if !user {
+                let _ = fontconfig.merge_config(Path::new("/etc/fonts/local.conf"));
+            }
+            let _ = fontconfig.merge_config(Path::new("/etc/fonts/fonts.conf"));
+        }
+        for dir in fontconfig.dirs {
+            match dir.path.strip_prefix("~") {
+                Ok(relative) => dirs.extend(home.as_ref().map(|home| home.join(relative))),
+                Err(_) => dirs.push(dir.path),
+            }
+        }
+    }
+    dirs
+}
+
+/// A face loaded from its file or looked up in the font index.
+struct Loaded<'a> {
+    entry: font_index::Entry,
+    /// The path of the file if the face was read from it and is missing in the index.
+    fresh: Option<&'a Path>,
+    /// The content of the file if it was read completely for a selected face.
+    data: Option<Bytes>,
+}
+
+/// Whether two faces are stored in the same file.
+fn same_file(a: &fontdb::Source, b: &fontdb::Source) -> bool {
+    match (a, b) {
+        (fontdb::Source::File(a), fontdb::Source::File(b))
+        | (fontdb::Source::SharedFile(a, _), fontdb::Source::SharedFile(b, _)) => a == b,
+        _ => false,
+    }
+}
+
+/// Loads the faces of a file, reading it only if some of them are missing in the index.
+///
+/// Faces that aren't `selected` are only read partially, without their coverage. The content
+/// of a file read for a selected face is kept, so that it isn't read again when rendering.
+/// Without a selection, it isn't kept, as that would hold every font in memory.
+/// A face is `None` if its file can't be read.
+fn load_faces<'a>(
+    faces: &[&'a fontdb::FaceInfo],
+    font_index: &FontIndex,
+    selected: Option<&Selected<'_>>,
+) -> Vec<Option<Loaded<'a>>> {
+    let mut file: Option<Option<Bytes>> = None;
+    faces
+        .iter()
+        .map(|face| {
+            let load = |data: &[u8], path: Option<&Path>| {
+                let info = FontInfo::new(data, face.index)?;
+                Some((
+                    info,
+                    FontFile::new(path.map(Path::to_owned), data, face.index),
+                ))
+            };
+            let path = match &face.source {
+                fontdb::Source::File(path) | fontdb::Source::SharedFile(path, _) => path,
+                fontdb::Source::Binary(data) => {
+                    let entry = load((**data).as_ref(), None);
+                    return Some(Loaded {
+                        entry,
+                        fresh: None,
+                        data: None,
+                    });
+                }
+            };
+            let unselected = |info: &FontInfo, file: &FontFile| {
+                selected.is_some_and(|selected| !selected(info, file))
+            };
+            if let Some(mut entry) = font_index.get(path, face.index) {
+                // The coverage is dropped even if it is known, so that the fallback of the
+                // collection's own text doesn't depend on which fonts previous runs read.
+                if let Some((info, _)) =
+                    entry.as_mut().filter(|(info, file)| unselected(info, file))
+                {
+                    info.coverage = Coverage::from_vec(Vec::new());
+                }
+                return Some(Loaded {
+                    entry,
+                    fresh: None,
+                    data: None,
+                });
+            }
+            if let Some(entry) = font_names::read(&face.source, face.index)
+                .filter(|(info, file)| unselected(info, file))
+            {
+                return Some(Loaded {
+                    entry: Some(entry),
+                    fresh: None,
+                    data: None,
+                });
+            }
+            let (entry, data) = match &face.source {
+                fontdb::Source::SharedFile(_, data) => (load((**data).as_ref(), Some(path)), None),
+                _ => {
+                    let data = file
+                        .get_or_insert_with(|| fs::read(path).ok().map(Bytes::from))
+                        .clone()?;
+                    (load(&data, Some(path)), Some(data))
+                }
+            };
+            // Only the files of selected faces are kept, as they are about to be rendered.
+            let data = data.filter(|_| {
+                selected.is_some()
+                    && entry
+                        .as_ref()
+                        .is_some_and(|(info, file)| !unselected(info, file))
+            });
+            Some(Loaded {
+                entry,
+                fresh: Some(path),
+                data,
+            })
+        })
+        .collect()
+}
+
+/// The fonts shared between all clones of a world.
+struct FontStore {
+    slots: Vec<FontSlot>,
+    /// The contents of the font files, shared between the faces of a collection. Starts with
+    /// the files that were read completely while searching the fonts.
+    files: Mutex<HashMap<PathBuf, Bytes>>,
+}
+
+impl FontStore {
+    /// Reads a font file, or returns its content if it was read before.
+    fn read(&self, path: &Path) -> Option<Bytes> {
+        let mut files = self.files.lock().unwrap();
+        if let Some(bytes) = files.get(path) {
+            return Some(bytes.clone());
+        }
+        let bytes = Bytes::from(fs::read(path).ok()?);
+        files.insert(path.to_owned(), bytes.clone());
+        Some(bytes)
+    }
+}
+
 struct FontSlot {
     index: u32,
     source: Option<fontdb::Source>,
+    file: FontFile,
     font: OnceLock<Option<Font>>,
 }
+
+/// Technical information about a font face and the file it is stored in.
+#[derive(Clone)]
+pub(crate) struct FontFile {
+    /// The path of the file, or `None` for embedded fonts.
+    pub(crate) path: Option<PathBuf>,
+    /// The size of the whole file in bytes.
+    pub(crate) size: usize,
+    /// Whether the file is a font collection containing multiple faces.
+    pub(crate) collection: bool,
+    /// The kind of glyph outlines the face uses.
+    pub(crate) outlines: &'static str,
+    /// Whether the face has variation axes.
+    pub(crate) variable: bool,
+    /// The number of glyphs in the face.
+    pub(crate) glyphs: u16,
+    /// Which of the notable OpenType tables the face contains.
+    pub(crate) tables: Vec<&'static str>,
+    /// The PostScript name of the face, like `NotoSansCJKjp-Bold`.
+    pub(crate) postscript_name: Option<String>,
+    /// The full name of the face, like `Noto Sans CJK JP Bold`.
+    pub(crate) full_name: Option<String>,
+}
+
+impl FontFile {
+    /// The tables that are worth pointing out when they are present.
+    pub(crate) const NOTABLE_TABLES: [&'static str; 9] = [
+        "GSUB", "GPOS", "GDEF", "kern", "MATH", "COLR", "SVG", "CBDT", "sbix",
+    ];
+
+    /// The information about a face whose file wasn't read completely, but only the given
+    /// tables with its names.
+    pub(crate) fn unread(path: Option<PathBuf>, tables: &[u8]) -> FontFile {
+        let face = ttf_parser::Face::parse(tables, 0).ok();
+        FontFile {
+            path,
+            size: 0,
+            collection: false,
+            outlines: "none",
+            variable: false,
+            glyphs: 0,
+            tables: Vec::new(),
+            postscript_name: face
+                .as_ref()
+                .and_then(|face| find_name(face, ttf_parser::name_id::POST_SCRIPT_NAME)),
+            full_name: face
+                .as_ref()
+                .and_then(|face| find_name(face, ttf_parser::name_id::FULL_NAME)),
+        }
+    }
+
+    fn new(path: Option<PathBuf>, data: &[u8], index: u32) -> FontFile {
+        let face = ttf_parser::Face::parse(data, index).ok();
+        let has_table = |name: &str| {
+            let tag = ttf_parser::Tag::from_bytes_lossy(format!("{name:<4}").as_bytes());
+            face.as_ref()
+                .is_some_and(|face| face.raw_face().table(tag).is_some())
+        };
+        let outlines = if has_table("CFF2") {
+            "CFF2"
+        } else if has_table("CFF") {
+            "CFF"
+        } else if has_table("glyf") {
+            "TrueType"
+        } else {
+            "none"
+        };
+        FontFile {
+            path,
+            size: data.len(),
+            collection: data.starts_with(b"ttcf"),
+            outlines,
+            variable: face.as_ref().is_some_and(|face| face.is_variable()),
+            glyphs: face.as_ref().map_or(0, |face| face.number_of_glyphs()),
+            tables: Self::NOTABLE_TABLES
+                .into_iter()
+                .filter(|name| has_table(name))
+                .collect(),
+            postscript_name: face
+                .as_ref()
+                .and_then(|face| find_name(face, ttf_parser::name_id::POST_SCRIPT_NAME)),
+            full_name: face
+                .as_ref()
+                .and_then(|face| find_name(face, ttf_parser::name_id::FULL_NAME)),
+        }
+    }
+
+    /// The family, PostScript and full name of a face, as far as they are known.
+    pub(crate) fn names<'a>(&'a self, info: &'a FontInfo) -> impl Iterator<Item = &'a str> {
+        [
+            Some(info.family.as_str()),
+            self.postscript_name.as_deref(),
+            self.full_name.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// Finds the name with the given ID, preferring English.
+fn find_name(face: &ttf_parser::Face, id: u16) -> Option<String> {
+    let names = face.names().into_iter().filter(|name| name.name_id == id);
+    names
+        .clone()
+        .filter(|name| name.language() == ttf_parser::Language::English_UnitedStates)
+        .chain(names)
+        .find_map(|name| name.to_string())
+}
+
+/// Formats the container format, e.g. `OTF (CFF, variable)`.
+impl fmt::Display for FontFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let container = match (self.collection, self.outlines) {
+            (true, _) => "TTC",
+            (false, "CFF" | "CFF2") => "OTF",
+            (false, _) => "TTF",
+        };
+        write!(f, "{container} ({}", self.outlines)?;
+        if self.variable {
+            write!(f, ", variable")?;
+        }
+        write!(f, ")")
+    }
+}